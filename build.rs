@@ -1,4 +1,16 @@
 fn main() {
+    // The host simulator links like any other std program
+    let target = std::env::var("TARGET").unwrap_or_default();
+    if !target.starts_with("thumb") {
+        return;
+    }
+
+    // memory.x ends the flash before the settings sector of src/storage.rs
+    let out = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    std::fs::copy("memory.x", out.join("memory.x")).unwrap();
+    println!("cargo:rustc-link-search={}", out.display());
+    println!("cargo:rerun-if-changed=memory.x");
+
     println!("cargo:rustc-link-arg-bins=--nmagic");
     println!("cargo:rustc-link-arg-bins=-Tlink.x");
     println!("cargo:rustc-link-arg-bins=-Tdefmt.x");
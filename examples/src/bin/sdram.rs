@@ -9,69 +9,13 @@ use defmt::*;
 use {defmt_rtt as _, panic_probe as _};
 
 use embassy_executor::Spawner;
-use embassy_stm32::fmc::Fmc;
-use embassy_stm32::time::mhz;
 use embassy_time::Timer;
-use embedded_alloc::TlsfHeap as Heap;
 
-#[global_allocator]
-static HEAP: Heap = Heap::empty();
+use f7disco_rs::{rcc, Board};
 
-use embassy_stm32::rcc::{
-    AHBPrescaler, APBPrescaler, Hse, HseMode, Pll, PllMul, PllPDiv, PllPreDiv, PllQDiv, PllRDiv,
-    PllSource, Sysclk,
-};
 extern crate alloc;
 
 use alloc::boxed::Box;
-use core::slice;
-
-// --------------------------------------------------
-// SDRAM chip definition
-// --------------------------------------------------
-mod mt48lc4m32b2_6 {
-    use stm32_fmc::{SdramChip, SdramConfiguration, SdramTiming};
-
-    const BURST_LENGTH_1: u16 = 0x0000;
-    const BURST_TYPE_SEQUENTIAL: u16 = 0x0000;
-    const CAS_LATENCY_3: u16 = 0x0030;
-    const OPERATING_MODE_STANDARD: u16 = 0x0000;
-    const WRITEBURST_MODE_SINGLE: u16 = 0x0200;
-
-    #[derive(Clone, Copy, Debug)]
-    pub struct Mt48lc4m32b2;
-
-    impl SdramChip for Mt48lc4m32b2 {
-        const MODE_REGISTER: u16 = BURST_LENGTH_1
-            | BURST_TYPE_SEQUENTIAL
-            | CAS_LATENCY_3
-            | OPERATING_MODE_STANDARD
-            | WRITEBURST_MODE_SINGLE;
-
-        const TIMING: SdramTiming = SdramTiming {
-            startup_delay_ns: 100_000,
-            max_sd_clock_hz: 100_000_000,
-            refresh_period_ns: 15_625,
-            mode_register_to_active: 2,
-            exit_self_refresh: 7,
-            active_to_precharge: 4,
-            row_cycle: 7,
-            row_precharge: 2,
-            row_to_column: 2,
-        };
-
-        const CONFIG: SdramConfiguration = SdramConfiguration {
-            column_bits: 8,
-            row_bits: 12,
-            memory_data_width: 16,
-            internal_banks: 4,
-            cas_latency: 3,
-            write_protection: false,
-            read_burst: true,
-            read_pipe_delay_cycles: 0,
-        };
-    }
-}
 
 // --------------------------------------------------
 // Main entry
@@ -80,85 +24,8 @@ mod mt48lc4m32b2_6 {
 async fn main(_spawner: Spawner) {
     info!("SDRAM example");
 
-    let mut config = embassy_stm32::Config::default();
-    config.rcc.sys = Sysclk::PLL1_P;
-    config.rcc.ahb_pre = AHBPrescaler::DIV1;
-    config.rcc.apb1_pre = APBPrescaler::DIV4;
-    config.rcc.apb2_pre = APBPrescaler::DIV2;
-
-    // HSE is on and ready
-    config.rcc.hse = Some(Hse {
-        freq: mhz(25),
-        mode: HseMode::Oscillator,
-    });
-    config.rcc.pll_src = PllSource::HSE;
-
-    config.rcc.pll = Some(Pll {
-        prediv: PllPreDiv::DIV25,  // PLLM
-        mul: PllMul::MUL400,       // PLLN
-        divp: Some(PllPDiv::DIV2), // SYSCLK = 400/2 = 200 MHz
-        divq: Some(PllQDiv::DIV9), // PLLQ = 400/9 = 44.44 MHz
-        divr: None,
-    });
-
-    // This seems to be working, the values in the RCC.PLLSAICFGR are correct according to the debugger. Also on and ready according to CR
-    config.rcc.pllsai = Some(Pll {
-        prediv: PllPreDiv::DIV25,  // Actually ignored
-        mul: PllMul::MUL384,       // PLLN
-        divp: Some(PllPDiv::DIV8), // PLLP
-        divq: Some(PllQDiv::DIV2), // PLLQ
-        divr: Some(PllRDiv::DIV5), // PLLR
-    });
-
-    // PLLI2S
-    config.rcc.plli2s = Some(Pll {
-        prediv: PllPreDiv::DIV25,  // Actually ignored
-        mul: PllMul::MUL100,       // PLLN
-        divp: Some(PllPDiv::DIV2), // PLLP
-        divq: Some(PllQDiv::DIV2), // PLLQ
-        divr: Some(PllRDiv::DIV2), // PLLR (I2S PLLR is always 2)
-    });
-
-    let p = embassy_stm32::init(config);
-
-    // Config SDRAM
-    // ----------------------------------------------------------
-    // Configure MPU for external SDRAM (64 Mbit = 8 Mbyte)
-    // MPU is disabled by default!
-    const SDRAM_SIZE: usize = 8 * 1024 * 1024; // 8 MB
-
-    #[rustfmt::skip]
-    let mut sdram = Fmc::sdram_a12bits_d16bits_4banks_bank1(
-        p.FMC,
-        // A0-A11
-        p.PF0, p.PF1, p.PF2, p.PF3, p.PF4, p.PF5, p.PF12, p.PF13, p.PF14, p.PF15, p.PG0, p.PG1,
-        // BA0-BA1
-        p.PG4, p.PG5,
-        // D0-D15
-        p.PD14, p.PD15, p.PD0, p.PD1, p.PE7, p.PE8, p.PE9, p.PE10, p.PE11, p.PE12, p.PE13, p.PE14, p.PE15, p.PD8, p.PD9, p.PD10,
-        // NBL0 - NBL1
-        p.PE0, p.PE1,
-        p.PC3,  // SDCKE0
-        p.PG8,  // SDCLK
-        p.PG15, // SDNCAS
-        p.PH3,  // SDNE0 (!CS)
-        p.PF11, // SDRAS
-        p.PH5,  // SDNWE
-        mt48lc4m32b2_6::Mt48lc4m32b2 {},
-    );
-
-    let mut delay = embassy_time::Delay;
-
-    // Initialise controller and SDRAM
-    let ram_ptr: *mut u8 = sdram.init(&mut delay) as *mut _;
-
-    info!("SDRAM Initialized at {:x}", ram_ptr as usize);
-
-    unsafe {
-        // Convert raw pointer to slice
-        // Move Heap to SDRAM!
-        HEAP.init(ram_ptr as usize, SDRAM_SIZE)
-    };
+    // Initialises the SDRAM and moves the heap onto it, see sdram::init_heap
+    let _board = Board::init(&rcc::DEFAULT);
 
     // Dynamic Vec allocation backed by SDRAM
     let mut v: alloc::vec::Vec<u32> = alloc::vec::Vec::new();
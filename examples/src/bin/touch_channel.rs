@@ -12,11 +12,12 @@ use embassy_sync::blocking_mutex::raw::ThreadModeRawMutex;
 use embassy_sync::channel::Channel;
 use embassy_time::{Delay, Timer};
 use embedded_graphics::geometry::Point;
+use static_cell::StaticCell;
 
 use ft5336;
 //Declate a channel of 2 u32s
 static SHARED: Channel<ThreadModeRawMutex, Point, 1> = Channel::new();
-static mut DELAY: Delay = Delay;
+static DELAY: StaticCell<Delay> = StaticCell::new();
 
 use {defmt_rtt as _, panic_probe as _};
 
@@ -80,7 +81,7 @@ async fn main(spawner: Spawner) {
 
     let i2c = I2c::new_blocking(p.I2C3, p.PH7, p.PH8, Hertz(50_000), Default::default());
 
-    let delay_ref: &'static mut Delay = unsafe { &mut DELAY };
+    let delay_ref: &'static mut Delay = DELAY.init(Delay);
     let touch = ft5336::Ft5336::new(&i2c, 0x38, delay_ref).unwrap();
 
     spawner.spawn(blink(led)).unwrap();
@@ -0,0 +1,7 @@
+/* STM32F746NG. Flash sector 7 at 0x080C0000 holds the settings of
+   src/storage.rs and is left out, so the firmware cannot grow into it. */
+MEMORY
+{
+  FLASH : ORIGIN = 0x08000000, LENGTH = 768K
+  RAM   : ORIGIN = 0x20000000, LENGTH = 320K
+}
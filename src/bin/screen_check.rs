@@ -0,0 +1,99 @@
+//! Checks screen descriptions before they go to flash or the SD card.
+//!
+//! ```sh
+//! cargo run --no-default-features --features sim \
+//!     --target x86_64-unknown-linux-gnu --bin screen-check -- [--size WxH] [FILE]...
+//! ```
+//!
+//! Without files every `.toml` in `screens/` is checked. `--size` checks
+//! against another panel resolution than the 480 x 272 of the board.
+//! Exits with 1 if any description is invalid.
+
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+use embedded_graphics::geometry::Size;
+
+use f7disco_rs::framebuffer::{LCD_HEIGHT, LCD_WIDTH};
+use f7disco_rs::screens::ScreenDescription;
+
+const DEFAULT_DIR: &str = "screens";
+
+fn parse_size(s: &str) -> Option<Size> {
+    let (width, height) = s.split_once('x')?;
+    Some(Size::new(width.parse().ok()?, height.parse().ok()?))
+}
+
+fn default_files() -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(DEFAULT_DIR)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "toml") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Prints the result for `path`, returns whether it is valid
+fn check(path: &Path, size: Size) -> bool {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            return false;
+        }
+    };
+
+    match ScreenDescription::parse_for(&text, size) {
+        Ok(screen) => {
+            println!(
+                "{}: \"{}\", {} widgets",
+                path.display(),
+                screen.name,
+                screen.widgets.len()
+            );
+            true
+        }
+        Err(e) => {
+            eprintln!("{}:{}: {}", path.display(), e.line, e.message);
+            false
+        }
+    }
+}
+
+fn main() {
+    let mut size = Size::new(LCD_WIDTH as u32, LCD_HEIGHT as u32);
+    let mut files = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--size" {
+            size = match args.next().as_deref().and_then(parse_size) {
+                Some(size) => size,
+                None => {
+                    eprintln!("expected `--size WIDTHxHEIGHT`");
+                    process::exit(2);
+                }
+            };
+        } else {
+            files.push(PathBuf::from(arg));
+        }
+    }
+
+    if files.is_empty() {
+        files = default_files().unwrap_or_else(|e| {
+            eprintln!("{DEFAULT_DIR}: {e}");
+            process::exit(2);
+        });
+    }
+
+    let mut valid = true;
+    for file in &files {
+        valid &= check(file, size);
+    }
+    if !valid {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,78 @@
+//! Runs the GUI on the host and saves snapshots of the panel.
+//!
+//! ```sh
+//! cargo run --no-default-features --features sim \
+//!     --target x86_64-unknown-linux-gnu --bin sim -- [SCRIPT] [OUT_DIR] [SCREEN]
+//! ```
+//!
+//! Without arguments `sim/med_chamber.txt` is played on the firmware GUI
+//! for `screens/med_chamber.toml` and the images are written to
+//! `target/sim`. Snapshot names without an extension are saved as PNG.
+
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+use f7disco_rs::screens::{self, ScreenDescription};
+use f7disco_rs::sim::{self, NavigatorSim, Step};
+
+const DEFAULT_SCRIPT: &str = "sim/med_chamber.txt";
+const DEFAULT_OUT_DIR: &str = "target/sim";
+const DEFAULT_SCREEN: &str = "screens/med_chamber.toml";
+
+fn load_screen(path: &Path) -> std::io::Result<ScreenDescription> {
+    let text = fs::read_to_string(path)?;
+    ScreenDescription::parse(&text).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{}: {e}", path.display()),
+        )
+    })
+}
+
+fn run(script: &Path, out_dir: &Path, screen: &Path) -> std::io::Result<()> {
+    let steps = sim::load_script(script)?;
+    let description = load_screen(screen)?;
+    fs::create_dir_all(out_dir)?;
+
+    let mut panel = NavigatorSim::new(screens::operator_panel(&description))
+        .with_interlock(description.interlock());
+
+    for step in steps {
+        match step {
+            Step::Touch(point) => {
+                println!("Point {} x {}", point.x, point.y);
+                if let Some(state) = panel.touch(point) {
+                    println!("{state:?}");
+                }
+            }
+            Step::Snapshot(name) => {
+                let mut path = out_dir.join(name);
+                if path.extension().is_none() {
+                    path.set_extension("png");
+                }
+                panel.frame().save(&path)?;
+                println!("Saved {}", path.display());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let mut args = env::args_os().skip(1);
+    let script = args
+        .next()
+        .map_or_else(|| PathBuf::from(DEFAULT_SCRIPT), PathBuf::from);
+    let out_dir = args
+        .next()
+        .map_or_else(|| PathBuf::from(DEFAULT_OUT_DIR), PathBuf::from);
+    let screen = args
+        .next()
+        .map_or_else(|| PathBuf::from(DEFAULT_SCREEN), PathBuf::from);
+
+    if let Err(e) = run(&script, &out_dir, &screen) {
+        eprintln!("{}: {e}", script.display());
+        process::exit(1);
+    }
+}
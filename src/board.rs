@@ -0,0 +1,230 @@
+use defmt::{info, unwrap};
+use embassy_stm32::exti::ExtiInput;
+use embassy_stm32::gpio::{Level, Output, Pull, Speed};
+use embassy_stm32::peripherals::*;
+use embassy_stm32::Peri;
+
+use crate::display::{self, Display, DisplayPins};
+use crate::dma2d;
+use crate::gpio::ArduinoPins;
+use crate::panel::RK043FN48H;
+use crate::rcc::ClockProfile;
+use crate::sdram::{self, SdramPins};
+use crate::touch::{self, Touch, TouchDma, TouchPins};
+
+/// RMII Ethernet MAC and the pins routed to the LAN8742A PHY
+pub struct EthPeripherals {
+    pub eth: Peri<'static, ETH>,
+    pub ref_clk: Peri<'static, PA1>,
+    pub mdio: Peri<'static, PA2>,
+    pub mdc: Peri<'static, PC1>,
+    pub crs: Peri<'static, PA7>,
+    pub rx_d0: Peri<'static, PC4>,
+    pub rx_d1: Peri<'static, PC5>,
+    pub tx_d0: Peri<'static, PG13>,
+    pub tx_d1: Peri<'static, PB13>,
+    pub tx_en: Peri<'static, PG11>,
+}
+
+/// USB OTG FS on the CN13 micro-USB connector
+pub struct UsbPeripherals {
+    pub usb: Peri<'static, USB_OTG_FS>,
+    pub dp: Peri<'static, PA12>,
+    pub dm: Peri<'static, PA11>,
+}
+
+/// STM32F746G-DISCO with every on-board device brought up.
+///
+/// The heap is moved to SDRAM, LTDC drives the RK043FN48H panel and the
+/// FT5336 is ready on I2C3. Peripherals that are not used on the board
+/// itself are handed out untouched.
+pub struct Board {
+    pub display: Display,
+    pub touch: Touch,
+    pub led: Output<'static>,
+    pub button: ExtiInput<'static>,
+    pub arduino: ArduinoPins,
+    pub eth: EthPeripherals,
+    pub usb: UsbPeripherals,
+    pub rng: Peri<'static, RNG>,
+    pub flash: Peri<'static, FLASH>,
+}
+
+impl Board {
+    /// Initializes clocks, SDRAM heap, display and touch panel.
+    ///
+    /// A profile without PLLSAI gets the setting that matches the panel's
+    /// pixel clock, one with PLLSAI, e.g. [`crate::rcc::LCD_TUNED`], keeps
+    /// it. Should be called once, first thing in `main`.
+    pub fn init(profile: &ClockProfile) -> Self {
+        let panel = &RK043FN48H;
+
+        let profile = match (profile.pllsai, profile.vco_input_hz()) {
+            (_, None) => defmt::panic!("LTDC needs the main PLL to be enabled"),
+            (Some(_), Some(_)) => *profile,
+            (None, Some(vco_in)) => match panel.pllsai(vco_in) {
+                Some(pllsai) => profile.with_pllsai(pllsai),
+                None => defmt::panic!("No PLLSAI setting for the panel pixel clock"),
+            },
+        };
+        let pllsai_divr = unwrap!(profile.pllsai).divr;
+
+        let p = embassy_stm32::init(profile.to_config());
+        info!("Starting...");
+
+        // Config SDRAM
+        // ----------------------------------------------------------
+        // Configure MPU for external SDRAM (64 Mbit = 8 Mbyte)
+        // MPU is disabled by default
+        let sdram_pins = SdramPins {
+            a0: p.PF0,
+            a1: p.PF1,
+            a2: p.PF2,
+            a3: p.PF3,
+            a4: p.PF4,
+            a5: p.PF5,
+            a6: p.PF12,
+            a7: p.PF13,
+            a8: p.PF14,
+            a9: p.PF15,
+            a10: p.PG0,
+            a11: p.PG1,
+            ba0: p.PG4,
+            ba1: p.PG5,
+
+            d0: p.PD14,
+            d1: p.PD15,
+            d2: p.PD0,
+            d3: p.PD1,
+            d4: p.PE7,
+            d5: p.PE8,
+            d6: p.PE9,
+            d7: p.PE10,
+            d8: p.PE11,
+            d9: p.PE12,
+            d10: p.PE13,
+            d11: p.PE14,
+            d12: p.PE15,
+            d13: p.PD8,
+            d14: p.PD9,
+            d15: p.PD10,
+
+            nbl0: p.PE0,
+            nbl1: p.PE1,
+
+            sdcke0: p.PC3,
+            sdclk: p.PG8,
+            sdncas: p.PG15,
+            sdne0: p.PH3,
+            sdnras: p.PF11,
+            sdnwe: p.PH5,
+        };
+        sdram::init_heap(p.FMC, sdram_pins);
+
+        let display_pins = DisplayPins {
+            r0: p.PI15,
+            r1: p.PJ0,
+            r2: p.PJ1,
+            r3: p.PJ2,
+            r4: p.PJ3,
+            r5: p.PJ4,
+            r6: p.PJ5,
+            r7: p.PJ6,
+
+            g0: p.PJ7,
+            g1: p.PJ8,
+            g2: p.PJ9,
+            g3: p.PJ10,
+            g4: p.PJ11,
+            g5: p.PK0,
+            g6: p.PK1,
+            g7: p.PK2,
+
+            b0: p.PE4,
+            b1: p.PJ13,
+            b2: p.PJ14,
+            b3: p.PJ15,
+            b4: p.PG12,
+            b5: p.PK4,
+            b6: p.PK5,
+            b7: p.PK6,
+
+            hsync: p.PI10,
+            vsync: p.PI9,
+            clk: p.PI14,
+            de: p.PK7,
+
+            lcd_en: p.PI12,
+            backlight: p.PK3,
+        };
+        let display = display::init_display(display_pins, p.LTDC, panel, pllsai_divr);
+        info!("Init LTDC display");
+
+        // Drawing into frame buffers falls back to the CPU without it
+        dma2d::init(p.DMA2D);
+
+        let touch = touch::init_touch(
+            p.I2C3,
+            TouchPins {
+                scl: p.PH7,
+                sda: p.PH8,
+                int: p.PI13,
+                int_exti: p.EXTI13,
+            },
+            TouchDma {
+                tx: p.DMA1_CH4,
+                rx: p.DMA1_CH2,
+            },
+        );
+
+        let led = Output::new(p.PI1, Level::High, Speed::Low);
+        let button = ExtiInput::new(p.PI11, p.EXTI11, Pull::Down);
+
+        let arduino = ArduinoPins {
+            d0: p.PC7,
+            d1: p.PC6,
+            d2: p.PG6,
+            d3: p.PB4,
+            d4: p.PG7,
+            d5: p.PI0,
+            d6: p.PH6,
+            d7: p.PI3,
+            d8: p.PI2,
+            d9: p.PA15,
+            d10: p.PA8,
+            d11: p.PB15,
+            d12: p.PB14,
+        };
+
+        let eth = EthPeripherals {
+            eth: p.ETH,
+            ref_clk: p.PA1,
+            mdio: p.PA2,
+            mdc: p.PC1,
+            crs: p.PA7,
+            rx_d0: p.PC4,
+            rx_d1: p.PC5,
+            tx_d0: p.PG13,
+            tx_d1: p.PB13,
+            tx_en: p.PG11,
+        };
+
+        let usb = UsbPeripherals {
+            usb: p.USB_OTG_FS,
+            dp: p.PA12,
+            dm: p.PA11,
+        };
+
+        Self {
+            display,
+            touch,
+            led,
+            button,
+            arduino,
+            eth,
+            usb,
+            rng: p.RNG,
+            flash: p.FLASH,
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Touch calibration: the affine map from touch controller coordinates to
+//! panel pixels.
+//!
+//! [`CalibrationRoutine`] shows three crosshairs and records where the taps
+//! land. The taps already went through the current calibration, so the
+//! routine solves for a correction and chains it to the current
+//! calibration. A panel that was never calibrated uses
+//! [`Calibration::DEFAULT`], which only swaps the axes of the FT5336.
+//!
+//! The result is kept in flash as [`Calibration::to_bytes`], see
+//! `storage`.
+
+use core::fmt::Debug;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+use embedded_graphics::primitives::{Circle, Line, Primitive, PrimitiveStyle};
+use embedded_graphics::text::{Alignment, Text};
+use embedded_graphics::Drawable;
+use heapless::Vec;
+
+use crate::framebuffer::{LCD_HEIGHT, LCD_WIDTH};
+use crate::rotation::Rotation;
+use crate::widgets::Theme;
+
+/// Size of the panel as the LTDC scans it
+pub const PANEL_SIZE: Size = Size::new(LCD_WIDTH as u32, LCD_HEIGHT as u32);
+
+/// Fixed-point one of the coefficients
+const ONE: i64 = 1 << 16;
+
+/// `x' = (a x + b y + c) / 65536`, `y' = (d x + e y + f) / 65536`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Calibration {
+    pub a: i32,
+    pub b: i32,
+    pub c: i32,
+    pub d: i32,
+    pub e: i32,
+    pub f: i32,
+}
+
+/// Length of [`Calibration::to_bytes`]
+pub const STORED_LEN: usize = 32;
+
+const MAGIC: [u8; 4] = *b"CAL1";
+
+impl Calibration {
+    pub const IDENTITY: Self = Self {
+        a: ONE as i32,
+        b: 0,
+        c: 0,
+        d: 0,
+        e: ONE as i32,
+        f: 0,
+    };
+
+    /// The FT5336 of the STM32F746G-DISCO reports x and y swapped against
+    /// the LCD
+    pub const DEFAULT: Self = Self {
+        a: 0,
+        b: ONE as i32,
+        c: 0,
+        d: ONE as i32,
+        e: 0,
+        f: 0,
+    };
+
+    /// Panel pixel of the controller point `raw`
+    pub fn apply(&self, raw: Point) -> Point {
+        let (x, y) = (raw.x as i64, raw.y as i64);
+        let map = |a: i32, b: i32, c: i32| {
+            let v = a as i64 * x + b as i64 * y + c as i64;
+            // Rounded to the nearest pixel
+            (v + ONE / 2).div_euclid(ONE) as i32
+        };
+        Point::new(map(self.a, self.b, self.c), map(self.d, self.e, self.f))
+    }
+
+    /// `self` followed by `next`
+    pub fn then(&self, next: &Calibration) -> Calibration {
+        let mul = |p: i32, q: i32| (p as i64 * q as i64 / ONE) as i32;
+        Calibration {
+            a: mul(next.a, self.a) + mul(next.b, self.d),
+            b: mul(next.a, self.b) + mul(next.b, self.e),
+            c: mul(next.a, self.c) + mul(next.b, self.f) + next.c,
+            d: mul(next.d, self.a) + mul(next.e, self.d),
+            e: mul(next.d, self.b) + mul(next.e, self.e),
+            f: mul(next.d, self.c) + mul(next.e, self.f) + next.f,
+        }
+    }
+
+    /// The map that takes each of `measured` to the matching `targets`.
+    /// `None` if the measured points lie (almost) on a line.
+    pub fn from_samples(measured: [Point; 3], targets: [Point; 3]) -> Option<Self> {
+        let [m0, m1, m2] = measured.map(|p| (p.x as i64, p.y as i64));
+        let (dx0, dy0) = (m0.0 - m2.0, m0.1 - m2.1);
+        let (dx1, dy1) = (m1.0 - m2.0, m1.1 - m2.1);
+
+        // Twice the area of the triangle, below 100 px² the taps are too
+        // close together for a useful result
+        let det = dx0 * dy1 - dx1 * dy0;
+        if det.abs() < 200 {
+            return None;
+        }
+
+        // Cramer's rule for `v = p x + q y + r` through the three points
+        let solve = |v: [i64; 3]| {
+            let (dv0, dv1) = (v[0] - v[2], v[1] - v[2]);
+            let p = (dv0 * dy1 - dv1 * dy0) * ONE / det;
+            let q = (dx0 * dv1 - dx1 * dv0) * ONE / det;
+            let r = v[2] * ONE - p * m2.0 - q * m2.1;
+            (p as i32, q as i32, r as i32)
+        };
+        let (a, b, c) = solve(targets.map(|t| t.x as i64));
+        let (d, e, f) = solve(targets.map(|t| t.y as i64));
+
+        Some(Self { a, b, c, d, e, f })
+    }
+
+    /// Magic, the six coefficients and a checksum, little endian
+    pub fn to_bytes(&self) -> [u8; STORED_LEN] {
+        let mut bytes = [0; STORED_LEN];
+        bytes[..4].copy_from_slice(&MAGIC);
+        for (chunk, value) in bytes[4..28]
+            .chunks_exact_mut(4)
+            .zip([self.a, self.b, self.c, self.d, self.e, self.f])
+        {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        let sum = checksum(&bytes[..28]);
+        bytes[28..].copy_from_slice(&sum.to_le_bytes());
+        bytes
+    }
+
+    /// `None` for erased flash or a damaged record
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..STORED_LEN)?;
+        if bytes[..4] != MAGIC || bytes[28..] != checksum(&bytes[..28]).to_le_bytes() {
+            return None;
+        }
+
+        let mut values = bytes[4..28]
+            .chunks_exact(4)
+            .map(|chunk| i32::from_le_bytes(chunk.try_into().unwrap()));
+        let mut next = || values.next().unwrap();
+        Some(Self {
+            a: next(),
+            b: next(),
+            c: next(),
+            d: next(),
+            e: next(),
+            f: next(),
+        })
+    }
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// FNV-1a
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811C_9DC5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// From controller coordinates to the coordinates the screens draw in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Transform {
+    pub calibration: Calibration,
+    pub rotation: Rotation,
+}
+
+impl Transform {
+    pub const DEFAULT: Self = Self {
+        calibration: Calibration::DEFAULT,
+        rotation: Rotation::Deg0,
+    };
+
+    pub fn apply(&self, raw: Point) -> Point {
+        self.rotation
+            .to_logical(self.calibration.apply(raw), PANEL_SIZE)
+    }
+}
+
+/// Three crosshairs to tap, one after the other
+pub struct CalibrationRoutine {
+    rotation: Rotation,
+    /// In screen coordinates
+    targets: [Point; 3],
+    taps: Vec<Point, 3>,
+}
+
+impl CalibrationRoutine {
+    /// Crosshairs spread over the screen, as it is rotated by `rotation`
+    pub fn new(rotation: Rotation) -> Self {
+        let size = rotation.size(PANEL_SIZE);
+        let at = |x: u32, y: u32| {
+            Point::new(
+                (size.width * x / 100) as i32,
+                (size.height * y / 100) as i32,
+            )
+        };
+
+        Self {
+            rotation,
+            targets: [at(10, 15), at(90, 50), at(50, 85)],
+            taps: Vec::new(),
+        }
+    }
+
+    /// Where the next crosshair is, `None` once all were tapped
+    pub fn target(&self) -> Option<Point> {
+        self.targets.get(self.taps.len()).copied()
+    }
+
+    /// Records a tap, in screen coordinates as the touch task reports it
+    pub fn tap(&mut self, point: Point) {
+        let _ = self.taps.push(point);
+    }
+
+    /// Starts over, e.g. after [`Self::finish`] failed
+    pub fn restart(&mut self) {
+        self.taps.clear();
+    }
+
+    /// The calibration that replaces `current`, the one the taps were
+    /// reported with. `None` before all crosshairs were tapped, or if the
+    /// taps were too close together.
+    pub fn finish(&self, current: &Calibration) -> Option<Calibration> {
+        let taps: [Point; 3] = self.taps.as_slice().try_into().ok()?;
+
+        // The correction works on panel pixels, like the calibration
+        let physical = |p: Point| self.rotation.to_physical(p, PANEL_SIZE);
+        let correction = Calibration::from_samples(taps.map(physical), self.targets.map(physical))?;
+        Some(current.then(&correction))
+    }
+
+    /// The current crosshair and what to do on a blank screen
+    pub fn draw<D>(&self, target: &mut D, theme: &Theme) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+        D::Error: Debug,
+    {
+        target.clear(theme.background)?;
+
+        let size = target.bounding_box().size;
+        let text = match self.target() {
+            Some(_) => "Tap the centre of the cross",
+            None => "Calibration done",
+        };
+        Text::with_alignment(
+            text,
+            Point::new(size.width as i32 / 2, size.height as i32 / 2 - 30),
+            MonoTextStyle::new(theme.font, theme.text_color),
+            Alignment::Center,
+        )
+        .draw(target)?;
+
+        if let Some(center) = self.target() {
+            let style = PrimitiveStyle::with_stroke(theme.text_color, 1);
+            Line::new(center - Point::new(15, 0), center + Point::new(15, 0))
+                .into_styled(style)
+                .draw(target)?;
+            Line::new(center - Point::new(0, 15), center + Point::new(0, 15))
+                .into_styled(style)
+                .draw(target)?;
+            Circle::with_center(center, 17)
+                .into_styled(PrimitiveStyle::with_stroke(Rgb888::RED, 2))
+                .draw(target)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+//! Pixel colors that can be stored in a frame buffer.
+//!
+//! embedded-graphics only knows RGB colors, the LTDC also scans out formats
+//! with alpha and 8-bit CLUT indices. [`FrameColor`] ties a color type to
+//! its storage word and the matching [`PixelFormat`].
+
+use embedded_graphics::pixelcolor::raw::{RawU16, RawU32, RawU8};
+use embedded_graphics::pixelcolor::{IntoStorage, PixelColor, Rgb565, Rgb888};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum PixelFormat {
+    Argb8888,
+    Rgb888,
+    Rgb565,
+    Argb1555,
+    Argb4444,
+    L8,
+    Al44,
+    Al88,
+}
+
+impl PixelFormat {
+    pub const fn bytes_per_pixel(self) -> u16 {
+        match self {
+            PixelFormat::Argb8888 => 4,
+            PixelFormat::Rgb888 => 3,
+            PixelFormat::Rgb565
+            | PixelFormat::Argb1555
+            | PixelFormat::Argb4444
+            | PixelFormat::Al88 => 2,
+            PixelFormat::L8 | PixelFormat::Al44 => 1,
+        }
+    }
+}
+
+/// A color with a fixed in-memory layout the LTDC and DMA2D understand
+pub trait FrameColor: PixelColor {
+    /// One pixel in memory
+    type Word: Copy + Default + Into<u32> + 'static;
+    const FORMAT: PixelFormat;
+
+    fn into_word(self) -> Self::Word;
+}
+
+/// Colors that can be converted to and from ARGB8888 without a CLUT
+pub trait DirectColor: FrameColor {
+    fn word_from_argb8888(argb: u32) -> Self::Word;
+    fn word_to_argb8888(word: Self::Word) -> u32;
+}
+
+/// Extracts an 8-bit channel
+const fn channel(argb: u32, shift: u32) -> u32 {
+    (argb >> shift) & 0xFF
+}
+
+/// Expands an n-bit channel to 8 bits by repeating the high bits
+const fn expand(v: u32, bits: u32) -> u32 {
+    (v << (8 - bits)) | (v >> (2 * bits).saturating_sub(8))
+}
+
+/// Unpacks one pixel of `format` from little-endian `bytes` to ARGB8888.
+///
+/// Returns `None` for the CLUT formats.
+pub fn argb8888_from_bytes(format: PixelFormat, d: &[u8]) -> Option<u32> {
+    let argb = match format {
+        PixelFormat::Argb8888 => u32::from_le_bytes([d[0], d[1], d[2], d[3]]),
+        PixelFormat::Rgb888 => u32::from_le_bytes([d[0], d[1], d[2], 0xFF]),
+        PixelFormat::Rgb565 => Rgb565::word_to_argb8888(u16::from_le_bytes([d[0], d[1]])),
+        PixelFormat::Argb1555 => Argb1555::word_to_argb8888(u16::from_le_bytes([d[0], d[1]])),
+        PixelFormat::Argb4444 => Argb4444::word_to_argb8888(u16::from_le_bytes([d[0], d[1]])),
+        PixelFormat::L8 | PixelFormat::Al44 | PixelFormat::Al88 => return None,
+    };
+    Some(argb)
+}
+
+/// Stored as opaque ARGB8888, so overlays can still be cleared to
+/// transparent.
+impl FrameColor for Rgb888 {
+    type Word = u32;
+    const FORMAT: PixelFormat = PixelFormat::Argb8888;
+
+    fn into_word(self) -> u32 {
+        self.into_storage() | 0xFF00_0000
+    }
+}
+
+impl DirectColor for Rgb888 {
+    fn word_from_argb8888(argb: u32) -> u32 {
+        argb
+    }
+
+    fn word_to_argb8888(word: u32) -> u32 {
+        word
+    }
+}
+
+impl FrameColor for Rgb565 {
+    type Word = u16;
+    const FORMAT: PixelFormat = PixelFormat::Rgb565;
+
+    fn into_word(self) -> u16 {
+        self.into_storage()
+    }
+}
+
+impl DirectColor for Rgb565 {
+    fn word_from_argb8888(argb: u32) -> u16 {
+        ((channel(argb, 16) >> 3) << 11 | (channel(argb, 8) >> 2) << 5 | channel(argb, 0) >> 3)
+            as u16
+    }
+
+    fn word_to_argb8888(word: u16) -> u32 {
+        let v = word as u32;
+        0xFF00_0000
+            | expand(v >> 11, 5) << 16
+            | expand((v >> 5) & 0x3F, 6) << 8
+            | expand(v & 0x1F, 5)
+    }
+}
+
+/// 32-bit color with 8-bit alpha
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Argb8888(pub u32);
+
+impl Argb8888 {
+    pub const TRANSPARENT: Self = Self(0);
+
+    pub const fn new(a: u8, r: u8, g: u8, b: u8) -> Self {
+        Self((a as u32) << 24 | (r as u32) << 16 | (g as u32) << 8 | b as u32)
+    }
+}
+
+impl From<Rgb888> for Argb8888 {
+    fn from(color: Rgb888) -> Self {
+        Self(color.into_word())
+    }
+}
+
+impl PixelColor for Argb8888 {
+    type Raw = RawU32;
+}
+
+impl FrameColor for Argb8888 {
+    type Word = u32;
+    const FORMAT: PixelFormat = PixelFormat::Argb8888;
+
+    fn into_word(self) -> u32 {
+        self.0
+    }
+}
+
+impl DirectColor for Argb8888 {
+    fn word_from_argb8888(argb: u32) -> u32 {
+        argb
+    }
+
+    fn word_to_argb8888(word: u32) -> u32 {
+        word
+    }
+}
+
+/// 16-bit color with 1-bit alpha
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Argb1555(pub u16);
+
+impl Argb1555 {
+    pub const TRANSPARENT: Self = Self(0);
+
+    /// `r`, `g` and `b` are 5-bit values
+    pub const fn new(opaque: bool, r: u8, g: u8, b: u8) -> Self {
+        Self(
+            (opaque as u16) << 15
+                | (r as u16 & 0x1F) << 10
+                | (g as u16 & 0x1F) << 5
+                | (b as u16 & 0x1F),
+        )
+    }
+}
+
+impl From<Rgb888> for Argb1555 {
+    fn from(color: Rgb888) -> Self {
+        Self(Self::word_from_argb8888(color.into_word()))
+    }
+}
+
+impl PixelColor for Argb1555 {
+    type Raw = RawU16;
+}
+
+impl FrameColor for Argb1555 {
+    type Word = u16;
+    const FORMAT: PixelFormat = PixelFormat::Argb1555;
+
+    fn into_word(self) -> u16 {
+        self.0
+    }
+}
+
+impl DirectColor for Argb1555 {
+    fn word_from_argb8888(argb: u32) -> u16 {
+        let a = if channel(argb, 24) >= 0x80 { 1 } else { 0 };
+        (a << 15
+            | (channel(argb, 16) >> 3) << 10
+            | (channel(argb, 8) >> 3) << 5
+            | channel(argb, 0) >> 3) as u16
+    }
+
+    fn word_to_argb8888(word: u16) -> u32 {
+        let v = word as u32;
+        let a = if v & 0x8000 != 0 { 0xFF } else { 0 };
+        a << 24
+            | expand((v >> 10) & 0x1F, 5) << 16
+            | expand((v >> 5) & 0x1F, 5) << 8
+            | expand(v & 0x1F, 5)
+    }
+}
+
+/// 16-bit color with 4-bit alpha
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Argb4444(pub u16);
+
+impl Argb4444 {
+    pub const TRANSPARENT: Self = Self(0);
+
+    /// All channels are 4-bit values
+    pub const fn new(a: u8, r: u8, g: u8, b: u8) -> Self {
+        Self(
+            (a as u16 & 0xF) << 12
+                | (r as u16 & 0xF) << 8
+                | (g as u16 & 0xF) << 4
+                | (b as u16 & 0xF),
+        )
+    }
+}
+
+impl From<Rgb888> for Argb4444 {
+    fn from(color: Rgb888) -> Self {
+        Self(Self::word_from_argb8888(color.into_word()))
+    }
+}
+
+impl PixelColor for Argb4444 {
+    type Raw = RawU16;
+}
+
+impl FrameColor for Argb4444 {
+    type Word = u16;
+    const FORMAT: PixelFormat = PixelFormat::Argb4444;
+
+    fn into_word(self) -> u16 {
+        self.0
+    }
+}
+
+impl DirectColor for Argb4444 {
+    fn word_from_argb8888(argb: u32) -> u16 {
+        ((channel(argb, 24) >> 4) << 12
+            | (channel(argb, 16) >> 4) << 8
+            | (channel(argb, 8) >> 4) << 4
+            | channel(argb, 0) >> 4) as u16
+    }
+
+    fn word_to_argb8888(word: u16) -> u32 {
+        let v = word as u32;
+        expand(v >> 12, 4) << 24
+            | expand((v >> 8) & 0xF, 4) << 16
+            | expand((v >> 4) & 0xF, 4) << 8
+            | expand(v & 0xF, 4)
+    }
+}
+
+/// Index into the layer's color lookup table, see
+/// [`crate::layer::LtdcLayer::load_clut`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct L8(pub u8);
+
+impl PixelColor for L8 {
+    type Raw = RawU8;
+}
+
+impl FrameColor for L8 {
+    type Word = u8;
+    const FORMAT: PixelFormat = PixelFormat::L8;
+
+    fn into_word(self) -> u8 {
+        self.0
+    }
+}
+
+// Full scale has to expand to 0xFF, otherwise white turns grey
+const _: () = {
+    assert!(expand(0x1F, 5) == 0xFF && expand(0x3F, 6) == 0xFF && expand(0xF, 4) == 0xFF);
+    assert!(expand(0, 5) == 0 && expand(0x10, 5) == 0x84);
+};
@@ -0,0 +1,203 @@
+//! Dirty-rectangle tracking for double-buffered layers.
+//!
+//! Every draw call through a [`DamagedBuffer`] records the area it touched.
+//! Overlapping or adjacent rectangles are merged, so after a frame the
+//! [`Damage`] holds a few rectangles that cover everything that changed.
+//! [`crate::swapchain::FrameBufferSwapchain`] copies just those between the
+//! two buffers instead of redrawing whole frames.
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Dimensions, OriginDimensions, Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::Pixel;
+use heapless::Vec;
+
+use crate::color::{DirectColor, FrameColor};
+use crate::framebuffer::{Bitmap, DisplayBuffer, Overlay};
+
+/// Rectangles kept apart before the closest two are merged
+pub const MAX_DAMAGE_RECTS: usize = 8;
+
+/// Set of damaged rectangles
+#[derive(Clone, Debug, Default)]
+pub struct Damage {
+    rects: Vec<Rectangle, MAX_DAMAGE_RECTS>,
+}
+
+/// Smallest rectangle containing `a` and `b`
+fn union(a: &Rectangle, b: &Rectangle) -> Rectangle {
+    let (Some(a_br), Some(b_br)) = (a.bottom_right(), b.bottom_right()) else {
+        return if a.is_zero_sized() { *b } else { *a };
+    };
+
+    let top_left = a.top_left.component_min(b.top_left);
+    let bottom_right = a_br.component_max(b_br);
+    Rectangle::with_corners(top_left, bottom_right)
+}
+
+fn area(r: &Rectangle) -> u32 {
+    r.size.width * r.size.height
+}
+
+/// `a` and `b` overlap or share an edge
+fn touches(a: &Rectangle, b: &Rectangle) -> bool {
+    let grown = Rectangle::new(a.top_left - Point::new(1, 1), a.size + Size::new(2, 2));
+    !grown.intersection(b).is_zero_sized()
+}
+
+impl Damage {
+    pub const fn new() -> Self {
+        Self { rects: Vec::new() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    pub fn rects(&self) -> &[Rectangle] {
+        &self.rects
+    }
+
+    pub fn clear(&mut self) {
+        self.rects.clear();
+    }
+
+    /// Adds `rect`, merging it with every rectangle it touches.
+    ///
+    /// When all slots are used the pair whose union grows the least is
+    /// merged, so the result always covers the damage, maybe a bit more.
+    pub fn add(&mut self, rect: Rectangle) {
+        if rect.is_zero_sized() {
+            return;
+        }
+
+        let mut rect = rect;
+        while let Some(i) = self.rects.iter().position(|r| touches(r, &rect)) {
+            rect = union(&self.rects.swap_remove(i), &rect);
+        }
+
+        if let Err(rect) = self.rects.push(rect) {
+            let (i, _) = self
+                .rects
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, r)| area(&union(r, &rect)) - area(r))
+                .unwrap();
+            let merged = union(&self.rects.swap_remove(i), &rect);
+            // Growing may have made it touch others
+            self.add(merged);
+        }
+    }
+
+    /// Adds every rectangle of `other`
+    pub fn extend(&mut self, other: &Damage) {
+        for rect in other.rects() {
+            self.add(*rect);
+        }
+    }
+
+    /// Smallest rectangle containing all damage
+    pub fn bounding_box(&self) -> Rectangle {
+        self.rects
+            .iter()
+            .fold(Rectangle::zero(), |acc, r| union(&acc, r))
+    }
+}
+
+/// A frame buffer that records every area drawn to.
+///
+/// Implements `DrawTarget`, so it can be used anywhere a `DisplayBuffer` can.
+pub struct DamagedBuffer<'d, C: FrameColor> {
+    buffer: &'d mut DisplayBuffer<'static, C>,
+    damage: &'d mut Damage,
+}
+
+impl<'d, C: FrameColor> DamagedBuffer<'d, C> {
+    pub fn new(buffer: &'d mut DisplayBuffer<'static, C>, damage: &'d mut Damage) -> Self {
+        Self { buffer, damage }
+    }
+
+    /// Marks `area` as changed after writing to the buffer directly
+    pub fn mark(&mut self, area: Rectangle) {
+        self.damage
+            .add(area.intersection(&self.buffer.bounding_box()));
+    }
+
+    pub fn clear_transparent(&mut self) {
+        self.buffer.clear_transparent();
+        self.mark(self.buffer.bounding_box());
+    }
+}
+
+impl<C: DirectColor> DamagedBuffer<'_, C> {
+    pub fn blit(&mut self, bitmap: &Bitmap, at: Point) {
+        self.buffer.blit(bitmap, at);
+        self.mark(bitmap_area(bitmap, at));
+    }
+
+    pub fn blend(&mut self, bitmap: &Bitmap, at: Point, alpha: u8) {
+        self.buffer.blend(bitmap, at, alpha);
+        self.mark(bitmap_area(bitmap, at));
+    }
+}
+
+fn bitmap_area(bitmap: &Bitmap, at: Point) -> Rectangle {
+    Rectangle::new(at, Size::new(bitmap.width as u32, bitmap.height as u32))
+}
+
+impl<C: FrameColor> DrawTarget for DamagedBuffer<'_, C> {
+    type Color = C;
+    type Error = ();
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let mut min = Point::new(i32::MAX, i32::MAX);
+        let mut max = Point::new(i32::MIN, i32::MIN);
+
+        self.buffer
+            .draw_iter(pixels.into_iter().inspect(|Pixel(point, _)| {
+                min = min.component_min(*point);
+                max = max.component_max(*point);
+            }))?;
+
+        if min.x <= max.x {
+            self.mark(Rectangle::with_corners(min, max));
+        }
+        Ok(())
+    }
+
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        self.buffer.fill_contiguous(area, colors)?;
+        self.mark(*area);
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.buffer.fill_solid(area, color)?;
+        self.mark(*area);
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        DrawTarget::clear(self.buffer, color)?;
+        self.mark(self.buffer.bounding_box());
+        Ok(())
+    }
+}
+
+impl<C: FrameColor> Overlay for DamagedBuffer<'_, C> {
+    fn clear_transparent(&mut self) {
+        DamagedBuffer::clear_transparent(self);
+    }
+}
+
+impl<C: FrameColor> OriginDimensions for DamagedBuffer<'_, C> {
+    fn size(&self) -> Size {
+        self.buffer.size()
+    }
+}
@@ -1,99 +1,126 @@
 use embassy_stm32::{
-    pac::RCC,
     gpio::{AfType, Flex, Level, Output, OutputType, Speed},
     ltdc::Ltdc,
+    pac::ltdc::vals::{Depol, Hspol, Pcpol, Vspol},
+    pac::rcc::vals::Pllsaidivr,
+    pac::RCC,
+    peripherals::*,
+    Peri,
 };
 
-use embassy_stm32::peripherals::*;
+pub use crate::framebuffer::{Bitmap, DisplayBuffer, LCD_HEIGHT, LCD_WIDTH};
+use crate::layer::{LayerId, LtdcLayer};
+use crate::panel::{ClockEdge, PanelTiming, Polarity};
 
 pub struct DisplayPins {
-    pub r0: PI15,
-    pub r1: PJ0,
-    pub r2: PJ1,
-    pub r3: PJ2,
-    pub r4: PJ3,
-    pub r5: PJ4,
-    pub r6: PJ5,
-    pub r7: PJ6,
-
-    pub g0: PJ7,
-    pub g1: PJ8,
-    pub g2: PJ9,
-    pub g3: PJ10,
-    pub g4: PJ11,
-    pub g5: PK0,
-    pub g6: PK1,
-    pub g7: PK2,
-
-    pub b0: PE4,
-    pub b1: PJ13,
-    pub b2: PJ14,
-    pub b3: PJ15,
-    pub b4: PG12,
-    pub b5: PK4,
-    pub b6: PK5,
-    pub b7: PK6,
-
-    pub hsync: PI10,
-    pub vsync: PI9,
-    pub clk: PI14,
-    pub de: PK7,
-
-    pub lcd_en: PI12,
-    pub backlight: PK3,
+    pub r0: Peri<'static, PI15>,
+    pub r1: Peri<'static, PJ0>,
+    pub r2: Peri<'static, PJ1>,
+    pub r3: Peri<'static, PJ2>,
+    pub r4: Peri<'static, PJ3>,
+    pub r5: Peri<'static, PJ4>,
+    pub r6: Peri<'static, PJ5>,
+    pub r7: Peri<'static, PJ6>,
+
+    pub g0: Peri<'static, PJ7>,
+    pub g1: Peri<'static, PJ8>,
+    pub g2: Peri<'static, PJ9>,
+    pub g3: Peri<'static, PJ10>,
+    pub g4: Peri<'static, PJ11>,
+    pub g5: Peri<'static, PK0>,
+    pub g6: Peri<'static, PK1>,
+    pub g7: Peri<'static, PK2>,
+
+    pub b0: Peri<'static, PE4>,
+    pub b1: Peri<'static, PJ13>,
+    pub b2: Peri<'static, PJ14>,
+    pub b3: Peri<'static, PJ15>,
+    pub b4: Peri<'static, PG12>,
+    pub b5: Peri<'static, PK4>,
+    pub b6: Peri<'static, PK5>,
+    pub b7: Peri<'static, PK6>,
+
+    pub hsync: Peri<'static, PI10>,
+    pub vsync: Peri<'static, PI9>,
+    pub clk: Peri<'static, PI14>,
+    pub de: Peri<'static, PK7>,
+
+    pub lcd_en: Peri<'static, PI12>,
+    pub backlight: Peri<'static, PK3>,
+}
+
+/// Handle to the initialised LCD-TFT controller.
+///
+/// Owns the LTDC driver and every pin routed to the panel: dropping a
+/// `Flex` or `Output` disconnects the pin, so they have to live as long as
+/// the display does.
+pub struct Display {
+    pub ltdc: Ltdc<'static, LTDC>,
+    pub panel: PanelTiming,
+    /// Bottom layer, blended onto the background color
+    pub layer0: LtdcLayer,
+    /// Top layer, blended onto layer 0
+    pub layer1: LtdcLayer,
+    _lcd_en: Output<'static>,
+    _backlight: Output<'static>,
+    _pins: [Flex<'static>; 28],
+}
+
+/// Most LTDC signals are on AF14, only LTDC_B4 on PG12 lives on AF9.
+const LTDC_AF: u8 = 14;
+const LTDC_B4_AF: u8 = 9;
+
+fn ltdc_pin(pin: Peri<'static, impl embassy_stm32::gpio::Pin>, af: u8) -> Flex<'static> {
+    const DATA_AF: AfType = AfType::output(OutputType::PushPull, Speed::Low);
+
+    let mut flex = Flex::new(pin);
+    flex.set_as_af_unchecked(af, DATA_AF);
+    flex
 }
 
 /// Initializes the LTDC display controller and returns the configured instance
-pub fn init_display(pins: DisplayPins, ltdc_periph: embassy_stm32::peripherals::LTDC) {
-    let data_af = AfType::output(OutputType::PushPull, Speed::Low);
-    // Red
-    Flex::new(pins.r0).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.r1).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.r2).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.r3).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.r4).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.r5).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.r6).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.r7).set_as_af_unchecked(14, data_af);
-
-    // Green
-    Flex::new(pins.g0).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.g1).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.g2).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.g3).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.g4).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.g5).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.g6).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.g7).set_as_af_unchecked(14, data_af);
-
-    // Blue
-    Flex::new(pins.b0).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.b1).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.b2).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.b3).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.b4).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.b5).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.b6).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.b7).set_as_af_unchecked(14, data_af);
-
-    // Control Signals
-    Flex::new(pins.hsync).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.vsync).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.clk).set_as_af_unchecked(14, data_af);
-    Flex::new(pins.de).set_as_af_unchecked(14, data_af);
+///
+/// PLLSAI N and R are set up by `embassy_stm32::init`, only the final
+/// PLLSAIDIVR divider (`pllsai_divr`, 2/4/8/16) is written here.
+pub fn init_display(
+    pins: DisplayPins,
+    ltdc_periph: Peri<'static, LTDC>,
+    panel: &PanelTiming,
+    pllsai_divr: u32,
+) -> Display {
+    #[rustfmt::skip]
+    let pins_af = [
+        // Red
+        ltdc_pin(pins.r0, LTDC_AF), ltdc_pin(pins.r1, LTDC_AF), ltdc_pin(pins.r2, LTDC_AF), ltdc_pin(pins.r3, LTDC_AF),
+        ltdc_pin(pins.r4, LTDC_AF), ltdc_pin(pins.r5, LTDC_AF), ltdc_pin(pins.r6, LTDC_AF), ltdc_pin(pins.r7, LTDC_AF),
+        // Green
+        ltdc_pin(pins.g0, LTDC_AF), ltdc_pin(pins.g1, LTDC_AF), ltdc_pin(pins.g2, LTDC_AF), ltdc_pin(pins.g3, LTDC_AF),
+        ltdc_pin(pins.g4, LTDC_AF), ltdc_pin(pins.g5, LTDC_AF), ltdc_pin(pins.g6, LTDC_AF), ltdc_pin(pins.g7, LTDC_AF),
+        // Blue
+        ltdc_pin(pins.b0, LTDC_AF), ltdc_pin(pins.b1, LTDC_AF), ltdc_pin(pins.b2, LTDC_AF), ltdc_pin(pins.b3, LTDC_AF),
+        ltdc_pin(pins.b4, LTDC_B4_AF), ltdc_pin(pins.b5, LTDC_AF), ltdc_pin(pins.b6, LTDC_AF), ltdc_pin(pins.b7, LTDC_AF),
+        // Control Signals
+        ltdc_pin(pins.hsync, LTDC_AF), ltdc_pin(pins.vsync, LTDC_AF), ltdc_pin(pins.clk, LTDC_AF), ltdc_pin(pins.de, LTDC_AF),
+    ];
 
     // Display enable & backlight control
-    let _lcd_en = Output::new(pins.lcd_en, Level::High, Speed::Low);
-    let _backlight = Output::new(pins.backlight, Level::High, Speed::Low);
+    let lcd_en = Output::new(pins.lcd_en, Level::High, Speed::Low);
+    let backlight = Output::new(pins.backlight, Level::High, Speed::Low);
 
     // Initialize LTDC
     let mut ltdc = Ltdc::new(ltdc_periph);
 
+    let divr = match pllsai_divr {
+        2 => Pllsaidivr::DIV2,
+        4 => Pllsaidivr::DIV4,
+        8 => Pllsaidivr::DIV8,
+        _ => Pllsaidivr::DIV16,
+    };
+
     critical_section::with(|_cs| {
         // RM says the pllsaidivr should only be changed when pllsai is off. But this could have other unintended side effects. So let's just give it a try like this.
         // According to the debugger, this bit gets set, anyway.
-        RCC.dckcfgr1()
-            .modify(|w| w.set_pllsaidivr(embassy_stm32::pac::rcc::vals::Pllsaidivr::DIV8));
+        RCC.dckcfgr1().modify(|w| w.set_pllsaidivr(divr));
     });
 
     embassy_stm32::rcc::enable_and_reset::<embassy_stm32::peripherals::LTDC>();
@@ -102,36 +129,49 @@ pub fn init_display(pins: DisplayPins, ltdc_periph: embassy_stm32::peripherals::
 
     use embassy_stm32::pac::LTDC;
 
-    // Set the LTDC to 480x272
     LTDC.gcr().modify(|w| {
-        w.set_hspol(embassy_stm32::pac::ltdc::vals::Hspol::ACTIVELOW);
-        w.set_vspol(embassy_stm32::pac::ltdc::vals::Vspol::ACTIVELOW);
-        w.set_depol(embassy_stm32::pac::ltdc::vals::Depol::ACTIVELOW);
-        w.set_pcpol(embassy_stm32::pac::ltdc::vals::Pcpol::RISINGEDGE);
+        w.set_hspol(match panel.hsync_polarity {
+            Polarity::ActiveLow => Hspol::ACTIVELOW,
+            Polarity::ActiveHigh => Hspol::ACTIVEHIGH,
+        });
+        w.set_vspol(match panel.vsync_polarity {
+            Polarity::ActiveLow => Vspol::ACTIVELOW,
+            Polarity::ActiveHigh => Vspol::ACTIVEHIGH,
+        });
+        w.set_depol(match panel.de_polarity {
+            Polarity::ActiveLow => Depol::ACTIVELOW,
+            Polarity::ActiveHigh => Depol::ACTIVEHIGH,
+        });
+        w.set_pcpol(match panel.pixel_clock_edge {
+            ClockEdge::Rising => Pcpol::RISINGEDGE,
+            ClockEdge::Falling => Pcpol::FALLINGEDGE,
+        });
     });
 
+    let timing = panel.ltdc_timing();
+
     // Set Sync signals
     LTDC.sscr().write(|w| {
-        w.set_hsw(41);
-        w.set_vsh(10);
+        w.set_hsw(timing.hsw);
+        w.set_vsh(timing.vsh);
     });
 
     // Set Accumulated Back porch
     LTDC.bpcr().modify(|w| {
-        w.set_ahbp(53);
-        w.set_avbp(11);
+        w.set_ahbp(timing.ahbp);
+        w.set_avbp(timing.avbp);
     });
 
     // Set Accumulated Active Width
     LTDC.awcr().modify(|w| {
-        w.set_aah(283);
-        w.set_aaw(533);
+        w.set_aah(timing.aah);
+        w.set_aaw(timing.aaw);
     });
 
     // Set Total Width
     LTDC.twcr().modify(|w| {
-        w.set_totalh(285);
-        w.set_totalw(565);
+        w.set_totalh(timing.totalh);
+        w.set_totalw(timing.totalw);
     });
 
     // Set the background color value
@@ -146,4 +186,17 @@ pub fn init_display(pins: DisplayPins, ltdc_periph: embassy_stm32::peripherals::
         w.set_terrie(true);
         w.set_fuie(true);
     });
+
+    // Enable the LTDC
+    ltdc.enable();
+
+    Display {
+        ltdc,
+        panel: *panel,
+        layer0: LtdcLayer::new(LayerId::Layer0, timing),
+        layer1: LtdcLayer::new(LayerId::Layer1, timing),
+        _lcd_en: lcd_en,
+        _backlight: backlight,
+        _pins: pins_af,
+    }
 }
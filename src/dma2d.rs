@@ -0,0 +1,214 @@
+//! Chrom-ART (DMA2D) accelerated fills, copies and blends.
+//!
+//! Transfers are blocking: they are started and polled until done, so a
+//! drawing call has finished with the buffer when it returns. If the DMA2D
+//! was not initialised or another task is using it, every function returns
+//! `false` and the caller falls back to the CPU.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embassy_stm32::pac::dma2d::regs::Ocolr;
+use embassy_stm32::pac::dma2d::vals::{BgpfccrCm, FgpfccrAm, FgpfccrCm, Mode, OpfccrCm};
+use embassy_stm32::pac::DMA2D;
+use embassy_stm32::peripherals::DMA2D as Dma2dPeripheral;
+use embassy_stm32::Peri;
+
+use crate::layer::PixelFormat;
+
+/// Set once the DMA2D clock is running
+static READY: AtomicBool = AtomicBool::new(false);
+/// Held for the duration of one transfer
+static BUSY: AtomicBool = AtomicBool::new(false);
+
+/// Below this many pixels setting up a transfer costs more than the CPU
+pub const MIN_PIXELS: u32 = 64;
+
+/// Enables the DMA2D clock. Until this is called all drawing is done in
+/// software.
+pub fn init(_dma2d: Peri<'static, Dma2dPeripheral>) {
+    embassy_stm32::rcc::enable_and_reset::<Dma2dPeripheral>();
+    READY.store(true, Ordering::Release);
+}
+
+/// Rectangle of pixels in memory
+#[derive(Clone, Copy, Debug)]
+pub struct Surface {
+    /// Address of the top-left pixel
+    pub addr: u32,
+    /// Distance between two lines in pixels
+    pub pitch: u16,
+    pub format: PixelFormat,
+}
+
+struct Guard;
+
+impl Guard {
+    fn acquire() -> Option<Self> {
+        if !READY.load(Ordering::Acquire) {
+            return None;
+        }
+        BUSY.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| Guard)
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        BUSY.store(false, Ordering::Release);
+    }
+}
+
+fn fg_cm(format: PixelFormat) -> FgpfccrCm {
+    match format {
+        PixelFormat::Argb8888 => FgpfccrCm::ARGB8888,
+        PixelFormat::Rgb888 => FgpfccrCm::RGB888,
+        PixelFormat::Rgb565 => FgpfccrCm::RGB565,
+        PixelFormat::Argb1555 => FgpfccrCm::ARGB1555,
+        PixelFormat::Argb4444 => FgpfccrCm::ARGB4444,
+        PixelFormat::L8 => FgpfccrCm::L8,
+        PixelFormat::Al44 => FgpfccrCm::AL44,
+        PixelFormat::Al88 => FgpfccrCm::AL88,
+    }
+}
+
+fn bg_cm(format: PixelFormat) -> BgpfccrCm {
+    match format {
+        PixelFormat::Argb8888 => BgpfccrCm::ARGB8888,
+        PixelFormat::Rgb888 => BgpfccrCm::RGB888,
+        PixelFormat::Rgb565 => BgpfccrCm::RGB565,
+        PixelFormat::Argb1555 => BgpfccrCm::ARGB1555,
+        PixelFormat::Argb4444 => BgpfccrCm::ARGB4444,
+        PixelFormat::L8 => BgpfccrCm::L8,
+        PixelFormat::Al44 => BgpfccrCm::AL44,
+        PixelFormat::Al88 => BgpfccrCm::AL88,
+    }
+}
+
+/// The DMA2D can only write the direct-color formats
+fn out_cm(format: PixelFormat) -> Option<OpfccrCm> {
+    match format {
+        PixelFormat::Argb8888 => Some(OpfccrCm::ARGB8888),
+        PixelFormat::Rgb888 => Some(OpfccrCm::RGB888),
+        PixelFormat::Rgb565 => Some(OpfccrCm::RGB565),
+        PixelFormat::Argb1555 => Some(OpfccrCm::ARGB1555),
+        PixelFormat::Argb4444 => Some(OpfccrCm::ARGB4444),
+        PixelFormat::L8 | PixelFormat::Al44 | PixelFormat::Al88 => None,
+    }
+}
+
+fn set_output(dst: &Surface, cm: OpfccrCm, width: u16, height: u16) {
+    DMA2D.opfccr().write(|w| w.set_cm(cm));
+    DMA2D.omar().write(|w| w.set_ma(dst.addr));
+    DMA2D.oor().write(|w| w.set_lo(dst.pitch - width));
+    DMA2D.nlr().write(|w| {
+        w.set_pl(width);
+        w.set_nl(height);
+    });
+}
+
+/// Starts the configured transfer and waits for it
+fn run(mode: Mode) -> bool {
+    DMA2D.ifcr().write(|w| {
+        w.set_ctcif(true);
+        w.set_cteif(true);
+        w.set_cceif(true);
+    });
+    DMA2D.cr().write(|w| {
+        w.set_mode(mode);
+        w.set_start(true);
+    });
+
+    while DMA2D.cr().read().start() {}
+
+    let isr = DMA2D.isr().read();
+    if isr.teif() || isr.ceif() {
+        defmt::error!(
+            "DMA2D transfer failed, TEIF {} CEIF {}",
+            isr.teif(),
+            isr.ceif()
+        );
+        return false;
+    }
+    true
+}
+
+/// Fills `width` x `height` pixels of `dst` with `color`, which has to be
+/// in the raw storage format of `dst.format`.
+pub fn fill(dst: &Surface, width: u16, height: u16, color: u32) -> bool {
+    let Some(cm) = out_cm(dst.format) else {
+        return false;
+    };
+    if width == 0 || height == 0 {
+        return true;
+    }
+    let Some(_guard) = Guard::acquire() else {
+        return false;
+    };
+
+    set_output(dst, cm, width, height);
+    DMA2D.ocolr().write_value(Ocolr(color));
+    run(Mode::REGISTERTOMEMORY)
+}
+
+/// Copies a rectangle from `src` to `dst`, converting the pixel format if
+/// they differ.
+pub fn copy(src: &Surface, dst: &Surface, width: u16, height: u16) -> bool {
+    let Some(cm) = out_cm(dst.format) else {
+        return false;
+    };
+    if width == 0 || height == 0 {
+        return true;
+    }
+    let Some(_guard) = Guard::acquire() else {
+        return false;
+    };
+
+    DMA2D.fgmar().write(|w| w.set_ma(src.addr));
+    DMA2D.fgor().write(|w| w.set_lo(src.pitch - width));
+    DMA2D.fgpfccr().write(|w| {
+        w.set_cm(fg_cm(src.format));
+        w.set_am(FgpfccrAm::NOMODIFY);
+    });
+    set_output(dst, cm, width, height);
+
+    if src.format == dst.format {
+        run(Mode::MEMORYTOMEMORY)
+    } else {
+        run(Mode::MEMORYTOMEMORYPFC)
+    }
+}
+
+/// Blends `src` over `dst` using the source pixel alpha multiplied by
+/// `alpha`, 255 keeps the pixel alpha as is.
+pub fn blend(src: &Surface, dst: &Surface, width: u16, height: u16, alpha: u8) -> bool {
+    let Some(cm) = out_cm(dst.format) else {
+        return false;
+    };
+    if width == 0 || height == 0 {
+        return true;
+    }
+    let Some(_guard) = Guard::acquire() else {
+        return false;
+    };
+
+    DMA2D.fgmar().write(|w| w.set_ma(src.addr));
+    DMA2D.fgor().write(|w| w.set_lo(src.pitch - width));
+    DMA2D.fgpfccr().write(|w| {
+        w.set_cm(fg_cm(src.format));
+        w.set_am(if alpha == 255 {
+            FgpfccrAm::NOMODIFY
+        } else {
+            FgpfccrAm::MULTIPLY
+        });
+        w.set_alpha(alpha);
+    });
+
+    // The destination is read back as the background
+    DMA2D.bgmar().write(|w| w.set_ma(dst.addr));
+    DMA2D.bgor().write(|w| w.set_lo(dst.pitch - width));
+    DMA2D.bgpfccr().write(|w| w.set_cm(bg_cm(dst.format)));
+
+    set_output(dst, cm, width, height);
+    run(Mode::MEMORYTOMEMORYPFCBLENDING)
+}
@@ -0,0 +1,335 @@
+//! Frame buffers and everything drawn into them.
+//!
+//! Nothing here touches the hardware except through the DMA2D, which is
+//! stubbed out on the host, so the GUI code also builds for the simulator.
+
+use embedded_graphics::{
+    draw_target::DrawTarget,
+    geometry::{self, Dimensions, Point, Size},
+    pixelcolor::{Rgb888, RgbColor},
+    primitives::{PointsIter, Rectangle},
+    Pixel,
+};
+
+use crate::color::{self, DirectColor, FrameColor, PixelFormat};
+#[cfg(feature = "hw")]
+use crate::dma2d;
+use crate::panel::RK043FN48H;
+
+pub const LCD_WIDTH: u16 = RK043FN48H.width;
+pub const LCD_HEIGHT: u16 = RK043FN48H.height;
+
+/// Stand-in for the host build, every transfer falls back to the CPU
+#[cfg(not(feature = "hw"))]
+mod dma2d {
+    use crate::color::PixelFormat;
+
+    pub const MIN_PIXELS: u32 = 64;
+
+    // Filled in by the callers, nothing reads them without the DMA2D
+    #[allow(dead_code)]
+    pub struct Surface {
+        pub addr: u32,
+        pub pitch: u16,
+        pub format: PixelFormat,
+    }
+
+    pub fn fill(_dst: &Surface, _width: u16, _height: u16, _color: u32) -> bool {
+        false
+    }
+
+    pub fn copy(_src: &Surface, _dst: &Surface, _width: u16, _height: u16) -> bool {
+        false
+    }
+
+    pub fn blend(_src: &Surface, _dst: &Surface, _width: u16, _height: u16, _alpha: u8) -> bool {
+        false
+    }
+}
+
+/// Target for a layer drawn over another one, e.g. the LTDC overlay
+pub trait Overlay: DrawTarget {
+    /// Makes everything transparent, the layer below shows through
+    fn clear_transparent(&mut self);
+}
+
+// Graphics Driver
+
+/// Frame buffer of `C` pixels, one [`FrameColor::Word`] each.
+///
+/// The default `Rgb888` is stored as ARGB8888, smaller formats like
+/// `Rgb565` halve memory and LTDC bandwidth.
+pub struct DisplayBuffer<'a, C: FrameColor = Rgb888> {
+    pub buf: &'a mut [C::Word],
+    pub width: i32,
+    pub height: i32,
+}
+
+impl<'a, C: FrameColor> DisplayBuffer<'a, C> {
+    pub fn new(buf: &'a mut [C::Word], width: i32, height: i32) -> Self {
+        assert!(buf.len() >= (width * height) as usize);
+        Self { buf, width, height }
+    }
+}
+
+/// Raw pixels in one of the direct-color LTDC formats, e.g. an image
+/// converted at build time.
+#[derive(Clone, Copy)]
+pub struct Bitmap<'a> {
+    pub data: &'a [u8],
+    pub width: u16,
+    pub height: u16,
+    pub format: PixelFormat,
+}
+
+impl<'a> Bitmap<'a> {
+    /// Returns `None` if `data` is too short or `format` needs a CLUT.
+    pub fn new(data: &'a [u8], width: u16, height: u16, format: PixelFormat) -> Option<Self> {
+        let size = width as usize * height as usize * format.bytes_per_pixel() as usize;
+        let direct = !matches!(
+            format,
+            PixelFormat::L8 | PixelFormat::Al44 | PixelFormat::Al88
+        );
+
+        (direct && data.len() >= size).then_some(Self {
+            data,
+            width,
+            height,
+            format,
+        })
+    }
+
+    /// Pixel at `x`, `y` as ARGB8888
+    fn argb(&self, x: u16, y: u16) -> u32 {
+        let bpp = self.format.bytes_per_pixel() as usize;
+        let i = (y as usize * self.width as usize + x as usize) * bpp;
+
+        // CLUT formats are rejected by `Bitmap::new`
+        color::argb8888_from_bytes(self.format, &self.data[i..i + bpp]).unwrap_or(0)
+    }
+}
+
+/// `src` over `dst`, both ARGB8888, with `src` alpha scaled by `alpha`
+fn blend_argb(src: u32, dst: u32, alpha: u8) -> u32 {
+    let sa = (src >> 24) * alpha as u32 / 255;
+    let da = dst >> 24;
+    let inv = 255 - sa;
+
+    let channel = |shift: u32| {
+        let s = (src >> shift) & 0xFF;
+        let d = (dst >> shift) & 0xFF;
+        ((s * sa + d * inv) / 255) << shift
+    };
+
+    (sa + da * inv / 255) << 24 | channel(16) | channel(8) | channel(0)
+}
+
+impl<C: FrameColor> DisplayBuffer<'_, C> {
+    fn surface(&mut self, x: i32, y: i32) -> dma2d::Surface {
+        let offset = (y * self.width + x) as usize;
+        dma2d::Surface {
+            addr: self.buf[offset..].as_mut_ptr() as u32,
+            pitch: self.width as u16,
+            format: C::FORMAT,
+        }
+    }
+
+    /// Fills the clipped `area` with a raw pixel value
+    fn fill_word(&mut self, area: &Rectangle, word: C::Word) {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return;
+        }
+
+        let Point { x, y } = area.top_left;
+        let (w, h) = (area.size.width, area.size.height);
+
+        if w * h >= dma2d::MIN_PIXELS
+            && dma2d::fill(&self.surface(x, y), w as u16, h as u16, word.into())
+        {
+            return;
+        }
+
+        for row in y..y + h as i32 {
+            let start = (row * self.width + x) as usize;
+            self.buf[start..start + w as usize].fill(word);
+        }
+    }
+
+    /// Copies `area` of `src` to the same place in this buffer, both have to
+    /// be the same size.
+    pub fn copy_area_from(&mut self, src: &DisplayBuffer<'_, C>, area: &Rectangle) {
+        let area = area.intersection(&self.bounding_box());
+        if area.is_zero_sized() || src.width != self.width || src.height != self.height {
+            return;
+        }
+
+        let Point { x, y } = area.top_left;
+        let (w, h) = (area.size.width, area.size.height);
+        let offset = (y * self.width + x) as usize;
+
+        let src_surface = dma2d::Surface {
+            addr: src.buf[offset..].as_ptr() as u32,
+            pitch: src.width as u16,
+            format: C::FORMAT,
+        };
+        if w * h >= dma2d::MIN_PIXELS
+            && dma2d::copy(&src_surface, &self.surface(x, y), w as u16, h as u16)
+        {
+            return;
+        }
+
+        for row in y..y + h as i32 {
+            let start = (row * self.width + x) as usize;
+            let line = start..start + w as usize;
+            self.buf[line.clone()].copy_from_slice(&src.buf[line]);
+        }
+    }
+
+    /// Clears the buffer to fully transparent, for overlay layers. CLUT
+    /// buffers are set to index 0.
+    pub fn clear_transparent(&mut self) {
+        self.fill_word(&self.bounding_box(), C::Word::default());
+    }
+}
+
+impl<C: DirectColor> DisplayBuffer<'_, C> {
+    /// Copies `bitmap` to `at`, converting it to the buffer format. Pixels
+    /// outside the buffer are skipped.
+    pub fn blit(&mut self, bitmap: &Bitmap, at: Point) {
+        self.blit_with(bitmap, at, None);
+    }
+
+    /// Blends `bitmap` onto the buffer using its pixel alpha multiplied by
+    /// `alpha`.
+    pub fn blend(&mut self, bitmap: &Bitmap, at: Point, alpha: u8) {
+        self.blit_with(bitmap, at, Some(alpha));
+    }
+
+    fn blit_with(&mut self, bitmap: &Bitmap, at: Point, alpha: Option<u8>) {
+        let area = Rectangle::new(at, Size::new(bitmap.width as u32, bitmap.height as u32))
+            .intersection(&self.bounding_box());
+        if area.is_zero_sized() {
+            return;
+        }
+
+        let Point { x, y } = area.top_left;
+        let (w, h) = (area.size.width as u16, area.size.height as u16);
+        // First visible pixel inside the bitmap
+        let (sx, sy) = ((x - at.x) as u16, (y - at.y) as u16);
+
+        let bpp = bitmap.format.bytes_per_pixel() as usize;
+        let src = dma2d::Surface {
+            addr: bitmap.data[(sy as usize * bitmap.width as usize + sx as usize) * bpp..].as_ptr()
+                as u32,
+            pitch: bitmap.width,
+            format: bitmap.format,
+        };
+        let dst = self.surface(x, y);
+
+        let done = match alpha {
+            None => dma2d::copy(&src, &dst, w, h),
+            Some(alpha) => dma2d::blend(&src, &dst, w, h, alpha),
+        };
+        if done {
+            return;
+        }
+
+        for row in 0..h {
+            let start = ((y + row as i32) * self.width + x) as usize;
+            for (col, px) in self.buf[start..start + w as usize].iter_mut().enumerate() {
+                let argb = bitmap.argb(sx + col as u16, sy + row);
+                let argb = match alpha {
+                    None => argb,
+                    Some(alpha) => blend_argb(argb, C::word_to_argb8888(*px), alpha),
+                };
+                *px = C::word_from_argb8888(argb);
+            }
+        }
+    }
+}
+
+impl<C: FrameColor + RgbColor> DisplayBuffer<'_, C> {
+    /// Clears the buffer
+    pub fn clear(&mut self) {
+        // self.fill_word(&self.bounding_box(), C::BLACK.into_word()); // Solid black
+        self.fill_word(&self.bounding_box(), C::WHITE.into_word()); // Solid White
+    }
+}
+
+// To work with Embedded Graphics Crate we should implement DrawTarget
+// For as Display
+// Implement DrawTarget for
+impl<C: FrameColor> embedded_graphics::draw_target::DrawTarget for DisplayBuffer<'_, C> {
+    type Color = C;
+    type Error = ();
+
+    /// Draw a pixel
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for pixel in pixels {
+            let Pixel(point, color) = pixel;
+
+            if point.x >= 0 && point.y >= 0 && point.x < self.width && point.y < self.height {
+                let index = point.y * self.width + point.x;
+                self.buf[index as usize] = color.into_word();
+            } else {
+                // Ignore invalid points
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The colors come from an iterator, so the CPU writes them, but the
+    /// area is clipped once instead of per pixel.
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        if area.intersection(&self.bounding_box()) != *area {
+            return self.draw_iter(
+                area.points()
+                    .zip(colors)
+                    .map(|(point, color)| Pixel(point, color)),
+            );
+        }
+
+        let Point { x, y } = area.top_left;
+        let w = area.size.width as usize;
+        let mut colors = colors.into_iter();
+
+        for row in y..y + area.size.height as i32 {
+            let start = (row * self.width + x) as usize;
+            for (px, color) in self.buf[start..start + w].iter_mut().zip(&mut colors) {
+                *px = color.into_word();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_word(area, color.into_word());
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.fill_word(&self.bounding_box(), color.into_word());
+        Ok(())
+    }
+}
+impl<C: FrameColor> Overlay for DisplayBuffer<'_, C> {
+    fn clear_transparent(&mut self) {
+        DisplayBuffer::clear_transparent(self);
+    }
+}
+
+impl<C: FrameColor> geometry::OriginDimensions for DisplayBuffer<'_, C> {
+    /// Return the size of the display
+    fn size(&self) -> geometry::Size {
+        geometry::Size::new(self.width as u32, self.height as u32)
+    }
+}
@@ -0,0 +1,341 @@
+//! Tap, double tap, long press, swipe and pinch from touch events.
+//!
+//! [`Recognizer`] is a state machine fed with the [`TouchEvent`]s of a
+//! [`Tracker`](crate::multitouch::Tracker) and a millisecond clock. It does
+//! not read the clock itself, so it runs the same on the board and against
+//! recorded touch traces on the host.
+//!
+//! ```ignore
+//! for event in tracker.update(&contacts) {
+//!     if let Some(gesture) = recognizer.event(event, now) { .. }
+//! }
+//! // A long press is reported while the finger is still down
+//! if let Some(gesture) = recognizer.poll(now) { .. }
+//! ```
+
+use embedded_graphics::geometry::Point;
+
+use crate::multitouch::TouchEvent;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Gesture {
+    /// Reported when the finger is lifted
+    Tap(Point),
+    /// The second tap of a double tap, reported instead of a [`Gesture::Tap`]
+    DoubleTap(Point),
+    /// Reported once while the finger is still down, nothing follows when
+    /// it is lifted
+    LongPress(Point),
+    Swipe {
+        from: Point,
+        to: Point,
+        direction: Direction,
+    },
+    /// Reported on every move of two fingers once they moved far enough
+    /// apart or together. `distance / start_distance` is the zoom factor.
+    Pinch {
+        center: Point,
+        start_distance: u32,
+        distance: u32,
+    },
+}
+
+/// Thresholds, times in milliseconds and distances in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Config {
+    /// A finger that moves less still taps or presses
+    pub slop: u32,
+    pub long_press_ms: u32,
+    /// Between the end of the first tap and the end of the second one
+    pub double_tap_ms: u32,
+    /// Between the two taps of a double tap
+    pub double_tap_distance: u32,
+    pub swipe_distance: u32,
+    /// A slower movement is a drag, no gesture
+    pub swipe_ms: u32,
+    /// Change of the finger distance that starts a pinch
+    pub pinch_distance: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            slop: 10,
+            long_press_ms: 600,
+            double_tap_ms: 300,
+            double_tap_distance: 30,
+            swipe_distance: 60,
+            swipe_ms: 500,
+            pinch_distance: 20,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum State {
+    Idle,
+    /// One finger down
+    Pressed {
+        id: u8,
+        start: Point,
+        last: Point,
+        since: u32,
+        /// Left the slop, so it is no tap or long press any more
+        moved: bool,
+        long_pressed: bool,
+    },
+    /// Two fingers down, further ones are ignored
+    Pinching {
+        a: (u8, Point),
+        b: (u8, Point),
+        start_distance: u32,
+        started: bool,
+    },
+    /// The gesture is over, waits until every finger is lifted
+    Done,
+}
+
+#[derive(Clone, Debug)]
+pub struct Recognizer {
+    config: Config,
+    state: State,
+    /// Fingers down
+    fingers: u8,
+    /// Point and time of the last tap, for double taps
+    last_tap: Option<(Point, u32)>,
+}
+
+impl Default for Recognizer {
+    fn default() -> Self {
+        Self::new(Config::default())
+    }
+}
+
+impl Recognizer {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            state: State::Idle,
+            fingers: 0,
+            last_tap: None,
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Feeds one touch event that happened at `now`
+    pub fn event(&mut self, event: TouchEvent, now: u32) -> Option<Gesture> {
+        match event {
+            TouchEvent::Down { id, point } => {
+                self.fingers = self.fingers.saturating_add(1);
+                self.down(id, point, now);
+                None
+            }
+            TouchEvent::Move { id, point } => self.moved(id, point),
+            TouchEvent::Up { id, point } => {
+                self.fingers = self.fingers.saturating_sub(1);
+                self.up(id, point, now)
+            }
+        }
+    }
+
+    /// Reports a long press once the finger was held long enough. Call it
+    /// regularly while a finger is down.
+    pub fn poll(&mut self, now: u32) -> Option<Gesture> {
+        match &mut self.state {
+            State::Pressed {
+                start,
+                since,
+                moved: false,
+                long_pressed,
+                ..
+            } if !*long_pressed && now.wrapping_sub(*since) >= self.config.long_press_ms => {
+                *long_pressed = true;
+                Some(Gesture::LongPress(*start))
+            }
+            _ => None,
+        }
+    }
+
+    fn down(&mut self, id: u8, point: Point, now: u32) {
+        self.state = match self.state {
+            State::Idle => State::Pressed {
+                id,
+                start: point,
+                last: point,
+                since: now,
+                moved: false,
+                long_pressed: false,
+            },
+            // The first finger may have moved since it went down, the pinch
+            // starts from where it is now
+            State::Pressed {
+                id: first, last, ..
+            } => {
+                let a = (first, last);
+                State::Pinching {
+                    a,
+                    b: (id, point),
+                    start_distance: distance(a.1, point),
+                    started: false,
+                }
+            }
+            state => state,
+        };
+    }
+
+    fn moved(&mut self, id: u8, point: Point) -> Option<Gesture> {
+        match &mut self.state {
+            State::Pressed {
+                id: pressed,
+                start,
+                last,
+                moved,
+                ..
+            } if *pressed == id => {
+                *last = point;
+                if distance(*start, point) > self.config.slop {
+                    *moved = true;
+                }
+                None
+            }
+            State::Pinching {
+                a,
+                b,
+                start_distance,
+                started,
+            } => {
+                if a.0 == id {
+                    a.1 = point;
+                } else if b.0 == id {
+                    b.1 = point;
+                } else {
+                    return None;
+                }
+
+                let distance = distance(a.1, b.1);
+                if !*started && distance.abs_diff(*start_distance) < self.config.pinch_distance {
+                    return None;
+                }
+                *started = true;
+                Some(Gesture::Pinch {
+                    center: Point::new((a.1.x + b.1.x) / 2, (a.1.y + b.1.y) / 2),
+                    start_distance: *start_distance,
+                    distance,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn up(&mut self, id: u8, point: Point, now: u32) -> Option<Gesture> {
+        let state = self.state;
+        self.state = if self.fingers == 0 {
+            State::Idle
+        } else {
+            State::Done
+        };
+
+        let State::Pressed {
+            id: pressed,
+            start,
+            since,
+            moved,
+            long_pressed,
+            ..
+        } = state
+        else {
+            return None;
+        };
+        if pressed != id {
+            // Lifted a finger that was not tracked, keep going
+            self.state = state;
+            return None;
+        }
+
+        let held = now.wrapping_sub(since);
+        if long_pressed {
+            None
+        } else if !moved && distance(start, point) <= self.config.slop {
+            if held >= self.config.long_press_ms {
+                // Not polled in time
+                return Some(Gesture::LongPress(start));
+            }
+            Some(self.tap(point, now))
+        } else {
+            let swiped = distance(start, point);
+            (swiped >= self.config.swipe_distance && held <= self.config.swipe_ms).then(|| {
+                Gesture::Swipe {
+                    from: start,
+                    to: point,
+                    direction: direction(start, point),
+                }
+            })
+        }
+    }
+
+    fn tap(&mut self, point: Point, now: u32) -> Gesture {
+        let double = self.last_tap.is_some_and(|(last, at)| {
+            now.wrapping_sub(at) <= self.config.double_tap_ms
+                && distance(last, point) <= self.config.double_tap_distance
+        });
+
+        if double {
+            self.last_tap = None;
+            Gesture::DoubleTap(point)
+        } else {
+            self.last_tap = Some((point, now));
+            Gesture::Tap(point)
+        }
+    }
+}
+
+/// Of the larger movement, y grows downwards
+fn direction(from: Point, to: Point) -> Direction {
+    let delta = to - from;
+    if delta.x.abs() >= delta.y.abs() {
+        if delta.x < 0 {
+            Direction::Left
+        } else {
+            Direction::Right
+        }
+    } else if delta.y < 0 {
+        Direction::Up
+    } else {
+        Direction::Down
+    }
+}
+
+fn distance(a: Point, b: Point) -> u32 {
+    let delta = a - b;
+    let squared = (delta.x * delta.x + delta.y * delta.y) as u32;
+    isqrt(squared)
+}
+
+/// Rounded down
+fn isqrt(n: u32) -> u32 {
+    if n < 4 {
+        return (n > 0) as u32;
+    }
+    // Newton's method, starting above the root
+    let mut x = n;
+    let mut y = n / 2 + 1;
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+    x
+}
@@ -0,0 +1,62 @@
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use embassy_stm32::gpio::{AnyPin, Level, Output, Pin, Speed};
+use embassy_stm32::peripherals::*;
+use embassy_stm32::Peri;
+use heapless::Vec;
+
+use crate::outputs::{OutputConfig, MAX_OUTPUTS};
+
+/// Digital pins of the Arduino Uno V3 connector (CN4, CN7).
+///
+/// D0/D1 are also USART6 RX/TX and D13 is shared with the user LED
+/// (`Board::led`), so it is not part of this set.
+pub struct ArduinoPins {
+    pub d0: Peri<'static, PC7>,
+    pub d1: Peri<'static, PC6>,
+    pub d2: Peri<'static, PG6>,
+    pub d3: Peri<'static, PB4>,
+    pub d4: Peri<'static, PG7>,
+    pub d5: Peri<'static, PI0>,
+    pub d6: Peri<'static, PH6>,
+    pub d7: Peri<'static, PI3>,
+    pub d8: Peri<'static, PI2>,
+    pub d9: Peri<'static, PA15>,
+    pub d10: Peri<'static, PA8>,
+    pub d11: Peri<'static, PB15>,
+    pub d12: Peri<'static, PB14>,
+}
+
+/// Pin (`port * 16 + pin`) and safe level of every [`bank_output`]
+static SAFE_LEVELS: Mutex<RefCell<Vec<(u8, Level), MAX_OUTPUTS>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+/// `pin` as an output of an `OutputBank`, already at the safe level of
+/// `config`. [`force_safe_levels`] knows the pin from then on.
+pub fn bank_output(pin: Peri<'static, impl Pin>, config: &OutputConfig) -> Output<'static> {
+    let level = Level::from(config.pin_level(config.safe));
+    let pin_port = pin.port() * 16 + pin.pin();
+    critical_section::with(|cs| {
+        let _ = SAFE_LEVELS.borrow_ref_mut(cs).push((pin_port, level));
+    });
+    Output::new(pin, level, Speed::Low)
+}
+
+/// Drives every [`bank_output`] to its safe level, for the fault handler
+/// after a panic.
+///
+/// # Safety
+///
+/// Takes the pins away from their `Output`s, nothing but a reset may run
+/// afterwards.
+pub unsafe fn force_safe_levels() {
+    critical_section::with(|cs| {
+        // Last output first, like `OutputBank::safe_state`
+        for &(pin_port, level) in SAFE_LEVELS.borrow_ref(cs).iter().rev() {
+            let output = Output::new(unsafe { AnyPin::steal(pin_port) }, level, Speed::Low);
+            // Dropping it would turn the pin into an input
+            core::mem::forget(output);
+        }
+    });
+}
@@ -0,0 +1,375 @@
+//! HTTP/1.1 for the web page and the REST API of the outputs.
+//!
+//! ```text
+//! GET /                  the page of `web/index.html`
+//! GET /api/outputs       {"outputs":[{"name":"D0","on":false},...]}
+//! GET /api/outputs/d1    {"name":"D1","on":true}
+//! PUT /api/outputs/d1    {"on":true}, answered like GET once it is done
+//! ```
+//!
+//! Output names are not case sensitive. [`Request::parse`] takes the bytes
+//! received so far, [`route`] decides what a request asks for and checks
+//! changes against the [`Interlock`], [`respond`] writes the answer. The
+//! transport keeps connections alive and handles pipelined requests.
+//! Chunked request bodies are not supported, neither are JSON bodies with
+//! more than the one member.
+
+use core::fmt::{self, Write};
+
+use heapless::String;
+
+use crate::interlock::{Interlock, Violation};
+use crate::outputs::{Command, Levels, OutputId, OUTPUTS};
+
+/// The web page, from flash
+pub const PAGE: &str = include_str!("../web/index.html");
+
+/// Longest request body, the API only takes `{"on":false}`
+pub const MAX_BODY: usize = 256;
+
+/// Status code of a response, with its reason phrase
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Status {
+    pub code: u16,
+    pub reason: &'static str,
+}
+
+const fn status(code: u16, reason: &'static str) -> Status {
+    Status { code, reason }
+}
+
+pub const OK: Status = status(200, "OK");
+pub const BAD_REQUEST: Status = status(400, "Bad Request");
+pub const NOT_FOUND: Status = status(404, "Not Found");
+pub const METHOD_NOT_ALLOWED: Status = status(405, "Method Not Allowed");
+pub const CONFLICT: Status = status(409, "Conflict");
+pub const PAYLOAD_TOO_LARGE: Status = status(413, "Payload Too Large");
+pub const HEADERS_TOO_LARGE: Status = status(431, "Request Header Fields Too Large");
+pub const NOT_IMPLEMENTED: Status = status(501, "Not Implemented");
+pub const VERSION_NOT_SUPPORTED: Status = status(505, "HTTP Version Not Supported");
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Method {
+    Get,
+    Head,
+    Put,
+    /// Every other method, none of the resources allows it
+    Other,
+}
+
+/// A complete request, borrowed from the receive buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Request<'a> {
+    pub method: Method,
+    /// The target without its query
+    pub path: &'a str,
+    /// HTTP/1.1 unless `Connection: close`, HTTP/1.0 only with
+    /// `Connection: keep-alive`
+    pub keep_alive: bool,
+    pub body: &'a [u8],
+}
+
+impl<'a> Request<'a> {
+    /// Parses the request at the start of `buf`. Returns it with its
+    /// length up to the end of the body, `None` while it is incomplete.
+    /// Empty lines before the request line are skipped.
+    pub fn parse(buf: &'a [u8]) -> Result<Option<(Self, usize)>, Status> {
+        let start = buf
+            .iter()
+            .take_while(|&&b| b == b'\r' || b == b'\n')
+            .count();
+        let Some(head_len) = head_len(&buf[start..]) else {
+            return Ok(None);
+        };
+        let head_end = start + head_len;
+        let head = core::str::from_utf8(&buf[start..head_end]).map_err(|_| BAD_REQUEST)?;
+        let mut lines = head.lines();
+
+        let mut parts = lines.next().ok_or(BAD_REQUEST)?.split(' ');
+        let (Some(method), Some(target), Some(version), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(BAD_REQUEST);
+        };
+        let method = match method {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            "PUT" => Method::Put,
+            "" => return Err(BAD_REQUEST),
+            _ => Method::Other,
+        };
+        let mut keep_alive = match version {
+            "HTTP/1.1" => true,
+            "HTTP/1.0" => false,
+            v if v.starts_with("HTTP/") => return Err(VERSION_NOT_SUPPORTED),
+            _ => return Err(BAD_REQUEST),
+        };
+        if !target.starts_with('/') {
+            return Err(BAD_REQUEST);
+        }
+        let path = target.split_once('?').map_or(target, |(path, _)| path);
+
+        let mut content_length = None;
+        for line in lines.take_while(|line| !line.is_empty()) {
+            let (name, value) = line.split_once(':').ok_or(BAD_REQUEST)?;
+            if name.is_empty() || name.ends_with([' ', '\t']) {
+                return Err(BAD_REQUEST);
+            }
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("Content-Length") {
+                let len: usize = value.parse().map_err(|_| BAD_REQUEST)?;
+                if content_length.is_some_and(|l| l != len) {
+                    return Err(BAD_REQUEST);
+                }
+                content_length = Some(len);
+            } else if name.eq_ignore_ascii_case("Transfer-Encoding") {
+                return Err(NOT_IMPLEMENTED);
+            } else if name.eq_ignore_ascii_case("Connection") {
+                for option in value.split(',').map(str::trim) {
+                    if option.eq_ignore_ascii_case("close") {
+                        keep_alive = false;
+                    } else if option.eq_ignore_ascii_case("keep-alive") {
+                        keep_alive = true;
+                    }
+                }
+            }
+        }
+
+        let body_len = content_length.unwrap_or(0);
+        if body_len > MAX_BODY {
+            return Err(PAYLOAD_TOO_LARGE);
+        }
+        let Some(body) = buf.get(head_end..head_end + body_len) else {
+            return Ok(None);
+        };
+        let request = Request {
+            method,
+            path,
+            keep_alive,
+            body,
+        };
+        Ok(Some((request, head_end + body_len)))
+    }
+}
+
+/// Length of the head up to the empty line that ends it, lines may end
+/// with LF only
+fn head_len(buf: &[u8]) -> Option<usize> {
+    let mut line_start = 0;
+    for (i, &byte) in buf.iter().enumerate() {
+        if byte == b'\n' {
+            let line = &buf[line_start..i];
+            if line.is_empty() || line == b"\r" {
+                return Some(i + 1);
+            }
+            line_start = i + 1;
+        }
+    }
+    None
+}
+
+/// What a request asks for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Route {
+    Page,
+    Outputs,
+    Output(OutputId),
+    SetOutput(OutputId, bool),
+}
+
+impl Route {
+    /// What the outputs task has to do for it
+    pub fn command(self) -> Option<Command> {
+        match self {
+            Route::SetOutput(output, true) => Some(Command::Set(output)),
+            Route::SetOutput(output, false) => Some(Command::Clear(output)),
+            Route::Page | Route::Outputs | Route::Output(_) => None,
+        }
+    }
+}
+
+/// Why a request is not served
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Error {
+    Status(Status),
+    /// The change breaks a rule, answered with [`CONFLICT`]
+    Interlock(Violation),
+}
+
+impl From<Status> for Error {
+    fn from(status: Status) -> Self {
+        Error::Status(status)
+    }
+}
+
+impl Error {
+    pub fn status(&self) -> Status {
+        match self {
+            Error::Status(status) => *status,
+            Error::Interlock(_) => CONFLICT,
+        }
+    }
+}
+
+/// Routes `request` for outputs at `levels`. Changes the `interlock`
+/// rejects are errors.
+pub fn route(request: &Request, levels: Levels, interlock: &Interlock) -> Result<Route, Error> {
+    let read = matches!(request.method, Method::Get | Method::Head);
+    let route = match request.path {
+        "/" | "/index.html" if read => Route::Page,
+        "/api/outputs" if read => Route::Outputs,
+        path => {
+            let output = path
+                .strip_prefix("/api/outputs/")
+                .and_then(output)
+                .filter(|_| read || request.method == Method::Put);
+            match output {
+                Some(output) if read => Route::Output(output),
+                Some(output) => Route::SetOutput(output, level(request.body)?),
+                None if allowed_methods(path).is_some() => return Err(METHOD_NOT_ALLOWED.into()),
+                None => return Err(NOT_FOUND.into()),
+            }
+        }
+    };
+
+    if let Some(command) = route.command() {
+        interlock.plan(command, levels).map_err(Error::Interlock)?;
+    }
+    Ok(route)
+}
+
+/// The `Allow` header of a resource, `None` if there is none at `path`
+fn allowed_methods(path: &str) -> Option<&'static str> {
+    match path {
+        "/" | "/index.html" | "/api/outputs" => Some("GET, HEAD"),
+        path => path
+            .strip_prefix("/api/outputs/")
+            .and_then(output)
+            .map(|_| "GET, HEAD, PUT"),
+    }
+}
+
+fn output(name: &str) -> Option<OutputId> {
+    OUTPUTS
+        .iter()
+        .position(|o| o.name.eq_ignore_ascii_case(name))
+        .map(|i| OutputId(i as u8))
+}
+
+/// The level of `{"on":true}` or `{"on":false}`
+fn level(body: &[u8]) -> Result<bool, Status> {
+    let body = core::str::from_utf8(body).map_err(|_| BAD_REQUEST)?;
+    let members = body
+        .trim()
+        .strip_prefix('{')
+        .and_then(|b| b.strip_suffix('}'))
+        .ok_or(BAD_REQUEST)?;
+    let (name, value) = members.split_once(':').ok_or(BAD_REQUEST)?;
+    if name.trim() != "\"on\"" {
+        return Err(BAD_REQUEST);
+    }
+    match value.trim() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        _ => Err(BAD_REQUEST),
+    }
+}
+
+/// Status line, headers and body of an answer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Response<'a> {
+    pub status: Status,
+    pub content_type: &'static str,
+    pub body: &'a [u8],
+    /// For [`METHOD_NOT_ALLOWED`]
+    pub allow: Option<&'static str>,
+}
+
+impl Response<'_> {
+    /// Writes the status line and the headers, up to the empty line. The
+    /// body follows unless the request was `HEAD`.
+    pub fn write_head(&self, keep_alive: bool, out: &mut impl Write) -> fmt::Result {
+        write!(
+            out,
+            "HTTP/1.1 {} {}\r\n",
+            self.status.code, self.status.reason
+        )?;
+        write!(out, "Content-Type: {}\r\n", self.content_type)?;
+        write!(out, "Content-Length: {}\r\n", self.body.len())?;
+        // The levels change without the page
+        out.write_str("Cache-Control: no-store\r\n")?;
+        if let Some(allow) = self.allow {
+            write!(out, "Allow: {allow}\r\n")?;
+        }
+        if !keep_alive {
+            out.write_str("Connection: close\r\n")?;
+        }
+        out.write_str("\r\n")
+    }
+}
+
+const JSON: &str = "application/json";
+
+/// Answers `route`, a request for `path`, with the outputs at `levels`.
+/// JSON bodies are written to `json`.
+pub fn respond<'a, const N: usize>(
+    route: Result<Route, Error>,
+    path: &str,
+    levels: Levels,
+    json: &'a mut String<N>,
+) -> Result<Response<'a>, fmt::Error> {
+    json.clear();
+    let mut response = Response {
+        status: OK,
+        content_type: JSON,
+        body: &[],
+        allow: None,
+    };
+
+    match route {
+        Ok(Route::Page) => {
+            response.content_type = "text/html; charset=utf-8";
+            response.body = PAGE.as_bytes();
+            return Ok(response);
+        }
+        Ok(Route::Outputs) => {
+            json.write_str("{\"outputs\":[")?;
+            for i in 0..OUTPUTS.len() {
+                if i > 0 {
+                    json.write_char(',')?;
+                }
+                write_output(json, OutputId(i as u8), levels)?;
+            }
+            json.write_str("]}")?;
+        }
+        Ok(Route::Output(output) | Route::SetOutput(output, _)) => {
+            write_output(json, output, levels)?;
+        }
+        Err(e) => {
+            response.status = e.status();
+            if response.status == METHOD_NOT_ALLOWED {
+                response.allow = allowed_methods(path);
+            }
+            match e {
+                Error::Interlock(violation) => write!(json, "{{\"error\":\"{violation}\"}}")?,
+                Error::Status(status) => write!(json, "{{\"error\":\"{}\"}}", status.reason)?,
+            }
+        }
+    }
+
+    response.body = json.as_bytes();
+    Ok(response)
+}
+
+fn write_output(out: &mut impl Write, output: OutputId, levels: Levels) -> fmt::Result {
+    write!(
+        out,
+        "{{\"name\":\"{}\",\"on\":{}}}",
+        output.name(),
+        levels.get(output)
+    )
+}
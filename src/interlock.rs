@@ -0,0 +1,283 @@
+//! Interlocks between outputs.
+//!
+//! An [`Interlock`] turns every output [`Command`] into a plan: the steps
+//! the [`OutputBank`](crate::outputs::OutputBank) has to execute, in order
+//! and with delays, so that no [`Rule`] is broken on the way. A command
+//! that cannot be carried out safely is rejected with a [`Violation`].
+//!
+//! - [`Rule::Exclusive`]: at most one output of a group is on. Switching
+//!   one on switches the others off first.
+//! - [`Rule::Requires`]: outputs that may only be on while another one
+//!   is. They are rejected before it, and switched off first when it goes
+//!   off.
+//! - [`Rule::Sequence`]: outputs that go on in order and off in reverse
+//!   order. Switching one on switches those before it on first.
+//!
+//! Each rule has a delay between the steps it adds and the step that
+//! needed them, e.g. for a relay to open before the next one closes.
+//! [`Command::Read`] is never checked. [`Command::SafeState`] is never
+//! rejected: the outputs that go off are switched off in the order of the
+//! rules first, where the rules allow it.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::outputs::{Command, Levels, OutputId, MAX_OUTPUTS, OUTPUTS};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Rule {
+    Exclusive {
+        outputs: Vec<OutputId>,
+        delay_ms: u32,
+    },
+    Requires {
+        outputs: Vec<OutputId>,
+        requires: OutputId,
+        delay_ms: u32,
+    },
+    Sequence {
+        /// In the order they go on
+        outputs: Vec<OutputId>,
+        delay_ms: u32,
+    },
+}
+
+/// One command of a plan, executed `delay_ms` after the step before
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Step {
+    pub delay_ms: u32,
+    pub command: Command,
+}
+
+/// Why a command was rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Violation {
+    /// `output` can only go on while `requires` is on
+    Requires {
+        output: OutputId,
+        requires: OutputId,
+    },
+    /// Other outputs depend on `output`, the end of a pulse would break
+    /// their rules
+    Pulse { output: OutputId },
+    /// The rules depend on each other in a circle around `output`
+    Loop { output: OutputId },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Violation::Requires { output, requires } => {
+                write!(f, "{} requires {}", output.name(), requires.name())
+            }
+            Violation::Pulse { output } => {
+                write!(f, "other outputs depend on {}", output.name())
+            }
+            Violation::Loop { output } => {
+                write!(f, "the rules for {} form a loop", output.name())
+            }
+        }
+    }
+}
+
+impl core::error::Error for Violation {}
+
+/// Rule chains deeper than this are taken for a loop
+const MAX_DEPTH: usize = 16;
+
+/// The rules of one panel
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Interlock {
+    rules: Vec<Rule>,
+}
+
+impl Interlock {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Self { rules }
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// The steps that carry out `command` when the outputs are at
+    /// `levels`, empty if nothing has to change
+    pub fn plan(&self, command: Command, levels: Levels) -> Result<Vec<Step>, Violation> {
+        let mut plan = Plan {
+            rules: &self.rules,
+            levels,
+            steps: Vec::new(),
+            delay_ms: 0,
+        };
+
+        match command {
+            Command::Set(output) => plan.switch_on(output, command, 0)?,
+            Command::Clear(output) => plan.switch_off(output, 0)?,
+            Command::Toggle(output) if levels.get(output) => plan.switch_off(output, 0)?,
+            Command::Toggle(output) => plan.switch_on(output, Command::Set(output), 0)?,
+            Command::Pulse(output, _) => {
+                if self.is_depended_on(output) {
+                    return Err(Violation::Pulse { output });
+                }
+                plan.switch_on(output, command, 0)?;
+            }
+            Command::Read => plan.push(command),
+            Command::SafeState => {
+                // Rules that form a loop leave the order to the bank
+                if plan.switch_all_off().is_err() {
+                    plan.steps.clear();
+                    plan.delay_ms = 0;
+                }
+                plan.push(command);
+            }
+        }
+        Ok(plan.steps)
+    }
+
+    /// Whether switching `output` off may switch other outputs off
+    fn is_depended_on(&self, output: OutputId) -> bool {
+        self.rules.iter().any(|rule| match rule {
+            Rule::Exclusive { .. } => false,
+            Rule::Requires { requires, .. } => *requires == output,
+            Rule::Sequence { outputs, .. } => outputs
+                .iter()
+                .position(|&o| o == output)
+                .is_some_and(|i| i + 1 < outputs.len()),
+        })
+    }
+}
+
+/// A plan while it is made, with the levels the outputs will have after
+/// the steps so far
+struct Plan<'a> {
+    rules: &'a [Rule],
+    levels: Levels,
+    steps: Vec<Step>,
+    /// Before the next step
+    delay_ms: u32,
+}
+
+impl Plan<'_> {
+    fn push(&mut self, command: Command) {
+        self.steps.push(Step {
+            delay_ms: core::mem::take(&mut self.delay_ms),
+            command,
+        });
+    }
+
+    /// The next step waits at least `delay_ms`
+    fn wait(&mut self, delay_ms: u32) {
+        self.delay_ms = self.delay_ms.max(delay_ms);
+    }
+
+    /// Switches `output` on with `command`, after whatever the rules need
+    /// first
+    fn switch_on(
+        &mut self,
+        output: OutputId,
+        command: Command,
+        depth: usize,
+    ) -> Result<(), Violation> {
+        if depth > MAX_DEPTH {
+            return Err(Violation::Loop { output });
+        }
+        if self.levels.get(output) && !matches!(command, Command::Pulse(..)) {
+            return Ok(());
+        }
+
+        for rule in self.rules {
+            match rule {
+                Rule::Requires {
+                    outputs, requires, ..
+                } if outputs.contains(&output) && !self.levels.get(*requires) => {
+                    return Err(Violation::Requires {
+                        output,
+                        requires: *requires,
+                    });
+                }
+                Rule::Sequence { outputs, delay_ms } => {
+                    let Some(i) = outputs.iter().position(|&o| o == output) else {
+                        continue;
+                    };
+                    for &before in &outputs[..i] {
+                        if !self.levels.get(before) {
+                            self.switch_on(before, Command::Set(before), depth + 1)?;
+                            self.wait(*delay_ms);
+                        }
+                    }
+                }
+                Rule::Exclusive { outputs, delay_ms } if outputs.contains(&output) => {
+                    for &other in outputs {
+                        if other != output && self.levels.get(other) {
+                            self.switch_off(other, depth + 1)?;
+                            self.wait(*delay_ms);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.push(command);
+        self.levels.set(output, true);
+        Ok(())
+    }
+
+    /// Switches off every output of [`OUTPUTS`] that is off when safe.
+    /// Whatever depends on an output goes off before it, with the delays
+    /// of the rules.
+    fn switch_all_off(&mut self) -> Result<(), Violation> {
+        for i in 0..MAX_OUTPUTS {
+            let output = OutputId(i as u8);
+            if !OUTPUTS.get(i).is_some_and(|config| config.safe) {
+                self.switch_off(output, 0)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Switches `output` off, after switching off what depends on it
+    fn switch_off(&mut self, output: OutputId, depth: usize) -> Result<(), Violation> {
+        if depth > MAX_DEPTH {
+            return Err(Violation::Loop { output });
+        }
+        if !self.levels.get(output) {
+            return Ok(());
+        }
+
+        for rule in self.rules {
+            match rule {
+                Rule::Requires {
+                    outputs,
+                    requires,
+                    delay_ms,
+                } if *requires == output => {
+                    for &dependent in outputs {
+                        if self.levels.get(dependent) {
+                            self.switch_off(dependent, depth + 1)?;
+                            self.wait(*delay_ms);
+                        }
+                    }
+                }
+                Rule::Sequence { outputs, delay_ms } => {
+                    let Some(i) = outputs.iter().position(|&o| o == output) else {
+                        continue;
+                    };
+                    for &after in outputs[i + 1..].iter().rev() {
+                        if self.levels.get(after) {
+                            self.switch_off(after, depth + 1)?;
+                            self.wait(*delay_ms);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.push(Command::Clear(output));
+        self.levels.set(output, false);
+        Ok(())
+    }
+}
@@ -0,0 +1,276 @@
+//! Safe access to the two LTDC layers.
+//!
+//! Layer 0 is the bottom one and is blended onto the background color,
+//! layer 1 is blended on top of layer 0. Every setter only writes shadow
+//! registers; nothing reaches the panel until [`reload`] is called.
+
+use embassy_stm32::pac::ltdc::vals::{Bf1, Bf2, Imr, Pf, Vbr};
+use embassy_stm32::pac::LTDC;
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+pub use crate::color::PixelFormat;
+
+use crate::color::FrameColor;
+use crate::display::DisplayBuffer;
+use crate::panel::LtdcTiming;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum LayerId {
+    Layer0,
+    Layer1,
+}
+
+impl LayerId {
+    const fn index(self) -> usize {
+        match self {
+            LayerId::Layer0 => 0,
+            LayerId::Layer1 => 1,
+        }
+    }
+}
+
+fn pf(format: PixelFormat) -> Pf {
+    match format {
+        PixelFormat::Argb8888 => Pf::ARGB8888,
+        PixelFormat::Rgb888 => Pf::RGB888,
+        PixelFormat::Rgb565 => Pf::RGB565,
+        PixelFormat::Argb1555 => Pf::ARGB1555,
+        PixelFormat::Argb4444 => Pf::ARGB4444,
+        PixelFormat::L8 => Pf::L8,
+        PixelFormat::Al44 => Pf::AL44,
+        PixelFormat::Al88 => Pf::AL88,
+    }
+}
+
+/// How a layer is mixed with what is below it (BFCR)
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Blending {
+    /// Only the constant alpha is used, the whole layer has one opacity
+    Constant,
+    /// Pixel alpha multiplied by the constant alpha, for overlays with
+    /// transparent areas
+    PixelAlpha,
+}
+
+/// Layer window in active-area coordinates
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Window {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// When shadow registers are copied to the active ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub enum Reload {
+    Immediate,
+    VerticalBlanking,
+}
+
+/// Applies all pending layer changes
+pub fn reload(mode: Reload) {
+    match mode {
+        Reload::Immediate => LTDC.srcr().write(|w| w.set_imr(Imr::RELOAD)),
+        Reload::VerticalBlanking => LTDC.srcr().write(|w| w.set_vbr(Vbr::RELOAD)),
+    }
+}
+
+/// One of the two hardware layers.
+///
+/// Only [`crate::display::init_display`] creates them, so there is never
+/// more than one handle per layer.
+pub struct LtdcLayer {
+    id: LayerId,
+    timing: LtdcTiming,
+    format: PixelFormat,
+    window: Window,
+    /// Width in pixels of the attached frame buffer
+    fb_width: Option<u16>,
+}
+
+impl LtdcLayer {
+    /// Layer covering the whole active area, ARGB8888, fully opaque and
+    /// disabled.
+    pub(crate) fn new(id: LayerId, timing: LtdcTiming) -> Self {
+        let mut layer = Self {
+            id,
+            timing,
+            format: PixelFormat::Argb8888,
+            window: Window {
+                x: 0,
+                y: 0,
+                width: timing.aaw - timing.ahbp,
+                height: timing.aah - timing.avbp,
+            },
+            fb_width: None,
+        };
+
+        layer.disable();
+        layer.set_window(layer.window);
+        layer.set_pixel_format(PixelFormat::Argb8888);
+        layer.set_default_color(Rgb888::BLACK, 0);
+        layer.set_alpha(255);
+        layer.set_blending(Blending::Constant);
+        layer.set_color_key(None);
+        layer
+    }
+
+    pub fn id(&self) -> LayerId {
+        self.id
+    }
+
+    pub fn window(&self) -> Window {
+        self.window
+    }
+
+    pub fn pixel_format(&self) -> PixelFormat {
+        self.format
+    }
+
+    fn regs(&self) -> embassy_stm32::pac::ltdc::Layer {
+        LTDC.layer(self.id.index())
+    }
+
+    pub fn enable(&mut self) {
+        self.regs().cr().modify(|w| w.set_len(true));
+    }
+
+    pub fn disable(&mut self) {
+        self.regs().cr().modify(|w| w.set_len(false));
+    }
+
+    /// Moves and resizes the layer. The window is clipped to the active area.
+    pub fn set_window(&mut self, window: Window) {
+        let active_width = self.timing.aaw - self.timing.ahbp;
+        let active_height = self.timing.aah - self.timing.avbp;
+
+        let x = window.x.min(active_width - 1);
+        let y = window.y.min(active_height - 1);
+        let window = Window {
+            x,
+            y,
+            width: window.width.clamp(1, active_width - x),
+            height: window.height.clamp(1, active_height - y),
+        };
+        self.window = window;
+
+        let ahbp = self.timing.ahbp;
+        let avbp = self.timing.avbp;
+
+        // Configure the horizontal start and stop position
+        self.regs().whpcr().write(|w| {
+            w.set_whstpos(ahbp + 1 + window.x);
+            w.set_whsppos(ahbp + window.x + window.width);
+        });
+
+        // Configures the vertical start and stop position
+        self.regs().wvpcr().write(|w| {
+            w.set_wvstpos(avbp + 1 + window.y);
+            w.set_wvsppos(avbp + window.y + window.height);
+        });
+
+        self.update_line_length();
+    }
+
+    /// The CLUT is switched on for the L8, AL44 and AL88 formats.
+    pub fn set_pixel_format(&mut self, format: PixelFormat) {
+        self.format = format;
+        self.regs().pfcr().write(|w| w.set_pf(pf(format)));
+        self.regs().cr().modify(|w| {
+            w.set_cluten(matches!(
+                format,
+                PixelFormat::L8 | PixelFormat::Al44 | PixelFormat::Al88
+            ))
+        });
+        self.update_line_length();
+    }
+
+    /// Loads `palette` into the color lookup table, entry `i` is the color
+    /// of index `i`. At most 256 entries are used.
+    ///
+    /// The CLUT should only be written while the layer is disabled or
+    /// during vertical blanking.
+    pub fn load_clut(&mut self, palette: &[Rgb888]) {
+        for (i, color) in palette.iter().take(256).enumerate() {
+            self.regs().clutwr().write(|w| {
+                w.set_clutadd(i as u8);
+                w.set_red(color.r());
+                w.set_green(color.g());
+                w.set_blue(color.b());
+            });
+        }
+    }
+
+    /// Constant alpha, 255 is opaque
+    pub fn set_alpha(&mut self, alpha: u8) {
+        self.regs().cacr().write(|w| w.set_consta(alpha));
+    }
+
+    pub fn set_blending(&mut self, blending: Blending) {
+        self.regs().bfcr().write(|w| match blending {
+            Blending::Constant => {
+                w.set_bf1(Bf1::CONSTANT);
+                w.set_bf2(Bf2::CONSTANT);
+            }
+            Blending::PixelAlpha => {
+                w.set_bf1(Bf1::PIXEL);
+                w.set_bf2(Bf2::PIXEL);
+            }
+        });
+    }
+
+    /// Color shown outside the window and when the layer is disabled
+    pub fn set_default_color(&mut self, color: Rgb888, alpha: u8) {
+        self.regs().dccr().write(|w| {
+            w.set_dcblue(color.b());
+            w.set_dcgreen(color.g());
+            w.set_dcred(color.r());
+            w.set_dcalpha(alpha);
+        });
+    }
+
+    /// Pixels of exactly `key` become fully transparent, `None` disables keying
+    pub fn set_color_key(&mut self, key: Option<Rgb888>) {
+        if let Some(key) = key {
+            self.regs().ckcr().write(|w| {
+                w.set_ckblue(key.b());
+                w.set_ckgreen(key.g());
+                w.set_ckred(key.r());
+            });
+        }
+        self.regs().cr().modify(|w| w.set_colken(key.is_some()));
+    }
+
+    /// Points the layer at `fb` and switches to its pixel format.
+    ///
+    /// The buffer has to be `'static` since the LTDC keeps reading it after
+    /// this call returns.
+    pub fn set_framebuffer<C: FrameColor>(&mut self, fb: &DisplayBuffer<'static, C>) {
+        self.fb_width = Some(fb.width as u16);
+        if self.format != C::FORMAT {
+            self.set_pixel_format(C::FORMAT);
+        }
+
+        self.regs()
+            .cfbar()
+            .write(|w| w.set_cfbadd(fb.buf.as_ptr() as u32));
+        self.update_line_length();
+    }
+
+    fn update_line_length(&mut self) {
+        let bpp = self.format.bytes_per_pixel();
+        let window = self.window;
+        // Until a buffer is set assume it is exactly as wide as the window
+        let pitch = self.fb_width.unwrap_or(window.width) * bpp;
+
+        // Configures the color frame buffer pitch in byte
+        self.regs().cfblr().write(|w| {
+            w.set_cfbp(pitch);
+            w.set_cfbll(window.width * bpp + 3);
+        });
+
+        // Configures the frame buffer line number
+        self.regs().cfblnr().write(|w| w.set_cfblnbr(window.height));
+    }
+}
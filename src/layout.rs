@@ -0,0 +1,427 @@
+//! Rows, columns and grids that compute widget rectangles from the screen
+//! size.
+//!
+//! A layout is a tree of [`Layout`] nodes. Leaves carry a key, usually a
+//! [`WidgetId`], and [`Layout::apply`] reports the rectangle of every leaf
+//! for a given area. Sizes are given in pixels, as a percentage of the
+//! parent, or as a share of the space left over, so the same description
+//! works for other panel resolutions.
+//!
+//! ```ignore
+//! Layout::column([
+//!     Layout::leaf(title).height(Length::Px(30)),
+//!     Layout::row([Layout::leaf(ok), Layout::leaf(cancel)]).gap(8),
+//! ])
+//! .padding(Insets::all(10))
+//! .apply_to(&mut ui, screen);
+//! ```
+
+use alloc::vec::Vec;
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+
+use crate::widgets::{WidgetId, WidgetTree};
+
+/// Size along one axis
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Length {
+    Px(u32),
+    /// Of the parent's inner size, 0..=100
+    Percent(u8),
+    /// Share of the space left after the fixed and percentage sizes,
+    /// weighted against the other `Fill`s. In the cross direction a `Fill`
+    /// stretches over the whole parent.
+    Fill(u16),
+}
+
+impl Length {
+    fn fixed(self, available: u32) -> Option<u32> {
+        match self {
+            Length::Px(px) => Some(px.min(available)),
+            Length::Percent(p) => Some(available * p.min(100) as u32 / 100),
+            Length::Fill(_) => None,
+        }
+    }
+}
+
+/// Position in the cross direction, or of the whole group in the main
+/// direction when nothing fills it
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Align {
+    #[default]
+    Start,
+    Center,
+    End,
+}
+
+impl Align {
+    fn offset(self, free: u32) -> u32 {
+        match self {
+            Align::Start => 0,
+            Align::Center => free / 2,
+            Align::End => free,
+        }
+    }
+}
+
+/// How free space in the main direction is spread
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Justify {
+    /// Children packed together, placed by the [`Align`] of the group
+    #[default]
+    Packed,
+    /// First and last child at the edges, equal space in between
+    SpaceBetween,
+    /// Equal space around and between all children
+    SpaceEvenly,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Insets {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+impl Insets {
+    pub const fn all(px: u32) -> Self {
+        Self {
+            top: px,
+            right: px,
+            bottom: px,
+            left: px,
+        }
+    }
+
+    pub const fn symmetric(horizontal: u32, vertical: u32) -> Self {
+        Self {
+            top: vertical,
+            right: horizontal,
+            bottom: vertical,
+            left: horizontal,
+        }
+    }
+
+    /// `area` shrunk by the insets
+    pub fn shrink(&self, area: &Rectangle) -> Rectangle {
+        Rectangle::new(
+            area.top_left + Point::new(self.left as i32, self.top as i32),
+            Size::new(
+                area.size.width.saturating_sub(self.left + self.right),
+                area.size.height.saturating_sub(self.top + self.bottom),
+            ),
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Kind<K> {
+    Leaf(K),
+    /// Takes up space, nothing is placed there
+    Spacer,
+    Row(Vec<Layout<K>>),
+    Column(Vec<Layout<K>>),
+    /// Children fill the cells row by row
+    Grid {
+        columns: u16,
+        rows: u16,
+        children: Vec<Layout<K>>,
+    },
+}
+
+/// One node of a layout tree
+#[derive(Clone, Debug)]
+pub struct Layout<K = WidgetId> {
+    pub kind: Kind<K>,
+    pub width: Length,
+    pub height: Length,
+    pub padding: Insets,
+    /// Between children, for grids between both columns and rows
+    pub gap: u32,
+    /// Of the children, across the main direction
+    pub align: Align,
+    /// Overrides the parent's `align` for this node
+    pub align_self: Option<Align>,
+    pub justify: Justify,
+}
+
+impl<K: Copy> Layout<K> {
+    fn new(kind: Kind<K>) -> Self {
+        Self {
+            kind,
+            width: Length::Fill(1),
+            height: Length::Fill(1),
+            padding: Insets::default(),
+            gap: 0,
+            align: Align::Start,
+            align_self: None,
+            justify: Justify::Packed,
+        }
+    }
+
+    pub fn leaf(key: K) -> Self {
+        Self::new(Kind::Leaf(key))
+    }
+
+    pub fn spacer() -> Self {
+        Self::new(Kind::Spacer)
+    }
+
+    /// Children left to right
+    pub fn row(children: impl IntoIterator<Item = Layout<K>>) -> Self {
+        Self::new(Kind::Row(children.into_iter().collect()))
+    }
+
+    /// Children top to bottom
+    pub fn column(children: impl IntoIterator<Item = Layout<K>>) -> Self {
+        Self::new(Kind::Column(children.into_iter().collect()))
+    }
+
+    /// `columns` x `rows` equal cells
+    pub fn grid(columns: u16, rows: u16, children: impl IntoIterator<Item = Layout<K>>) -> Self {
+        Self::new(Kind::Grid {
+            columns: columns.max(1),
+            rows: rows.max(1),
+            children: children.into_iter().collect(),
+        })
+    }
+
+    pub fn width(mut self, width: Length) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn height(mut self, height: Length) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// Fixed size in pixels
+    pub fn size(self, width: u32, height: u32) -> Self {
+        self.width(Length::Px(width)).height(Length::Px(height))
+    }
+
+    pub fn padding(mut self, padding: Insets) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn gap(mut self, gap: u32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn align_self(mut self, align: Align) -> Self {
+        self.align_self = Some(align);
+        self
+    }
+
+    pub fn justify(mut self, justify: Justify) -> Self {
+        self.justify = justify;
+        self
+    }
+
+    /// Lays the tree out in `area` and calls `place` for every leaf
+    pub fn apply(&self, area: Rectangle, place: &mut impl FnMut(K, Rectangle)) {
+        let inner = self.padding.shrink(&area);
+
+        match &self.kind {
+            Kind::Leaf(key) => place(*key, inner),
+            Kind::Spacer => {}
+            Kind::Row(children) => self.linear(children, inner, Axis::Horizontal, place),
+            Kind::Column(children) => self.linear(children, inner, Axis::Vertical, place),
+            Kind::Grid {
+                columns,
+                rows,
+                children,
+            } => self.grid_cells(*columns, *rows, children, inner, place),
+        }
+    }
+
+    /// Moves every widget of the layout in `ui` to its place in `area`
+    pub fn apply_to(&self, ui: &mut WidgetTree, area: Rectangle)
+    where
+        K: Into<WidgetId>,
+    {
+        self.apply(area, &mut |key, bounds| ui.set_bounds(key.into(), bounds));
+    }
+
+    /// The rectangle of every leaf in `area`
+    pub fn compute(&self, area: Rectangle) -> Vec<(K, Rectangle)> {
+        let mut placed = Vec::new();
+        self.apply(area, &mut |key, bounds| placed.push((key, bounds)));
+        placed
+    }
+
+    fn linear(
+        &self,
+        children: &[Layout<K>],
+        inner: Rectangle,
+        axis: Axis,
+        place: &mut impl FnMut(K, Rectangle),
+    ) {
+        if children.is_empty() {
+            return;
+        }
+
+        let main_size = axis.main(inner.size);
+        let cross_size = axis.cross(inner.size);
+        let gaps = self.gap * (children.len() as u32 - 1);
+        let available = main_size.saturating_sub(gaps);
+
+        // Fixed and percentage sizes first, the rest is shared by the fills
+        let mut sizes: Vec<u32> = children
+            .iter()
+            .map(|c| axis.main_length(c).fixed(available).unwrap_or(0))
+            .collect();
+        let used: u32 = sizes.iter().sum();
+        let mut free = available.saturating_sub(used);
+
+        let weights: u32 = children
+            .iter()
+            .map(|c| match axis.main_length(c) {
+                Length::Fill(w) => w as u32,
+                _ => 0,
+            })
+            .sum();
+        if weights > 0 {
+            let mut remaining = free;
+            let mut remaining_weight = weights;
+            for (size, child) in sizes.iter_mut().zip(children) {
+                if let Length::Fill(w) = axis.main_length(child) {
+                    // Last fills get the rounding remainder
+                    let share = remaining * w as u32 / remaining_weight.max(1);
+                    *size = share;
+                    remaining -= share;
+                    remaining_weight -= w as u32;
+                }
+            }
+            free = 0;
+        }
+
+        let n = children.len() as u32;
+        let (mut pos, spacing) = match self.justify {
+            Justify::Packed => (self.align.offset(free), 0),
+            Justify::SpaceBetween if n > 1 => (0, free / (n - 1)),
+            Justify::SpaceBetween => (self.align.offset(free), 0),
+            Justify::SpaceEvenly => (free / (n + 1), free / (n + 1)),
+        };
+
+        for (child, size) in children.iter().zip(sizes) {
+            let cross = axis
+                .cross_length(child)
+                .fixed(cross_size)
+                .unwrap_or(cross_size);
+            let align = child.align_self.unwrap_or(self.align);
+            let cross_pos = align.offset(cross_size - cross);
+
+            let area = axis.rect(inner.top_left, pos, cross_pos, size, cross);
+            child.apply(area, place);
+
+            pos += size + self.gap + spacing;
+        }
+    }
+
+    fn grid_cells(
+        &self,
+        columns: u16,
+        rows: u16,
+        children: &[Layout<K>],
+        inner: Rectangle,
+        place: &mut impl FnMut(K, Rectangle),
+    ) {
+        let (columns, rows) = (columns as u32, rows as u32);
+        let cell_width = inner.size.width.saturating_sub(self.gap * (columns - 1)) / columns;
+        let cell_height = inner.size.height.saturating_sub(self.gap * (rows - 1)) / rows;
+
+        for (i, child) in children.iter().enumerate().take((columns * rows) as usize) {
+            let (column, row) = (i as u32 % columns, i as u32 / columns);
+            let cell_origin = inner.top_left
+                + Point::new(
+                    (column * (cell_width + self.gap)) as i32,
+                    (row * (cell_height + self.gap)) as i32,
+                );
+
+            // Inside its cell a child is sized and aligned like in a row
+            let width = child.width.fixed(cell_width).unwrap_or(cell_width);
+            let height = child.height.fixed(cell_height).unwrap_or(cell_height);
+            let align = child.align_self.unwrap_or(self.align);
+            let offset = Point::new(
+                align.offset(cell_width - width) as i32,
+                align.offset(cell_height - height) as i32,
+            );
+
+            child.apply(
+                Rectangle::new(cell_origin + offset, Size::new(width, height)),
+                place,
+            );
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Axis {
+    Horizontal,
+    Vertical,
+}
+
+impl Axis {
+    fn main(self, size: Size) -> u32 {
+        match self {
+            Axis::Horizontal => size.width,
+            Axis::Vertical => size.height,
+        }
+    }
+
+    fn cross(self, size: Size) -> u32 {
+        match self {
+            Axis::Horizontal => size.height,
+            Axis::Vertical => size.width,
+        }
+    }
+
+    fn main_length<K>(self, layout: &Layout<K>) -> Length {
+        match self {
+            Axis::Horizontal => layout.width,
+            Axis::Vertical => layout.height,
+        }
+    }
+
+    fn cross_length<K>(self, layout: &Layout<K>) -> Length {
+        match self {
+            Axis::Horizontal => layout.height,
+            Axis::Vertical => layout.width,
+        }
+    }
+
+    fn rect(
+        self,
+        origin: Point,
+        main: u32,
+        cross: u32,
+        main_size: u32,
+        cross_size: u32,
+    ) -> Rectangle {
+        match self {
+            Axis::Horizontal => Rectangle::new(
+                origin + Point::new(main as i32, cross as i32),
+                Size::new(main_size, cross_size),
+            ),
+            Axis::Vertical => Rectangle::new(
+                origin + Point::new(cross as i32, main as i32),
+                Size::new(cross_size, main_size),
+            ),
+        }
+    }
+}
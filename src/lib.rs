@@ -0,0 +1,63 @@
+//! Board support for the STM32F746G-DISCO.
+//!
+//! `Board::init` brings up clocks, the SDRAM heap, the LTDC display and the
+//! FT5336 touch panel and hands out typed handles for everything else.
+//!
+//! Without the default `hw` feature only the GUI modules are built, the
+//! `sim` feature adds a host-side simulator on top of them.
+#![no_std]
+
+extern crate alloc;
+#[cfg(feature = "sim")]
+extern crate std;
+
+#[cfg(feature = "hw")]
+pub mod board;
+pub mod calibration;
+pub mod color;
+pub mod damage;
+#[cfg(feature = "hw")]
+pub mod display;
+#[cfg(feature = "hw")]
+pub mod dma2d;
+pub mod framebuffer;
+pub mod gesture;
+#[cfg(feature = "hw")]
+pub mod gpio;
+pub mod http;
+pub mod interlock;
+#[cfg(feature = "hw")]
+pub mod layer;
+pub mod layout;
+#[cfg(feature = "hw")]
+pub mod link;
+pub mod multitouch;
+#[cfg(feature = "hw")]
+pub mod net;
+pub mod netconfig;
+pub mod outputs;
+pub mod panel;
+pub mod rcc;
+pub mod remote;
+pub mod rotation;
+pub mod scpi;
+pub mod screens;
+#[cfg(feature = "hw")]
+pub mod sdram;
+pub mod shared;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(feature = "hw")]
+pub mod storage;
+#[cfg(feature = "hw")]
+pub mod swapchain;
+#[cfg(feature = "hw")]
+pub mod tasks;
+#[cfg(feature = "hw")]
+pub mod touch;
+#[cfg(feature = "hw")]
+pub mod usb;
+pub mod widgets;
+
+#[cfg(feature = "hw")]
+pub use board::Board;
@@ -0,0 +1,315 @@
+//! Byte streams the control protocols are served on.
+//!
+//! The remote control protocol of [`crate::remote`], [`crate::scpi`] and
+//! [`crate::http`] only need to read bytes and write replies, a [`Link`]
+//! does that on a USB serial port or a TCP connection. The serve functions
+//! run one connection until the link closes.
+
+use defmt::*;
+use embassy_futures::select::{select, Either};
+use embassy_net::tcp::TcpSocket;
+use embassy_time::{with_deadline, with_timeout, Duration, Instant};
+use embassy_usb::class::cdc_acm::CdcAcmClass;
+use embedded_io_async::Write;
+
+use crate::http::{self, Method, Request, Route};
+use crate::interlock::Interlock;
+use crate::netconfig::NetChange;
+use crate::outputs::Levels;
+use crate::remote::{Effect, LineBuffer, RequestError, Session};
+use crate::scpi::{self, Scpi};
+use crate::shared::{state, StateReceiver, NET_SETTINGS, OUTPUT_COMMANDS};
+use crate::usb::{UsbDriver, MAX_PACKET_SIZE};
+
+/// The other side went away, or was idle for too long
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Closed;
+
+// Only used with concrete links on the single-threaded executor, the
+// futures need no `Send` bound
+#[allow(async_fn_in_trait)]
+pub trait Link {
+    /// Reads at least one byte into `buf`, which has to hold a USB packet
+    /// on USB
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Closed>;
+
+    async fn write_all(&mut self, data: &[u8]) -> Result<(), Closed>;
+}
+
+impl Link for CdcAcmClass<'static, UsbDriver> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Closed> {
+        // A cancelled read leaves the packet in the endpoint
+        self.read_packet(buf).await.map_err(|_| Closed)
+    }
+
+    async fn write_all(&mut self, data: &[u8]) -> Result<(), Closed> {
+        let max = MAX_PACKET_SIZE as usize;
+        for packet in data.chunks(max) {
+            self.write_packet(packet).await.map_err(|_| Closed)?;
+        }
+        // A full last packet does not end the transfer
+        if !data.is_empty() && data.len() % max == 0 {
+            self.write_packet(&[]).await.map_err(|_| Closed)?;
+        }
+        Ok(())
+    }
+}
+
+/// An accepted TCP connection that closes after `idle` without a byte
+/// from the client
+pub struct TcpLink<'a> {
+    socket: TcpSocket<'a>,
+    idle: Duration,
+    last_read: Instant,
+}
+
+impl<'a> TcpLink<'a> {
+    pub fn new(socket: TcpSocket<'a>, idle: Duration) -> Self {
+        Self {
+            socket,
+            idle,
+            last_read: Instant::now(),
+        }
+    }
+
+    /// Sends what is left and closes the connection, aborts it if the
+    /// client does not take the data within a second
+    pub async fn close(mut self) {
+        self.socket.close();
+        if with_timeout(Duration::from_secs(1), self.socket.flush())
+            .await
+            .is_err()
+        {
+            self.socket.abort();
+        }
+    }
+}
+
+impl Link for TcpLink<'_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Closed> {
+        // From the last byte read, the events written in between do not
+        // keep a connection open
+        let deadline = self.last_read + self.idle;
+        match with_deadline(deadline, self.socket.read(buf)).await {
+            Ok(Ok(len)) if len > 0 => {
+                self.last_read = Instant::now();
+                Ok(len)
+            }
+            Ok(_) => Err(Closed),
+            Err(_) => {
+                info!("TCP connection idle for {} s", self.idle.as_secs());
+                Err(Closed)
+            }
+        }
+    }
+
+    async fn write_all(&mut self, data: &[u8]) -> Result<(), Closed> {
+        self.socket.write_all(data).await.map_err(|_| Closed)?;
+        self.socket.flush().await.map_err(|_| Closed)
+    }
+}
+
+/// Longest request line
+const LINE_LEN: usize = 128;
+
+/// Serves the remote control protocol. Commands go to the outputs task
+/// like the touchscreen's and network settings to the network task,
+/// subscribers get the changes from the shared state.
+pub async fn serve_remote(
+    link: &mut impl Link,
+    state_changes: &mut StateReceiver,
+) -> Result<(), Closed> {
+    let mut session = Session::default();
+    let mut lines = LineBuffer::<LINE_LEN>::new();
+    let mut reported = state();
+    let mut buf = [0; MAX_PACKET_SIZE as usize];
+    let mut reply = heapless::String::<256>::new();
+
+    loop {
+        match select(link.read(&mut buf), state_changes.changed()).await {
+            Either::First(len) => {
+                for &byte in &buf[..len?] {
+                    let Some(line) = lines.push(byte) else {
+                        continue;
+                    };
+                    reply.clear();
+                    match session.handle(line, &state(), &mut reply) {
+                        Ok(Some(Effect::Output(command))) => OUTPUT_COMMANDS.send(command).await,
+                        Ok(Some(Effect::Network(setting))) => {
+                            NET_SETTINGS.send(NetChange::Setting(setting)).await
+                        }
+                        Ok(None) => {}
+                        Err(_) => warn!("Reply does not fit its buffer"),
+                    }
+                    link.write_all(reply.as_bytes()).await?;
+                }
+            }
+            Either::Second(new) => {
+                reply.clear();
+                let written = session
+                    .events(reported.outputs, new.outputs, &mut reply)
+                    .and_then(|_| {
+                        session.network_events(&reported.network, &new.network, &mut reply)
+                    });
+                if written.is_err() {
+                    warn!("Events do not fit their buffer");
+                }
+                reported = new;
+                link.write_all(reply.as_bytes()).await?;
+            }
+        }
+    }
+}
+
+/// Longest wait for the outputs after a change, the interlock delays are
+/// far shorter
+const SETTLE: Duration = Duration::from_secs(2);
+
+/// Waits until the outputs are `done`, false if they are not after
+/// [`SETTLE`]
+async fn settle(state_changes: &mut StateReceiver, done: impl Fn(Levels) -> bool) -> bool {
+    let settled = async {
+        while !done(state().outputs) {
+            state_changes.changed().await;
+        }
+    };
+    with_timeout(SETTLE, settled).await.is_ok()
+}
+
+/// Serves SCPI, one program message per line
+pub async fn serve_scpi(
+    link: &mut impl Link,
+    scpi: &mut Scpi,
+    state_changes: &mut StateReceiver,
+) -> Result<(), Closed> {
+    let mut lines = LineBuffer::<LINE_LEN>::new();
+    let mut buf = [0; MAX_PACKET_SIZE as usize];
+    let mut reply = heapless::String::<256>::new();
+
+    loop {
+        let len = link.read(&mut buf).await?;
+        for &byte in &buf[..len] {
+            let message = match lines.push(byte) {
+                None => continue,
+                Some(Ok(message)) => message,
+                Some(Err(RequestError::TooLong)) => {
+                    scpi.push_error(scpi::INPUT_BUFFER_OVERRUN);
+                    continue;
+                }
+                Some(Err(_)) => {
+                    scpi.push_error(scpi::SYNTAX_ERROR);
+                    continue;
+                }
+            };
+
+            reply.clear();
+            let Ok(effect) = scpi.process(message, state().outputs, &mut reply) else {
+                warn!("SCPI response does not fit its buffer, message dropped");
+                continue;
+            };
+            link.write_all(reply.as_bytes()).await?;
+
+            // The next message sees what this one did, like on an
+            // instrument that executes commands in order
+            if !effect.commands.is_empty() {
+                for command in effect.commands {
+                    OUTPUT_COMMANDS.send(command).await;
+                }
+                if !settle(state_changes, |levels| levels == effect.levels).await {
+                    warn!(
+                        "Outputs did not reach {} after SCPI commands",
+                        effect.levels
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Longest HTTP request, a browser's head with a few cookies and the body
+const HTTP_REQUEST_LEN: usize = 2048;
+
+/// Serves the web page and the REST API of [`crate::http`]. A change is
+/// answered once the outputs followed it, or did not in time.
+pub async fn serve_http(
+    link: &mut impl Link,
+    interlock: &Interlock,
+    state_changes: &mut StateReceiver,
+) -> Result<(), Closed> {
+    let mut buf = [0; HTTP_REQUEST_LEN];
+    let mut len = 0;
+
+    loop {
+        let parsed = match Request::parse(&buf[..len]) {
+            Ok(None) if len < buf.len() => {
+                len += link.read(&mut buf[len..]).await?;
+                continue;
+            }
+            Ok(None) => Err(http::HEADERS_TOO_LARGE),
+            Ok(Some(parsed)) => Ok(parsed),
+            Err(status) => Err(status),
+        };
+        let (request, request_len) = match parsed {
+            Ok(parsed) => parsed,
+            Err(status) => {
+                // The rest of the stream cannot be parsed, answer and close
+                info!("HTTP request rejected: {}", status);
+                return answer(link, Err(status.into()), "", true, false, state_changes).await;
+            }
+        };
+
+        let route = http::route(&request, state().outputs, interlock);
+        match route {
+            Ok(route) => debug!("HTTP {} {}", request.method, route),
+            Err(e) => info!("HTTP {} {} rejected: {}", request.method, request.path, e),
+        }
+        let with_body = request.method != Method::Head;
+        answer(
+            link,
+            route,
+            request.path,
+            with_body,
+            request.keep_alive,
+            state_changes,
+        )
+        .await?;
+        if !request.keep_alive {
+            return Ok(());
+        }
+
+        // Pipelined requests may follow
+        buf.copy_within(request_len..len, 0);
+        len -= request_len;
+    }
+}
+
+/// Carries out `route` and writes the response
+async fn answer(
+    link: &mut impl Link,
+    route: Result<Route, http::Error>,
+    path: &str,
+    with_body: bool,
+    keep_alive: bool,
+    state_changes: &mut StateReceiver,
+) -> Result<(), Closed> {
+    if let Ok(set @ Route::SetOutput(output, on)) = route {
+        OUTPUT_COMMANDS.send(unwrap!(set.command())).await;
+        if !settle(state_changes, |levels| levels.get(output) == on).await {
+            warn!("{} did not follow the HTTP request", output);
+        }
+    }
+
+    let mut json = heapless::String::<256>::new();
+    let mut head = heapless::String::<256>::new();
+    let written = http::respond(route, path, state().outputs, &mut json)
+        .and_then(|response| response.write_head(keep_alive, &mut head).map(|_| response));
+    let Ok(response) = written else {
+        warn!("HTTP response does not fit its buffer");
+        return Err(Closed);
+    };
+    link.write_all(head.as_bytes()).await?;
+    if with_body {
+        link.write_all(response.body).await?;
+    }
+    Ok(())
+}
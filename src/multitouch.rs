@@ -0,0 +1,164 @@
+//! Fingers on the FT5336 and the down/move/up events they make.
+//!
+//! The controller tracks up to five touch points and gives each one an ID
+//! that stays the same while the finger is down. [`parse_registers`]
+//! decodes one read of its point registers, [`Tracker`] compares
+//! successive reads and reports what changed.
+
+use embedded_graphics::geometry::Point;
+use heapless::Vec;
+
+/// Touch points the FT5336 reports at once
+pub const MAX_CONTACTS: usize = 5;
+
+/// First register of a point read, TD_STATUS with the number of points
+pub const REGISTERS_START: u8 = 0x02;
+
+/// TD_STATUS and 6 registers per point
+pub const REGISTERS_LEN: usize = 1 + 6 * MAX_CONTACTS;
+
+/// One finger on the panel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Contact {
+    /// Stays the same while the finger is down
+    pub id: u8,
+    /// In controller coordinates from [`parse_registers`], in screen
+    /// coordinates once mapped by a
+    /// [`Transform`](crate::calibration::Transform)
+    pub point: Point,
+}
+
+pub type Contacts = Vec<Contact, MAX_CONTACTS>;
+
+/// Event flag in the high bits of P_XH
+const EVENT_LIFT_UP: u8 = 1;
+const EVENT_NONE: u8 = 3;
+
+/// Decodes `REGISTERS_LEN` bytes read from `REGISTERS_START`. Points that
+/// are just being lifted are left out.
+pub fn parse_registers(registers: &[u8; REGISTERS_LEN]) -> Contacts {
+    let count = (registers[0] & 0x0F) as usize;
+
+    registers[1..]
+        .chunks_exact(6)
+        .take(count.min(MAX_CONTACTS))
+        .filter_map(|point| {
+            let event = point[0] >> 6;
+            let id = point[2] >> 4;
+            if event == EVENT_LIFT_UP || event == EVENT_NONE || id as usize >= MAX_CONTACTS {
+                return None;
+            }
+
+            let x = ((point[0] & 0x0F) as i32) << 8 | point[1] as i32;
+            let y = ((point[2] & 0x0F) as i32) << 8 | point[3] as i32;
+            Some(Contact {
+                id,
+                point: Point::new(x, y),
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum TouchEvent {
+    Down {
+        id: u8,
+        point: Point,
+    },
+    Move {
+        id: u8,
+        point: Point,
+    },
+    /// `point` is where the finger was last seen
+    Up {
+        id: u8,
+        point: Point,
+    },
+}
+
+impl TouchEvent {
+    pub fn id(&self) -> u8 {
+        match *self {
+            TouchEvent::Down { id, .. }
+            | TouchEvent::Move { id, .. }
+            | TouchEvent::Up { id, .. } => id,
+        }
+    }
+
+    pub fn point(&self) -> Point {
+        match *self {
+            TouchEvent::Down { point, .. }
+            | TouchEvent::Move { point, .. }
+            | TouchEvent::Up { point, .. } => point,
+        }
+    }
+}
+
+/// Events of one update, at most every finger lifted and put down again
+pub type TouchEvents = Vec<TouchEvent, { 2 * MAX_CONTACTS }>;
+
+/// Remembers the fingers of the last read
+#[derive(Clone, Debug, Default)]
+pub struct Tracker {
+    contacts: Contacts,
+}
+
+impl Tracker {
+    /// Fingers currently down
+    pub fn contacts(&self) -> &[Contact] {
+        &self.contacts
+    }
+
+    /// Compares `contacts` with the last read: lifted fingers first, then
+    /// the ones that moved, then new ones
+    pub fn update(&mut self, contacts: &[Contact]) -> TouchEvents {
+        let mut events = TouchEvents::new();
+        let find = |list: &[Contact], id| list.iter().find(|c: &&Contact| c.id == id).copied();
+
+        for old in &self.contacts {
+            if find(contacts, old.id).is_none() {
+                let _ = events.push(TouchEvent::Up {
+                    id: old.id,
+                    point: old.point,
+                });
+            }
+        }
+        for new in contacts {
+            match find(&self.contacts, new.id) {
+                Some(old) if old.point != new.point => {
+                    let _ = events.push(TouchEvent::Move {
+                        id: new.id,
+                        point: new.point,
+                    });
+                }
+                Some(_) => {}
+                None => {
+                    let _ = events.push(TouchEvent::Down {
+                        id: new.id,
+                        point: new.point,
+                    });
+                }
+            }
+        }
+
+        self.contacts = contacts.iter().copied().take(MAX_CONTACTS).collect();
+        events
+    }
+}
+
+/// A read in the format of the recorded touch traces, `t id:x,y ...`.
+/// Logged at trace level by the touch task.
+#[cfg(feature = "hw")]
+pub struct TraceLine<'a>(pub u32, pub &'a [Contact]);
+
+#[cfg(feature = "hw")]
+impl defmt::Format for TraceLine<'_> {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=u32}", self.0);
+        for c in self.1 {
+            defmt::write!(f, " {=u8}:{=i32},{=i32}", c.id, c.point.x, c.point.y);
+        }
+    }
+}
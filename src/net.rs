@@ -0,0 +1,148 @@
+//! Ethernet on the LAN8742A PHY (CN9) with the embassy-net stack, and the
+//! TCP servers for the control protocols.
+//!
+//! [`init_net`] hands out the stack and the runner that has to be run, see
+//! `tasks::net_task`. `tasks::network_task` applies the settings of
+//! [`crate::netconfig`] and falls back from DHCP to the static ones. Every TCP session is a `tasks::tcp_session_task` with
+//! its own socket, a server takes as many connections at once as it has
+//! sessions.
+
+use embassy_net::{Config, Ipv4Cidr, Runner, Stack, StackResources, StaticConfigV4};
+use embassy_stm32::eth::{self, Ethernet, GenericPhy, PacketQueue};
+use embassy_stm32::peripherals::{ETH, RNG};
+use embassy_stm32::rng::{self, Rng};
+use embassy_stm32::{bind_interrupts, Peri};
+use embassy_time::Duration;
+use static_cell::StaticCell;
+
+use crate::board::EthPeripherals;
+use crate::netconfig::{self, NetConfig};
+use crate::scpi::Instrument;
+
+bind_interrupts!(struct Irqs {
+    ETH => eth::InterruptHandler;
+    RNG => rng::InterruptHandler<RNG>;
+});
+
+pub type Device = Ethernet<'static, ETH, GenericPhy>;
+
+/// Sockets of the stack, for the TCP sessions and DHCP
+pub const SOCKETS: usize = TCP_SESSIONS + 2;
+
+/// This board's MAC address, from the chip's unique ID
+pub fn mac_address() -> [u8; 6] {
+    netconfig::mac_address(embassy_stm32::uid::uid())
+}
+
+/// The static settings of `config`
+pub fn static_v4(config: &NetConfig) -> StaticConfigV4 {
+    let mut v4 = StaticConfigV4 {
+        address: Ipv4Cidr::new(config.address, config.prefix_len),
+        dns_servers: Default::default(),
+        gateway: config.gateway,
+    };
+    v4.dns_servers.extend(config.dns);
+    v4
+}
+
+/// What the stack starts with: DHCP, or the static settings right away
+pub fn config(config: &NetConfig) -> Config {
+    if config.dhcp {
+        Config::dhcpv4(Default::default())
+    } else {
+        Config::ipv4_static(static_v4(config))
+    }
+}
+
+/// Brings up the MAC and the stack with `config`. May be called once.
+pub fn init_net(
+    p: EthPeripherals,
+    rng: Peri<'static, RNG>,
+    mac_address: [u8; 6],
+    config: Config,
+) -> (Stack<'static>, Runner<'static, Device>) {
+    static PACKETS: StaticCell<PacketQueue<4, 4>> = StaticCell::new();
+    static RESOURCES: StaticCell<StackResources<SOCKETS>> = StaticCell::new();
+
+    // Random local ports and TCP sequence numbers after every reset
+    let mut rng = Rng::new(rng, Irqs);
+    let mut seed = [0; 8];
+    rng.fill_bytes(&mut seed);
+
+    let device = Ethernet::new(
+        PACKETS.init(PacketQueue::new()),
+        p.eth,
+        Irqs,
+        p.ref_clk,
+        p.mdio,
+        p.mdc,
+        p.crs,
+        p.rx_d0,
+        p.rx_d1,
+        p.tx_d0,
+        p.tx_d1,
+        p.tx_en,
+        GenericPhy::new_auto(),
+        mac_address,
+    );
+
+    embassy_net::new(
+        device,
+        config,
+        RESOURCES.init(StackResources::new()),
+        u64::from_le_bytes(seed),
+    )
+}
+
+/// What a TCP server speaks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    /// The line protocol of [`crate::remote`]
+    Remote,
+    /// [`crate::scpi`] for the instrument
+    Scpi(Instrument),
+    /// The web page and REST API of [`crate::http`]
+    Http,
+}
+
+/// A listening port and what is served on it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TcpServer {
+    pub port: u16,
+    pub protocol: Protocol,
+    /// Connections it takes at once
+    pub sessions: usize,
+    /// Connections without a byte from the client for this long are
+    /// closed, subscribers have to send something now and then
+    pub idle_timeout: Duration,
+}
+
+/// The remote control protocol on port 5000
+pub const REMOTE_SERVER: TcpServer = TcpServer {
+    port: 5000,
+    protocol: Protocol::Remote,
+    sessions: 2,
+    idle_timeout: Duration::from_secs(600),
+};
+
+/// SCPI on the usual raw socket port 5025
+pub const fn scpi_server(instrument: Instrument) -> TcpServer {
+    TcpServer {
+        port: 5025,
+        protocol: Protocol::Scpi(instrument),
+        sessions: 2,
+        idle_timeout: Duration::from_secs(600),
+    }
+}
+
+/// The web page and REST API on port 80. Browsers open a few connections
+/// at once and keep them open for a while.
+pub const HTTP_SERVER: TcpServer = TcpServer {
+    port: 80,
+    protocol: Protocol::Http,
+    sessions: 4,
+    idle_timeout: Duration::from_secs(30),
+};
+
+/// Sessions of all servers, the size of the task pool
+pub const TCP_SESSIONS: usize = 8;
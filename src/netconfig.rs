@@ -0,0 +1,229 @@
+//! Network settings, kept in flash next to the touch calibration.
+//!
+//! With DHCP on, the board asks for a lease whenever the link comes up and
+//! falls back to the static settings if none came after
+//! [`DHCP_TIMEOUT_SECS`]. With DHCP off the static settings are used right
+//! away. The MAC address is not a setting, [`mac_address`] derives it from
+//! the chip's unique ID.
+
+use core::net::Ipv4Addr;
+
+/// Length of [`NetConfig::to_bytes`]
+pub const STORED_LEN: usize = 24;
+
+const MAGIC: [u8; 4] = *b"NET1";
+
+/// Wait for a DHCP lease before the static settings are used
+pub const DHCP_TIMEOUT_SECS: u64 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetConfig {
+    /// Ask a DHCP server first, the static settings are the fallback
+    pub dhcp: bool,
+    pub address: Ipv4Addr,
+    /// Bits of the network mask
+    pub prefix_len: u8,
+    pub gateway: Option<Ipv4Addr>,
+    pub dns: Option<Ipv4Addr>,
+}
+
+impl Default for NetConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl NetConfig {
+    /// DHCP, falling back to the lab network's 192.168.210.201/24
+    pub const DEFAULT: Self = Self {
+        dhcp: true,
+        address: Ipv4Addr::new(192, 168, 210, 201),
+        prefix_len: 24,
+        gateway: Some(Ipv4Addr::new(192, 168, 210, 1)),
+        dns: None,
+    };
+
+    pub fn netmask(&self) -> Ipv4Addr {
+        netmask(self.prefix_len)
+    }
+
+    /// The settings after `change`
+    pub fn changed(self, change: NetChange) -> Self {
+        match change {
+            NetChange::Setting(setting) => self.with(setting),
+            NetChange::Config(config) => config,
+        }
+    }
+
+    /// The settings with `setting` changed
+    pub fn with(mut self, setting: NetSetting) -> Self {
+        match setting {
+            NetSetting::Dhcp(on) => self.dhcp = on,
+            NetSetting::Address(address, prefix_len) => {
+                self.address = address;
+                self.prefix_len = prefix_len.unwrap_or(self.prefix_len);
+            }
+            NetSetting::PrefixLen(prefix_len) => self.prefix_len = prefix_len,
+            NetSetting::Gateway(gateway) => self.gateway = gateway,
+            NetSetting::Dns(dns) => self.dns = dns,
+        }
+        self
+    }
+
+    pub fn to_bytes(&self) -> [u8; STORED_LEN] {
+        let mut bytes = [0; STORED_LEN];
+        bytes[..4].copy_from_slice(&MAGIC);
+        bytes[4] = self.dhcp as u8;
+        bytes[5] = self.prefix_len;
+        bytes[8..12].copy_from_slice(&self.address.octets());
+        // 0.0.0.0 for none
+        let unspecified = Ipv4Addr::UNSPECIFIED;
+        bytes[12..16].copy_from_slice(&self.gateway.unwrap_or(unspecified).octets());
+        bytes[16..20].copy_from_slice(&self.dns.unwrap_or(unspecified).octets());
+        let sum = checksum(&bytes[..20]);
+        bytes[20..].copy_from_slice(&sum.to_le_bytes());
+        bytes
+    }
+
+    /// `None` for erased flash or a damaged record
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..STORED_LEN)?;
+        if bytes[..4] != MAGIC
+            || bytes[20..] != checksum(&bytes[..20]).to_le_bytes()
+            || bytes[4] > 1
+            || bytes[5] > 32
+        {
+            return None;
+        }
+
+        let address = |offset: usize| {
+            let octets: [u8; 4] = bytes[offset..offset + 4].try_into().unwrap();
+            Some(Ipv4Addr::from(octets)).filter(|a| !a.is_unspecified())
+        };
+        Some(Self {
+            dhcp: bytes[4] == 1,
+            address: address(8)?,
+            prefix_len: bytes[5],
+            gateway: address(12),
+            dns: address(16),
+        })
+    }
+}
+
+/// FNV-1a, like the calibration record's
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811C_9DC5, |hash, &byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// One setting changed from the serial console
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetSetting {
+    Dhcp(bool),
+    /// With the prefix length, if it was given
+    Address(Ipv4Addr, Option<u8>),
+    PrefixLen(u8),
+    Gateway(Option<Ipv4Addr>),
+    Dns(Option<Ipv4Addr>),
+}
+
+/// New settings for the network task. It applies them to the settings in
+/// use when they arrive, so changes made one after another all stay.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetChange {
+    /// One setting, from the remote protocol
+    Setting(NetSetting),
+    /// All of them, as saved on the touchscreen
+    Config(NetConfig),
+}
+
+/// The mask of the first `prefix_len` bits
+pub fn netmask(prefix_len: u8) -> Ipv4Addr {
+    let bits = u32::MAX.checked_shl(32 - prefix_len.min(32) as u32);
+    Ipv4Addr::from(bits.unwrap_or(0))
+}
+
+/// Bits of `mask`, `None` unless they are contiguous
+pub fn prefix_len(mask: Ipv4Addr) -> Option<u8> {
+    let bits = u32::from(mask);
+    let len = bits.leading_ones() as u8;
+    (netmask(len) == mask).then_some(len)
+}
+
+/// `a.b.c.d` or `a.b.c.d/len`. Addresses a host cannot have, e.g.
+/// multicast or broadcast, are rejected.
+pub fn parse_address(text: &str) -> Option<(Ipv4Addr, Option<u8>)> {
+    let (address, prefix_len) = match text.split_once('/') {
+        Some((address, len)) => (address, Some(len.parse().ok().filter(|&l| l <= 32)?)),
+        None => (text, None),
+    };
+    let address: Ipv4Addr = address.parse().ok()?;
+    let usable = !(address.is_unspecified() || address.is_broadcast() || address.is_multicast());
+    usable.then_some((address, prefix_len))
+}
+
+/// Locally administered unicast address from the 96-bit unique ID, the
+/// same on every start and different on every board
+pub fn mac_address(uid: &[u8; 12]) -> [u8; 6] {
+    let hash = uid.iter().fold(0xCBF2_9CE4_8422_2325_u64, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    });
+    let bytes = hash.to_le_bytes();
+    [0x02, bytes[0], bytes[1], bytes[2], bytes[3], bytes[4]]
+}
+
+/// What the network is doing, for the GUI and the remote protocol
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetState {
+    pub mac: [u8; 6],
+    /// The stored settings
+    pub config: NetConfig,
+    pub link_up: bool,
+    /// Address and prefix length in use, from DHCP or the static settings
+    pub address: Option<(Ipv4Addr, u8)>,
+    /// DHCP gave no lease, the static settings are in use
+    pub fallback: bool,
+}
+
+#[cfg(feature = "hw")]
+struct Octets(Ipv4Addr);
+
+#[cfg(feature = "hw")]
+impl defmt::Format for Octets {
+    fn format(&self, f: defmt::Formatter) {
+        let [a, b, c, d] = self.0.octets();
+        defmt::write!(f, "{}.{}.{}.{}", a, b, c, d)
+    }
+}
+
+#[cfg(feature = "hw")]
+impl defmt::Format for NetConfig {
+    fn format(&self, f: defmt::Formatter) {
+        let mode = if self.dhcp { "DHCP, else " } else { "" };
+        defmt::write!(
+            f,
+            "{}{}/{} gateway {} DNS {}",
+            mode,
+            Octets(self.address),
+            self.prefix_len,
+            self.gateway.map(Octets),
+            self.dns.map(Octets)
+        )
+    }
+}
+
+#[cfg(feature = "hw")]
+impl defmt::Format for NetState {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(
+            f,
+            "link {}, address {}",
+            if self.link_up { "up" } else { "down" },
+            self.address.map(|(a, len)| (Octets(a), len))
+        );
+        if self.fallback {
+            defmt::write!(f, " (no DHCP lease)");
+        }
+    }
+}
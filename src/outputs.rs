@@ -0,0 +1,280 @@
+//! Digital outputs behind one command channel.
+//!
+//! An [`OutputBank`] owns the pins of all outputs and executes
+//! [`Command`]s on them: set, clear, toggle, timed pulses and read-back.
+//! Outputs are addressed by [`OutputId`], their index in the bank, and
+//! work in logical levels; an [`inverted`](OutputConfig::inverted) output
+//! is on while its pin is low. On boot every output goes to its safe
+//! state.
+//!
+//! The bank is generic over `embedded-hal` pins, so the simulator and the
+//! tests run it on fake pins.
+//!
+//! ```ignore
+//! let mut bank = OutputBank::new([d0, d1, d2, d3], OUTPUTS);
+//! bank.execute(Command::Pulse(OutputId(1), 200), now)?;
+//! // ... and at `bank.next_deadline()`
+//! bank.poll(now);
+//! ```
+
+use core::convert::Infallible;
+
+use embedded_hal::digital::{PinState, StatefulOutputPin};
+
+/// Outputs the firmware drives, in bank order. Screen descriptions refer
+/// to them by name.
+pub const OUTPUTS: [OutputConfig; 4] = [
+    OutputConfig::new("D0"),
+    OutputConfig::new("D1"),
+    OutputConfig::new("D2"),
+    OutputConfig::new("D3"),
+];
+
+/// Outputs a bank can hold, one bit each in [`Levels`]
+pub const MAX_OUTPUTS: usize = 32;
+
+/// How one output is wired
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct OutputConfig {
+    pub name: &'static str,
+    /// On while the pin is low, e.g. an active-low relay board
+    pub inverted: bool,
+    /// Logical level on boot, on panic and after [`Command::SafeState`]
+    pub safe: bool,
+}
+
+impl OutputConfig {
+    /// Active high and off when safe
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            inverted: false,
+            safe: false,
+        }
+    }
+
+    pub const fn inverted(self) -> Self {
+        Self {
+            inverted: true,
+            ..self
+        }
+    }
+
+    /// On when safe, e.g. a brake released by power
+    pub const fn safe_on(self) -> Self {
+        Self { safe: true, ..self }
+    }
+
+    /// Pin level for the logical level `on`
+    pub const fn pin_level(&self, on: bool) -> bool {
+        on != self.inverted
+    }
+}
+
+/// Index of an output in its bank
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OutputId(pub u8);
+
+impl OutputId {
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    /// The output of [`OUTPUTS`] called `name`
+    pub fn by_name(name: &str) -> Option<Self> {
+        OUTPUTS
+            .iter()
+            .position(|o| o.name == name)
+            .map(|i| Self(i as u8))
+    }
+
+    /// Name in [`OUTPUTS`], `"?"` for other banks
+    pub fn name(self) -> &'static str {
+        OUTPUTS.get(self.index()).map_or("?", |o| o.name)
+    }
+}
+
+#[cfg(feature = "hw")]
+impl defmt::Format for OutputId {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{=str}", self.name())
+    }
+}
+
+/// New logical level of one output, as the screens apply it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct OutputLevel {
+    pub output: OutputId,
+    pub on: bool,
+}
+
+/// Logical level of every output of a bank, one bit per [`OutputId`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Levels(pub u32);
+
+impl Levels {
+    pub fn get(&self, output: OutputId) -> bool {
+        output.index() < MAX_OUTPUTS && self.0 & (1 << output.index()) != 0
+    }
+
+    pub fn set(&mut self, output: OutputId, on: bool) {
+        if output.index() < MAX_OUTPUTS {
+            self.0 = self.0 & !(1 << output.index()) | (on as u32) << output.index();
+        }
+    }
+
+    /// The outputs that differ from `old`
+    pub fn changes_since(self, old: Levels) -> impl Iterator<Item = OutputLevel> {
+        let changed = self.0 ^ old.0;
+        (0..MAX_OUTPUTS as u8)
+            .filter(move |i| changed & (1 << i) != 0)
+            .map(move |i| OutputLevel {
+                output: OutputId(i),
+                on: self.get(OutputId(i)),
+            })
+    }
+}
+
+/// What the bank is asked to do, see [`OutputBank::execute`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Command {
+    Set(OutputId),
+    Clear(OutputId),
+    Toggle(OutputId),
+    /// On for the given milliseconds, then off
+    Pulse(OutputId, u32),
+    /// Nothing but reading the levels back, see [`OutputBank::levels`]
+    Read,
+    /// Every output to its safe level
+    SafeState,
+}
+
+/// A command named an output the bank does not have
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct UnknownOutput(pub OutputId);
+
+/// `N` outputs on pins of type `P`
+pub struct OutputBank<P, const N: usize> {
+    pins: [P; N],
+    configs: [OutputConfig; N],
+    /// End of the running pulse, in ms
+    pulse_ends: [Option<u32>; N],
+}
+
+impl<P, const N: usize> OutputBank<P, N>
+where
+    P: StatefulOutputPin<Error = Infallible>,
+{
+    /// Takes over the pins and drives every output to its safe level
+    pub fn new(pins: [P; N], configs: [OutputConfig; N]) -> Self {
+        const { assert!(N <= MAX_OUTPUTS, "too many outputs for `Levels`") };
+
+        let mut bank = Self {
+            pins,
+            configs,
+            pulse_ends: [None; N],
+        };
+        bank.safe_state();
+        bank
+    }
+
+    pub fn configs(&self) -> &[OutputConfig; N] {
+        &self.configs
+    }
+
+    pub fn pins(&self) -> &[P; N] {
+        &self.pins
+    }
+
+    /// Executes `command` at `now_ms`. Any command for an output ends its
+    /// running pulse.
+    pub fn execute(&mut self, command: Command, now_ms: u32) -> Result<(), UnknownOutput> {
+        match command {
+            Command::Set(output) => self.set(output, true),
+            Command::Clear(output) => self.set(output, false),
+            Command::Toggle(output) => {
+                let on = self.get(output).ok_or(UnknownOutput(output))?;
+                self.set(output, !on)
+            }
+            Command::Pulse(output, ms) => {
+                self.set(output, true)?;
+                self.pulse_ends[output.index()] = Some(now_ms.wrapping_add(ms));
+                Ok(())
+            }
+            Command::Read => Ok(()),
+            Command::SafeState => {
+                self.safe_state();
+                Ok(())
+            }
+        }
+    }
+
+    /// Drives `output` to the logical level `on`
+    pub fn set(&mut self, output: OutputId, on: bool) -> Result<(), UnknownOutput> {
+        let i = output.index();
+        if i >= N {
+            return Err(UnknownOutput(output));
+        }
+
+        self.pulse_ends[i] = None;
+        let level = PinState::from(self.configs[i].pin_level(on));
+        ok(self.pins[i].set_state(level));
+        Ok(())
+    }
+
+    /// Logical level read back from the pin of `output`
+    pub fn get(&mut self, output: OutputId) -> Option<bool> {
+        let i = output.index();
+        let pin = self.pins.get_mut(i)?;
+        Some(ok(pin.is_set_high()) != self.configs[i].inverted)
+    }
+
+    /// Every output, read back from the pins
+    pub fn levels(&mut self) -> Levels {
+        let mut levels = Levels::default();
+        for i in 0..N {
+            let output = OutputId(i as u8);
+            levels.set(output, self.get(output).unwrap_or_default());
+        }
+        levels
+    }
+
+    /// Every output to its safe level, pulses are cancelled. The last
+    /// output goes first: outputs usually depend on the ones before them,
+    /// like the power levels on RF. An interlock plans the exact order,
+    /// see [`Interlock::plan`](crate::interlock::Interlock::plan).
+    pub fn safe_state(&mut self) {
+        for i in (0..N).rev() {
+            let _ = self.set(OutputId(i as u8), self.configs[i].safe);
+        }
+    }
+
+    /// When [`Self::poll`] has to run next, `None` without pulses
+    pub fn next_deadline(&self) -> Option<u32> {
+        self.pulse_ends.iter().flatten().copied().min()
+    }
+
+    /// Ends the pulses that are due at `now_ms`
+    pub fn poll(&mut self, now_ms: u32) {
+        for i in 0..N {
+            if let Some(end) = self.pulse_ends[i] {
+                // Wraps like the millisecond clock
+                if now_ms.wrapping_sub(end) as i32 >= 0 {
+                    let _ = self.set(OutputId(i as u8), false);
+                }
+            }
+        }
+    }
+}
+
+fn ok<T>(result: Result<T, Infallible>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(never) => match never {},
+    }
+}
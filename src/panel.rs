@@ -0,0 +1,202 @@
+//! Timing model for parallel RGB panels driven by the LTDC.
+//!
+//! Panel datasheets give porches and sync widths in clocks/lines, the LTDC
+//! wants them accumulated and minus one. [`PanelTiming::ltdc_timing`] does
+//! the conversion and [`PanelTiming::pllsai`] finds PLLSAI settings for the
+//! pixel clock.
+
+use crate::rcc::PllSai;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Polarity {
+    ActiveLow,
+    ActiveHigh,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum ClockEdge {
+    Rising,
+    Falling,
+}
+
+/// Panel timing as found in the datasheet. Horizontal values in pixel
+/// clocks, vertical in lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct PanelTiming {
+    pub width: u16,
+    pub height: u16,
+
+    pub hsync: u16,
+    pub hbp: u16,
+    pub hfp: u16,
+
+    pub vsync: u16,
+    pub vbp: u16,
+    pub vfp: u16,
+
+    pub hsync_polarity: Polarity,
+    pub vsync_polarity: Polarity,
+    pub de_polarity: Polarity,
+    pub pixel_clock_edge: ClockEdge,
+
+    pub pixel_clock_hz: u32,
+}
+
+/// Register values for LTDC SSCR, BPCR, AWCR and TWCR (RM0385 18.7)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct LtdcTiming {
+    /// SSCR.HSW
+    pub hsw: u16,
+    /// SSCR.VSH
+    pub vsh: u16,
+    /// BPCR.AHBP
+    pub ahbp: u16,
+    /// BPCR.AVBP
+    pub avbp: u16,
+    /// AWCR.AAW
+    pub aaw: u16,
+    /// AWCR.AAH
+    pub aah: u16,
+    /// TWCR.TOTALW
+    pub totalw: u16,
+    /// TWCR.TOTALH
+    pub totalh: u16,
+}
+
+impl PanelTiming {
+    pub const fn ltdc_timing(&self) -> LtdcTiming {
+        let ahbp = self.hsync + self.hbp - 1;
+        let avbp = self.vsync + self.vbp - 1;
+        let aaw = ahbp + self.width;
+        let aah = avbp + self.height;
+
+        LtdcTiming {
+            hsw: self.hsync - 1,
+            vsh: self.vsync - 1,
+            ahbp,
+            avbp,
+            aaw,
+            aah,
+            totalw: aaw + self.hfp,
+            totalh: aah + self.vfp,
+        }
+    }
+
+    /// Pixel clocks per line including blanking
+    pub const fn total_width(&self) -> u32 {
+        (self.hsync + self.hbp + self.width + self.hfp) as u32
+    }
+
+    /// Lines per frame including blanking
+    pub const fn total_height(&self) -> u32 {
+        (self.vsync + self.vbp + self.height + self.vfp) as u32
+    }
+
+    /// Frame rate in mHz at the nominal pixel clock
+    pub const fn refresh_rate_mhz(&self) -> u32 {
+        ((self.pixel_clock_hz as u64 * 1000) / (self.total_width() * self.total_height()) as u64)
+            as u32
+    }
+
+    /// PLLSAI N, R and PLLSAIDIVR closest to `pixel_clock_hz`.
+    ///
+    /// `vco_in_hz` is HSE or HSI after the shared PLLM divider. P and Q are
+    /// left at their largest dividers since only R feeds the LTDC.
+    /// Returns `None` if no setting keeps the VCO within 100..=432 MHz.
+    pub const fn pllsai(&self, vco_in_hz: u32) -> Option<PllSai> {
+        const DIVR: [u32; 4] = [2, 4, 8, 16];
+
+        let target = self.pixel_clock_hz as u64;
+        let mut best: Option<PllSai> = None;
+        let mut best_error = u64::MAX;
+
+        let mut d = 0;
+        while d < DIVR.len() {
+            let divr = DIVR[d];
+            let mut r = 2;
+            while r <= 7 {
+                // Round to the nearest N
+                let div = (r * divr) as u64;
+                let n = (target * div + vco_in_hz as u64 / 2) / vco_in_hz as u64;
+                let vco = n * vco_in_hz as u64;
+
+                if n >= 50 && n <= 432 && vco >= 100_000_000 && vco <= 432_000_000 {
+                    let clock = vco / div;
+                    let error = clock.abs_diff(target);
+                    if error < best_error {
+                        best_error = error;
+                        best = Some(PllSai {
+                            n: n as u32,
+                            p: 8,
+                            q: 15,
+                            r,
+                            divr,
+                        });
+                    }
+                }
+                r += 1;
+            }
+            d += 1;
+        }
+
+        best
+    }
+}
+
+/// Rocktech RK043FN48H, the 4.3" 480x272 panel on the DISCO board
+pub const RK043FN48H: PanelTiming = PanelTiming {
+    width: 480,
+    height: 272,
+
+    hsync: 41,
+    hbp: 13,
+    hfp: 32,
+
+    vsync: 10,
+    vbp: 2,
+    vfp: 2,
+
+    hsync_polarity: Polarity::ActiveLow,
+    vsync_polarity: Polarity::ActiveLow,
+    de_polarity: Polarity::ActiveLow,
+    pixel_clock_edge: ClockEdge::Rising,
+
+    // Datasheet typical is 9 MHz, 9.6 MHz is what ST's BSP uses
+    pixel_clock_hz: 9_600_000,
+};
+
+/// Innolux AT070TN92, a common 7" 800x480 panel
+pub const AT070TN92: PanelTiming = PanelTiming {
+    width: 800,
+    height: 480,
+
+    hsync: 20,
+    hbp: 26,
+    hfp: 210,
+
+    vsync: 10,
+    vbp: 13,
+    vfp: 22,
+
+    hsync_polarity: Polarity::ActiveLow,
+    vsync_polarity: Polarity::ActiveLow,
+    de_polarity: Polarity::ActiveHigh,
+    pixel_clock_edge: ClockEdge::Rising,
+
+    pixel_clock_hz: 33_300_000,
+};
+
+// The computed register values have to match ST's BSP for the DISCO panel
+const _: () = {
+    let t = RK043FN48H.ltdc_timing();
+    assert!(t.hsw == 40 && t.vsh == 9);
+    assert!(t.ahbp == 53 && t.avbp == 11);
+    assert!(t.aaw == 533 && t.aah == 283);
+    assert!(t.totalw == 565 && t.totalh == 285);
+};
+const _: () = assert!(RK043FN48H.pllsai(1_000_000).is_some());
+const _: () = assert!(AT070TN92.pllsai(1_000_000).is_some());
@@ -1,49 +1,511 @@
-use embassy_stm32::Config;
+//! Clock tree profiles for the STM32F746.
+//!
+//! A [`ClockProfile`] is plain numbers (dividers and multipliers as written
+//! in RM0385), so it can be checked by [`ClockProfile::validate`] in a
+//! `const` context before it ever reaches `embassy_stm32::init`.
+
+#[cfg(feature = "hw")]
 use embassy_stm32::rcc::{
-    AHBPrescaler, APBPrescaler, Hse, HseMode, Pll, PllMul, PllPDiv, PllPreDiv, PllQDiv,
+    mux, AHBPrescaler, APBPrescaler, Hse, HseMode, Pll, PllMul, PllPDiv, PllPreDiv, PllQDiv,
     PllRDiv, PllSource, Sysclk,
 };
-use embassy_stm32::time::mhz;
-
-pub fn init_rcc() -> Config
-{
-    let mut config = embassy_stm32::Config::default();
-    config.rcc.sys = Sysclk::PLL1_P;
-    config.rcc.ahb_pre = AHBPrescaler::DIV1;
-    config.rcc.apb1_pre = APBPrescaler::DIV4;
-    config.rcc.apb2_pre = APBPrescaler::DIV2;
-
-    // HSE is on and ready
-    config.rcc.hse = Some(Hse {
-        freq: mhz(25),
-        mode: HseMode::Oscillator,
-    });
-    config.rcc.pll_src = PllSource::HSE;
-
-    config.rcc.pll = Some(Pll {
-        prediv: PllPreDiv::DIV25,  // PLLM
-        mul: PllMul::MUL400,       // PLLN
-        divp: Some(PllPDiv::DIV2), // SYSCLK = 400/2 = 200 MHz
-        divq: Some(PllQDiv::DIV9), // PLLQ = 400/9 = 44.44 MHz
-        divr: None,
-    });
-
-    // This seems to be working, the values in the RCC.PLLSAICFGR are correct according to the debugger. Also on and ready according to CR
-    config.rcc.pllsai = Some(Pll {
-        prediv: PllPreDiv::DIV25,  // Actually ignored
-        mul: PllMul::MUL384,       // PLLN
-        divp: Some(PllPDiv::DIV8), // PLLP
-        divq: Some(PllQDiv::DIV2), // PLLQ
-        divr: Some(PllRDiv::DIV5), // PLLR
-    });
-
-    // PLLI2S
-    config.rcc.plli2s = Some(Pll {
-        prediv: PllPreDiv::DIV25,  // Actually ignored
-        mul: PllMul::MUL100,       // PLLN
-        divp: Some(PllPDiv::DIV2), // PLLP
-        divq: Some(PllQDiv::DIV2), // PLLQ
-        divr: Some(PllRDiv::DIV2), // PLLR (I2S PLLR is always 2)
-    });
-    config
-}
\ No newline at end of file
+#[cfg(feature = "hw")]
+use embassy_stm32::time::Hertz;
+#[cfg(feature = "hw")]
+use embassy_stm32::Config;
+
+/// HSE crystal on the DISCO board (X2)
+pub const HSE_HZ: u32 = 25_000_000;
+/// Internal RC oscillator
+pub const HSI_HZ: u32 = 16_000_000;
+
+// Limits from the STM32F746 datasheet (DS10916) and RM0385
+const VCO_IN_MIN_HZ: u32 = 1_000_000;
+const VCO_IN_MAX_HZ: u32 = 2_000_000;
+const VCO_OUT_MIN_HZ: u32 = 100_000_000;
+const VCO_OUT_MAX_HZ: u32 = 432_000_000;
+const SYSCLK_MAX_HZ: u32 = 180_000_000;
+const SYSCLK_MAX_OVERDRIVE_HZ: u32 = 216_000_000;
+const APB1_MAX_HZ: u32 = 54_000_000;
+const APB2_MAX_HZ: u32 = 108_000_000;
+const USB_HZ: u32 = 48_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum ClockSource {
+    Hse,
+    Hsi,
+}
+
+/// Main PLL. SYSCLK is taken from P, USB OTG FS/SDMMC/RNG from Q.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct MainPll {
+    pub m: u32,
+    pub n: u32,
+    pub p: u32,
+    pub q: u32,
+}
+
+/// PLLSAI. R divided by `divr` (PLLSAIDIVR) is the LCD-TFT pixel clock.
+///
+/// PLLSAI and PLLI2S share the M divider of the main PLL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct PllSai {
+    pub n: u32,
+    pub p: u32,
+    pub q: u32,
+    pub r: u32,
+    pub divr: u32,
+}
+
+/// PLLI2S. Shares the M divider of the main PLL.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct PllI2s {
+    pub n: u32,
+    pub p: u32,
+    pub q: u32,
+    pub r: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct ClockProfile {
+    pub source: ClockSource,
+    /// `None` runs SYSCLK straight from `source`
+    pub pll: Option<MainPll>,
+    pub pllsai: Option<PllSai>,
+    pub plli2s: Option<PllI2s>,
+    pub ahb_div: u32,
+    pub apb1_div: u32,
+    pub apb2_div: u32,
+    /// Voltage regulator over-drive, required above 180 MHz
+    pub overdrive: bool,
+    /// USB OTG FS is used, so PLLQ has to be exactly 48 MHz
+    pub usb: bool,
+}
+
+/// Resulting bus frequencies of a valid [`ClockProfile`], in Hz.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Clocks {
+    pub sysclk: u32,
+    pub hclk: u32,
+    pub pclk1: u32,
+    pub pclk2: u32,
+    /// PLLQ output, `None` without main PLL
+    pub pll48: Option<u32>,
+    /// LCD-TFT pixel clock, `None` without PLLSAI
+    pub lcd: Option<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum ClockError {
+    /// PLLM outside 2..=63
+    PllmOutOfRange,
+    /// Input / PLLM outside 1..=2 MHz
+    VcoInputOutOfRange,
+    /// PLLN outside 50..=432
+    PllnOutOfRange,
+    /// VCO output outside 100..=432 MHz
+    VcoOutputOutOfRange,
+    /// P divider is not 2, 4, 6 or 8
+    InvalidPllp,
+    /// Q divider outside 2..=15
+    PllqOutOfRange,
+    /// R divider outside 2..=7
+    PllrOutOfRange,
+    /// PLLSAIDIVR is not 2, 4, 8 or 16
+    InvalidPllSaiDivr,
+    /// AHB or APB prescaler the hardware does not have
+    InvalidPrescaler,
+    /// Above 180 MHz without over-drive or above 216 MHz at all
+    SysclkTooHigh,
+    /// APB1 above 54 MHz
+    Apb1TooHigh,
+    /// APB2 above 108 MHz
+    Apb2TooHigh,
+    /// USB requested but PLLQ is not 48 MHz
+    UsbClockNot48MHz,
+}
+
+impl ClockProfile {
+    /// Frequency of the PLL input after the shared M divider
+    const fn vco_input(&self, m: u32) -> u32 {
+        match self.source {
+            ClockSource::Hse => HSE_HZ / m,
+            ClockSource::Hsi => HSI_HZ / m,
+        }
+    }
+
+    const fn check_vco(&self, m: u32, n: u32) -> Result<u32, ClockError> {
+        if m < 2 || m > 63 {
+            return Err(ClockError::PllmOutOfRange);
+        }
+        let vco_in = self.vco_input(m);
+        if vco_in < VCO_IN_MIN_HZ || vco_in > VCO_IN_MAX_HZ {
+            return Err(ClockError::VcoInputOutOfRange);
+        }
+        if n < 50 || n > 432 {
+            return Err(ClockError::PllnOutOfRange);
+        }
+        let vco_out = vco_in * n;
+        if vco_out < VCO_OUT_MIN_HZ || vco_out > VCO_OUT_MAX_HZ {
+            return Err(ClockError::VcoOutputOutOfRange);
+        }
+        Ok(vco_out)
+    }
+
+    const fn check_pqr(p: u32, q: u32, r: Option<u32>) -> Result<(), ClockError> {
+        if !matches!(p, 2 | 4 | 6 | 8) {
+            return Err(ClockError::InvalidPllp);
+        }
+        if q < 2 || q > 15 {
+            return Err(ClockError::PllqOutOfRange);
+        }
+        if let Some(r) = r {
+            if r < 2 || r > 7 {
+                return Err(ClockError::PllrOutOfRange);
+            }
+        }
+        Ok(())
+    }
+
+    /// PLL input after PLLM, shared by the main PLL, PLLSAI and PLLI2S.
+    /// `None` if the main PLL is off.
+    pub const fn vco_input_hz(&self) -> Option<u32> {
+        match self.pll {
+            Some(pll) => Some(self.vco_input(pll.m)),
+            None => None,
+        }
+    }
+
+    /// Same profile with PLLSAI replaced, e.g. by [`crate::panel::PanelTiming::pllsai`]
+    pub const fn with_pllsai(self, pllsai: PllSai) -> Self {
+        Self {
+            pllsai: Some(pllsai),
+            ..self
+        }
+    }
+
+    /// Checks every PLL and bus limit and returns the resulting frequencies.
+    pub const fn validate(&self) -> Result<Clocks, ClockError> {
+        // PLLSAI and PLLI2S take M from the main PLL, without it they are off
+        let m = match self.pll {
+            Some(pll) => pll.m,
+            None => 0,
+        };
+
+        let (sysclk, pll48) = match self.pll {
+            Some(pll) => {
+                let vco = match self.check_vco(pll.m, pll.n) {
+                    Ok(vco) => vco,
+                    Err(e) => return Err(e),
+                };
+                if let Err(e) = Self::check_pqr(pll.p, pll.q, None) {
+                    return Err(e);
+                }
+                (vco / pll.p, Some(vco / pll.q))
+            }
+            None => match self.source {
+                ClockSource::Hse => (HSE_HZ, None),
+                ClockSource::Hsi => (HSI_HZ, None),
+            },
+        };
+
+        let lcd = match self.pllsai {
+            Some(sai) => {
+                let vco = match self.check_vco(m, sai.n) {
+                    Ok(vco) => vco,
+                    Err(e) => return Err(e),
+                };
+                if let Err(e) = Self::check_pqr(sai.p, sai.q, Some(sai.r)) {
+                    return Err(e);
+                }
+                if !matches!(sai.divr, 2 | 4 | 8 | 16) {
+                    return Err(ClockError::InvalidPllSaiDivr);
+                }
+                Some(vco / sai.r / sai.divr)
+            }
+            None => None,
+        };
+
+        if let Some(i2s) = self.plli2s {
+            if let Err(e) = self.check_vco(m, i2s.n) {
+                return Err(e);
+            }
+            if let Err(e) = Self::check_pqr(i2s.p, i2s.q, Some(i2s.r)) {
+                return Err(e);
+            }
+        }
+
+        let max_sysclk = if self.overdrive {
+            SYSCLK_MAX_OVERDRIVE_HZ
+        } else {
+            SYSCLK_MAX_HZ
+        };
+        if sysclk > max_sysclk {
+            return Err(ClockError::SysclkTooHigh);
+        }
+
+        if !matches!(self.ahb_div, 1 | 2 | 4 | 8 | 16 | 64 | 128 | 256 | 512)
+            || !matches!(self.apb1_div, 1 | 2 | 4 | 8 | 16)
+            || !matches!(self.apb2_div, 1 | 2 | 4 | 8 | 16)
+        {
+            return Err(ClockError::InvalidPrescaler);
+        }
+        let hclk = sysclk / self.ahb_div;
+        let pclk1 = hclk / self.apb1_div;
+        let pclk2 = hclk / self.apb2_div;
+        if pclk1 > APB1_MAX_HZ {
+            return Err(ClockError::Apb1TooHigh);
+        }
+        if pclk2 > APB2_MAX_HZ {
+            return Err(ClockError::Apb2TooHigh);
+        }
+
+        if self.usb {
+            match pll48 {
+                Some(USB_HZ) => {}
+                _ => return Err(ClockError::UsbClockNot48MHz),
+            }
+        }
+
+        Ok(Clocks {
+            sysclk,
+            hclk,
+            pclk1,
+            pclk2,
+            pll48,
+            lcd,
+        })
+    }
+
+    /// Builds the embassy config for this profile.
+    ///
+    /// Panics if the profile does not pass [`ClockProfile::validate`].
+    #[cfg(feature = "hw")]
+    pub fn to_config(&self) -> Config {
+        if let Err(e) = self.validate() {
+            defmt::panic!("Invalid clock profile: {}", e);
+        }
+
+        let mut config = Config::default();
+
+        match self.source {
+            ClockSource::Hse => {
+                // HSE is on and ready
+                config.rcc.hse = Some(Hse {
+                    freq: Hertz(HSE_HZ),
+                    mode: HseMode::Oscillator,
+                });
+                config.rcc.pll_src = PllSource::HSE;
+            }
+            ClockSource::Hsi => {
+                config.rcc.hsi = true;
+                config.rcc.pll_src = PllSource::HSI;
+            }
+        }
+
+        config.rcc.pll = self.pll.map(|pll| Pll {
+            prediv: PllPreDiv::from_bits(pll.m as u8),   // PLLM
+            mul: PllMul::from_bits(pll.n as u16),        // PLLN
+            divp: Some(pll_p(pll.p)),                    // PLLP -> SYSCLK
+            divq: Some(PllQDiv::from_bits(pll.q as u8)), // PLLQ -> 48 MHz domain
+            divr: None,
+        });
+
+        // PLLSAI and PLLI2S use PLLM of the main PLL
+        let prediv = PllPreDiv::from_bits(self.pll.map_or(HSE_HZ / 1_000_000, |pll| pll.m) as u8);
+
+        config.rcc.pllsai = self.pllsai.map(|sai| Pll {
+            prediv, // Actually ignored
+            mul: PllMul::from_bits(sai.n as u16),
+            divp: Some(pll_p(sai.p)),
+            divq: Some(PllQDiv::from_bits(sai.q as u8)),
+            divr: Some(PllRDiv::from_bits(sai.r as u8)),
+        });
+
+        config.rcc.plli2s = self.plli2s.map(|i2s| Pll {
+            prediv, // Actually ignored
+            mul: PllMul::from_bits(i2s.n as u16),
+            divp: Some(pll_p(i2s.p)),
+            divq: Some(PllQDiv::from_bits(i2s.q as u8)),
+            divr: Some(PllRDiv::from_bits(i2s.r as u8)),
+        });
+
+        config.rcc.sys = match (self.pll, self.source) {
+            (Some(_), _) => Sysclk::PLL1_P,
+            (None, ClockSource::Hse) => Sysclk::HSE,
+            (None, ClockSource::Hsi) => Sysclk::HSI,
+        };
+
+        config.rcc.ahb_pre = match self.ahb_div {
+            1 => AHBPrescaler::DIV1,
+            2 => AHBPrescaler::DIV2,
+            4 => AHBPrescaler::DIV4,
+            8 => AHBPrescaler::DIV8,
+            16 => AHBPrescaler::DIV16,
+            64 => AHBPrescaler::DIV64,
+            128 => AHBPrescaler::DIV128,
+            256 => AHBPrescaler::DIV256,
+            _ => AHBPrescaler::DIV512,
+        };
+        config.rcc.apb1_pre = apb_pre(self.apb1_div);
+        config.rcc.apb2_pre = apb_pre(self.apb2_div);
+
+        if self.usb {
+            config.rcc.mux.clk48sel = mux::Clk48sel::PLL1_Q;
+        }
+
+        // Over-drive is switched on by embassy_stm32::init when SYSCLK needs it,
+        // the flag only gates validation.
+        config
+    }
+}
+
+#[cfg(feature = "hw")]
+fn pll_p(p: u32) -> PllPDiv {
+    match p {
+        2 => PllPDiv::DIV2,
+        4 => PllPDiv::DIV4,
+        6 => PllPDiv::DIV6,
+        _ => PllPDiv::DIV8,
+    }
+}
+
+#[cfg(feature = "hw")]
+fn apb_pre(div: u32) -> APBPrescaler {
+    match div {
+        1 => APBPrescaler::DIV1,
+        2 => APBPrescaler::DIV2,
+        4 => APBPrescaler::DIV4,
+        8 => APBPrescaler::DIV8,
+        _ => APBPrescaler::DIV16,
+    }
+}
+
+/// PLLI2S setting shared by all profiles. Nothing uses I2S or SAI yet, so
+/// the VCO runs at its 100 MHz minimum (1 MHz input, N = 100) and every
+/// divider is at its smallest legal value.
+const PLLI2S_DEFAULT: PllI2s = PllI2s {
+    n: 100,
+    p: 2,
+    q: 2,
+    r: 2, // PLLI2SR takes 2..=7, I2S clock = 100/2 = 50 MHz
+};
+
+/// 200 MHz from HSE, the configuration the firmware has always used.
+///
+/// PLLQ = 400/9 = 44.44 MHz, so USB is not available.
+pub const DEFAULT: ClockProfile = ClockProfile {
+    source: ClockSource::Hse,
+    pll: Some(MainPll {
+        m: 25,  // 1 MHz VCO input
+        n: 400, // 400 MHz VCO
+        p: 2,   // SYSCLK = 400/2 = 200 MHz
+        q: 9,   // PLLQ = 400/9 = 44.44 MHz
+    }),
+    pllsai: Some(PllSai {
+        n: 384,
+        p: 8,
+        q: 2,
+        r: 5,
+        divr: 8, // LCD = 384/5/8 = 9.6 MHz
+    }),
+    plli2s: Some(PLLI2S_DEFAULT),
+    ahb_div: 1,  // 200 MHz
+    apb1_div: 4, // 50 MHz
+    apb2_div: 2, // 100 MHz
+    overdrive: true,
+    usb: false,
+};
+
+/// 216 MHz with over-drive, the maximum the F746 is rated for.
+///
+/// 432/9 gives exactly 48 MHz on PLLQ, so USB works as well.
+pub const MAX_PERFORMANCE: ClockProfile = ClockProfile {
+    source: ClockSource::Hse,
+    pll: Some(MainPll {
+        m: 25,
+        n: 432,
+        p: 2, // SYSCLK = 432/2 = 216 MHz
+        q: 9, // PLLQ = 432/9 = 48 MHz
+    }),
+    pllsai: DEFAULT.pllsai,
+    plli2s: Some(PLLI2S_DEFAULT),
+    ahb_div: 1,  // 216 MHz
+    apb1_div: 4, // 54 MHz
+    apb2_div: 2, // 108 MHz
+    overdrive: true,
+    usb: true,
+};
+
+/// 96 MHz SYSCLK with a 48 MHz PLLQ for USB OTG FS.
+pub const USB: ClockProfile = ClockProfile {
+    source: ClockSource::Hse,
+    pll: Some(MainPll {
+        m: 25,
+        n: 384, // 384 MHz VCO
+        p: 4,   // SYSCLK = 384/4 = 96 MHz
+        q: 8,   // USB clock = 384/8 = 48 MHz
+    }),
+    pllsai: Some(PllSai {
+        n: 192,
+        p: 4,
+        q: 4,
+        r: 5,
+        divr: 4, // LCD = 192/5/4 = 9.6 MHz
+    }),
+    plli2s: Some(PLLI2S_DEFAULT),
+    ahb_div: 1,  // 96 MHz
+    apb1_div: 2, // 48 MHz
+    apb2_div: 1, // 96 MHz
+    overdrive: false,
+    usb: true,
+};
+
+/// 16 MHz straight from HSI with every PLL off.
+///
+/// No LCD, no USB, no Ethernet: for blinky-class firmware and battery tests.
+pub const LOW_POWER: ClockProfile = ClockProfile {
+    source: ClockSource::Hsi,
+    pll: None,
+    pllsai: None,
+    plli2s: None,
+    ahb_div: 1,
+    apb1_div: 1,
+    apb2_div: 1,
+    overdrive: false,
+    usb: false,
+};
+
+/// [`MAX_PERFORMANCE`] with PLLSAI trimmed to the 9 MHz typical pixel clock
+/// of the RK043FN48H panel, instead of the 9.6 MHz of ST's BSP.
+/// `Board::init` keeps it like any PLLSAI a profile sets.
+pub const LCD_TUNED: ClockProfile = ClockProfile {
+    pllsai: Some(PllSai {
+        n: 216,
+        p: 8,
+        q: 2,
+        r: 3,
+        divr: 8, // LCD = 216/3/8 = 9 MHz
+    }),
+    ..MAX_PERFORMANCE
+};
+
+// Every built-in profile is checked at compile time
+const _: () = assert!(DEFAULT.validate().is_ok());
+const _: () = assert!(MAX_PERFORMANCE.validate().is_ok());
+const _: () = assert!(USB.validate().is_ok());
+const _: () = assert!(LOW_POWER.validate().is_ok());
+const _: () = assert!(LCD_TUNED.validate().is_ok());
+
+/// Clock configuration used by the firmware
+#[cfg(feature = "hw")]
+pub fn init_rcc() -> Config {
+    DEFAULT.to_config()
+}
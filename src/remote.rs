@@ -0,0 +1,425 @@
+//! Line protocol for remote control of the outputs.
+//!
+//! A PC drives the same outputs as the touchscreen with one request per
+//! line, ended by CR, LF or both. Keywords and output names are not case
+//! sensitive, every request gets one reply line:
+//!
+//! ```text
+//! SET D1 ON          -> OK          (or OFF)
+//! TOGGLE D1          -> OK
+//! PULSE D1 200       -> OK          milliseconds
+//! SAFE               -> OK          every output to its safe level
+//! GET D1             -> D1=ON
+//! GET ALL            -> D0=OFF D1=ON D2=OFF D3=OFF
+//! STATUS             -> STATUS VERSION=0.1.0 OUTPUTS=D0,D1,D2,D3 SUBSCRIBED=NO
+//! SUBSCRIBE          -> OK          then `EVENT D1=OFF` on every change
+//! UNSUBSCRIBE        -> OK
+//! NET                -> NET DHCP=ON ADDRESS=192.168.210.201/24 ... LINK=UP
+//! NET DHCP ON        -> OK          (or OFF)
+//! NET ADDRESS 10.0.0.5/16 -> OK     the prefix length is optional
+//! NET MASK 255.255.0.0    -> OK
+//! NET GATEWAY 10.0.0.1    -> OK     or NONE, like DNS
+//! NET DNS 10.0.0.1        -> OK
+//! anything else      -> ERR <reason>
+//! ```
+//!
+//! `OK` means the command was queued for the outputs task, which may
+//! still drop it under the interlock rules. Subscribers see what really
+//! happened as events, and `EVENT LINK=UP` or `EVENT ADDRESS=10.0.0.5/16`
+//! when the network changes. Network settings are used at once and stored
+//! a few seconds later, see [`crate::netconfig`]. [`Session`] keeps the state of one connection
+//! and writes its replies; the transport only moves lines.
+
+use core::fmt::{self, Write};
+use core::net::Ipv4Addr;
+
+use heapless::Vec;
+
+use crate::netconfig::{self, NetSetting, NetState};
+use crate::outputs::{Command, Levels, OutputId, OUTPUTS};
+use crate::shared::AppState;
+
+/// One parsed request line
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Request {
+    Set(OutputId, bool),
+    Toggle(OutputId),
+    Pulse(OutputId, u32),
+    SafeState,
+    /// One output, `None` for all
+    Get(Option<OutputId>),
+    Status,
+    Subscribe,
+    Unsubscribe,
+    /// Shows the network, or changes one setting
+    Net(Option<NetSetting>),
+}
+
+/// Why a request line was not understood
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum RequestError {
+    Empty,
+    UnknownRequest,
+    UnknownOutput,
+    /// Expected `ON` or `OFF`
+    InvalidLevel,
+    InvalidDuration,
+    /// Not an address a host can have, or not a contiguous mask
+    InvalidAddress,
+    UnknownSetting,
+    MissingArgument,
+    TooManyArguments,
+    /// The line did not fit the line buffer
+    TooLong,
+    NotUtf8,
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RequestError::Empty => "empty request",
+            RequestError::UnknownRequest => "unknown request",
+            RequestError::UnknownOutput => "unknown output",
+            RequestError::InvalidLevel => "expected ON or OFF",
+            RequestError::InvalidDuration => "expected a duration in ms",
+            RequestError::InvalidAddress => "expected an IPv4 address",
+            RequestError::UnknownSetting => "unknown network setting",
+            RequestError::MissingArgument => "missing argument",
+            RequestError::TooManyArguments => "too many arguments",
+            RequestError::TooLong => "line too long",
+            RequestError::NotUtf8 => "invalid UTF-8",
+        })
+    }
+}
+
+impl core::error::Error for RequestError {}
+
+impl Request {
+    pub fn parse(line: &str) -> Result<Self, RequestError> {
+        let mut words = line.split_ascii_whitespace();
+        let keyword = words.next().ok_or(RequestError::Empty)?;
+        let mut arg = || words.next().ok_or(RequestError::MissingArgument);
+        let is = |word: &str| keyword.eq_ignore_ascii_case(word);
+
+        let request = if is("SET") {
+            let output = output(arg()?)?;
+            Request::Set(output, level(arg()?)?)
+        } else if is("TOGGLE") {
+            Request::Toggle(output(arg()?)?)
+        } else if is("PULSE") {
+            let output = output(arg()?)?;
+            let ms = arg()?.parse().map_err(|_| RequestError::InvalidDuration)?;
+            Request::Pulse(output, ms)
+        } else if is("SAFE") {
+            Request::SafeState
+        } else if is("GET") {
+            match arg()? {
+                all if all.eq_ignore_ascii_case("ALL") => Request::Get(None),
+                name => Request::Get(Some(output(name)?)),
+            }
+        } else if is("STATUS") {
+            Request::Status
+        } else if is("SUBSCRIBE") {
+            Request::Subscribe
+        } else if is("UNSUBSCRIBE") {
+            Request::Unsubscribe
+        } else if is("NET") {
+            match arg() {
+                Ok(name) => Request::Net(Some(net_setting(name, arg()?)?)),
+                Err(_) => Request::Net(None),
+            }
+        } else {
+            return Err(RequestError::UnknownRequest);
+        };
+
+        match words.next() {
+            Some(_) => Err(RequestError::TooManyArguments),
+            None => Ok(request),
+        }
+    }
+
+    /// What the outputs task has to do for it, `None` for requests the
+    /// session answers itself
+    pub fn command(self) -> Option<Command> {
+        match self {
+            Request::Set(output, true) => Some(Command::Set(output)),
+            Request::Set(output, false) => Some(Command::Clear(output)),
+            Request::Toggle(output) => Some(Command::Toggle(output)),
+            Request::Pulse(output, ms) => Some(Command::Pulse(output, ms)),
+            Request::SafeState => Some(Command::SafeState),
+            Request::Get(_)
+            | Request::Status
+            | Request::Subscribe
+            | Request::Unsubscribe
+            | Request::Net(_) => None,
+        }
+    }
+}
+
+fn level(word: &str) -> Result<bool, RequestError> {
+    if word.eq_ignore_ascii_case("ON") {
+        Ok(true)
+    } else if word.eq_ignore_ascii_case("OFF") {
+        Ok(false)
+    } else {
+        Err(RequestError::InvalidLevel)
+    }
+}
+
+fn net_setting(name: &str, value: &str) -> Result<NetSetting, RequestError> {
+    let is = |word: &str| name.eq_ignore_ascii_case(word);
+    // A gateway or DNS server, `NONE` for none
+    let host = || match value {
+        none if none.eq_ignore_ascii_case("NONE") => Ok(None),
+        value => match netconfig::parse_address(value) {
+            Some((address, None)) => Ok(Some(address)),
+            _ => Err(RequestError::InvalidAddress),
+        },
+    };
+
+    if is("DHCP") {
+        Ok(NetSetting::Dhcp(level(value)?))
+    } else if is("ADDRESS") {
+        let (address, prefix_len) =
+            netconfig::parse_address(value).ok_or(RequestError::InvalidAddress)?;
+        Ok(NetSetting::Address(address, prefix_len))
+    } else if is("MASK") {
+        let mask = value.parse().map_err(|_| RequestError::InvalidAddress)?;
+        let prefix_len = netconfig::prefix_len(mask).ok_or(RequestError::InvalidAddress)?;
+        Ok(NetSetting::PrefixLen(prefix_len))
+    } else if is("GATEWAY") {
+        Ok(NetSetting::Gateway(host()?))
+    } else if is("DNS") {
+        Ok(NetSetting::Dns(host()?))
+    } else {
+        Err(RequestError::UnknownSetting)
+    }
+}
+
+fn output(name: &str) -> Result<OutputId, RequestError> {
+    OUTPUTS
+        .iter()
+        .position(|o| o.name.eq_ignore_ascii_case(name))
+        .map(|i| OutputId(i as u8))
+        .ok_or(RequestError::UnknownOutput)
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "ON"
+    } else {
+        "OFF"
+    }
+}
+
+/// What has to be done for a request besides the reply
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Effect {
+    /// For the outputs task
+    Output(Command),
+    /// A network setting, to change in the stored ones and use
+    Network(NetSetting),
+}
+
+/// State of one connection
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Session {
+    subscribed: bool,
+}
+
+impl Session {
+    pub fn is_subscribed(&self) -> bool {
+        self.subscribed
+    }
+
+    /// Answers one request line in `state`. Writes the reply line to
+    /// `reply` and returns what else has to be done, if anything.
+    pub fn handle(
+        &mut self,
+        line: Result<&str, RequestError>,
+        state: &AppState,
+        reply: &mut impl Write,
+    ) -> Result<Option<Effect>, fmt::Error> {
+        let levels = state.outputs;
+        let request = match line.and_then(Request::parse) {
+            Ok(request) => request,
+            Err(e) => {
+                write!(reply, "ERR {e}\r\n")?;
+                return Ok(None);
+            }
+        };
+
+        match request {
+            Request::Get(Some(output)) => write!(
+                reply,
+                "{}={}\r\n",
+                output.name(),
+                on_off(levels.get(output))
+            )?,
+            Request::Get(None) => {
+                for (i, config) in OUTPUTS.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { " " };
+                    let on = levels.get(OutputId(i as u8));
+                    write!(reply, "{separator}{}={}", config.name, on_off(on))?;
+                }
+                reply.write_str("\r\n")?;
+            }
+            Request::Status => {
+                write!(
+                    reply,
+                    "STATUS VERSION={} OUTPUTS=",
+                    env!("CARGO_PKG_VERSION")
+                )?;
+                for (i, config) in OUTPUTS.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { "," };
+                    write!(reply, "{separator}{}", config.name)?;
+                }
+                write!(reply, " SUBSCRIBED={}\r\n", yes_no(self.subscribed))?;
+            }
+            Request::Subscribe | Request::Unsubscribe => {
+                self.subscribed = request == Request::Subscribe;
+                reply.write_str("OK\r\n")?;
+            }
+            Request::Net(None) => write_network(&state.network, reply)?,
+            Request::Net(Some(setting)) => {
+                reply.write_str("OK\r\n")?;
+                return Ok(Some(Effect::Network(setting)));
+            }
+            _ => reply.write_str("OK\r\n")?,
+        }
+        Ok(request.command().map(Effect::Output))
+    }
+
+    /// Writes an `EVENT` line for every output that changed from `old` to
+    /// `new`, nothing unless subscribed
+    pub fn events(&self, old: Levels, new: Levels, out: &mut impl Write) -> fmt::Result {
+        if !self.subscribed {
+            return Ok(());
+        }
+        for change in new.changes_since(old) {
+            write!(
+                out,
+                "EVENT {}={}\r\n",
+                change.output.name(),
+                on_off(change.on)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes an `EVENT` line for the link and for the address in use if
+    /// they changed from `old` to `new`, nothing unless subscribed
+    pub fn network_events(
+        &self,
+        old: &NetState,
+        new: &NetState,
+        out: &mut impl Write,
+    ) -> fmt::Result {
+        if !self.subscribed {
+            return Ok(());
+        }
+        if new.link_up != old.link_up {
+            let link = if new.link_up { "UP" } else { "DOWN" };
+            write!(out, "EVENT LINK={link}\r\n")?;
+        }
+        if new.address != old.address {
+            out.write_str("EVENT ADDRESS=")?;
+            write_cidr(new.address, out)?;
+            out.write_str("\r\n")?;
+        }
+        Ok(())
+    }
+}
+
+fn write_network(network: &NetState, out: &mut impl Write) -> fmt::Result {
+    let config = &network.config;
+    write!(
+        out,
+        "NET DHCP={} ADDRESS={}/{} GATEWAY=",
+        on_off(config.dhcp),
+        config.address,
+        config.prefix_len
+    )?;
+    write_host(config.gateway, out)?;
+    out.write_str(" DNS=")?;
+    write_host(config.dns, out)?;
+    out.write_str(" MAC=")?;
+    for (i, byte) in network.mac.iter().enumerate() {
+        let separator = if i == 0 { "" } else { ":" };
+        write!(out, "{separator}{byte:02X}")?;
+    }
+    let link = if network.link_up { "UP" } else { "DOWN" };
+    write!(out, " LINK={link} CURRENT=")?;
+    write_cidr(network.address, out)?;
+    write!(out, " FALLBACK={}\r\n", yes_no(network.fallback))
+}
+
+fn write_host(host: Option<Ipv4Addr>, out: &mut impl Write) -> fmt::Result {
+    match host {
+        Some(address) => write!(out, "{address}"),
+        None => out.write_str("NONE"),
+    }
+}
+
+fn write_cidr(cidr: Option<(Ipv4Addr, u8)>, out: &mut impl Write) -> fmt::Result {
+    match cidr {
+        Some((address, prefix_len)) => write!(out, "{address}/{prefix_len}"),
+        None => out.write_str("NONE"),
+    }
+}
+
+fn yes_no(yes: bool) -> &'static str {
+    if yes {
+        "YES"
+    } else {
+        "NO"
+    }
+}
+
+/// Collects received bytes into lines of up to `N` bytes
+#[derive(Clone, Debug, Default)]
+pub struct LineBuffer<const N: usize> {
+    line: Vec<u8, N>,
+    /// Bytes of the current line were dropped
+    overflow: bool,
+    /// The line was returned, clear it with the next byte
+    done: bool,
+}
+
+impl<const N: usize> LineBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            overflow: false,
+            done: false,
+        }
+    }
+
+    /// Adds `byte`, returns the line it ends. Empty lines are skipped, so
+    /// CR LF ends one line only.
+    pub fn push(&mut self, byte: u8) -> Option<Result<&str, RequestError>> {
+        if core::mem::take(&mut self.done) {
+            self.line.clear();
+            self.overflow = false;
+        }
+
+        if byte != b'\r' && byte != b'\n' {
+            if self.line.push(byte).is_err() {
+                self.overflow = true;
+            }
+            return None;
+        }
+        if self.line.is_empty() && !self.overflow {
+            return None;
+        }
+
+        self.done = true;
+        if self.overflow {
+            return Some(Err(RequestError::TooLong));
+        }
+        Some(core::str::from_utf8(&self.line).map_err(|_| RequestError::NotUtf8))
+    }
+}
@@ -0,0 +1,148 @@
+//! Display rotation in steps of 90°.
+//!
+//! The LTDC always scans the panel in landscape. A [`Rotated`] draw target
+//! turns the coordinates of everything drawn through it, and the touch
+//! [`Transform`](crate::calibration::Transform) turns touch points back with
+//! the same [`Rotation`], so screens only ever see one coordinate system.
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::Pixel;
+
+use crate::framebuffer::Overlay;
+
+/// Clockwise rotation of the picture on the panel
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    /// Size the screens see on a panel of `physical` size
+    pub const fn size(self, physical: Size) -> Size {
+        match self {
+            Rotation::Deg0 | Rotation::Deg180 => physical,
+            Rotation::Deg90 | Rotation::Deg270 => Size::new(physical.height, physical.width),
+        }
+    }
+
+    /// Panel pixel of the screen pixel `point`
+    pub fn to_physical(self, point: Point, physical: Size) -> Point {
+        let (w, h) = (physical.width as i32, physical.height as i32);
+        match self {
+            Rotation::Deg0 => point,
+            Rotation::Deg90 => Point::new(w - 1 - point.y, point.x),
+            Rotation::Deg180 => Point::new(w - 1 - point.x, h - 1 - point.y),
+            Rotation::Deg270 => Point::new(point.y, h - 1 - point.x),
+        }
+    }
+
+    /// Screen pixel of the panel pixel `point`
+    pub fn to_logical(self, point: Point, physical: Size) -> Point {
+        let (w, h) = (physical.width as i32, physical.height as i32);
+        match self {
+            Rotation::Deg0 => point,
+            Rotation::Deg90 => Point::new(point.y, w - 1 - point.x),
+            Rotation::Deg180 => Point::new(w - 1 - point.x, h - 1 - point.y),
+            Rotation::Deg270 => Point::new(h - 1 - point.y, point.x),
+        }
+    }
+
+    /// Panel area of the screen area `rect`
+    pub fn rect_to_physical(self, rect: &Rectangle, physical: Size) -> Rectangle {
+        match rect.bottom_right() {
+            Some(bottom_right) => Rectangle::with_corners(
+                self.to_physical(rect.top_left, physical),
+                self.to_physical(bottom_right, physical),
+            ),
+            None => Rectangle::zero(),
+        }
+    }
+}
+
+/// Draws into `target` with the picture rotated by `rotation`
+pub struct Rotated<'a, D> {
+    target: &'a mut D,
+    rotation: Rotation,
+}
+
+impl<'a, D: DrawTarget + OriginDimensions> Rotated<'a, D> {
+    pub fn new(target: &'a mut D, rotation: Rotation) -> Self {
+        Self { target, rotation }
+    }
+
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// The unrotated target
+    pub fn inner(&mut self) -> &mut D {
+        self.target
+    }
+}
+
+impl<D: DrawTarget + OriginDimensions> DrawTarget for Rotated<'_, D> {
+    type Color = D::Color;
+    type Error = D::Error;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (rotation, physical) = (self.rotation, self.target.size());
+        self.target.draw_iter(
+            pixels
+                .into_iter()
+                .map(|Pixel(point, color)| Pixel(rotation.to_physical(point, physical), color)),
+        )
+    }
+
+    /// Only unrotated areas keep the fast path, the colors come row by row
+    /// in screen coordinates
+    fn fill_contiguous<I>(&mut self, area: &Rectangle, colors: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Self::Color>,
+    {
+        if self.rotation == Rotation::Deg0 {
+            return self.target.fill_contiguous(area, colors);
+        }
+
+        let (rotation, physical) = (self.rotation, self.target.size());
+        let points = (area.top_left.y..area.top_left.y + area.size.height as i32).flat_map(|y| {
+            (area.top_left.x..area.top_left.x + area.size.width as i32)
+                .map(move |x| Point::new(x, y))
+        });
+        self.target.draw_iter(
+            points
+                .zip(colors)
+                .map(|(point, color)| Pixel(rotation.to_physical(point, physical), color)),
+        )
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = self.rotation.rect_to_physical(area, self.target.size());
+        self.target.fill_solid(&area, color)
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        self.target.clear(color)
+    }
+}
+
+impl<D: DrawTarget + OriginDimensions> OriginDimensions for Rotated<'_, D> {
+    fn size(&self) -> Size {
+        self.rotation.size(self.target.size())
+    }
+}
+
+impl<D: Overlay + OriginDimensions> Overlay for Rotated<'_, D> {
+    fn clear_transparent(&mut self) {
+        self.target.clear_transparent();
+    }
+}
@@ -0,0 +1,478 @@
+//! SCPI parser and dispatcher, so lab automation can talk to the panel
+//! like to any other instrument.
+//!
+//! [`Scpi`] takes one program message per line, e.g.
+//! `*RST;:OUTP:RF ON;:SOUR:POW:LEV 45;LEV?`, and turns it into output
+//! [`Command`]s and a response line. It knows nothing about the transport,
+//! the firmware serves it on the second USB serial port and over TCP, see
+//! [`crate::net`].
+//!
+//! | Header                                     | Parameter       | Query   |
+//! |--------------------------------------------|-----------------|---------|
+//! | `*IDN?`                                    |                 | ident   |
+//! | `*RST`                                     | safe state      |         |
+//! | `*CLS`                                     | clears errors   |         |
+//! | `*OPC?`                                    |                 | `1`     |
+//! | `OUTPut:RF[:STATe]`                        | `ON\|OFF\|1\|0` | `1\|0`  |
+//! | `[SOURce]:POWer[:LEVel][:IMMediate][:AMPLitude]` | dBm, `MIN\|MAX` | dBm |
+//! | `SYSTem:ERRor[:NEXT]?`                     |                 | error   |
+//! | `SYSTem:ERRor:COUNt?`                      |                 | count   |
+//!
+//! Headers match in their short (upper case) or long form, a header
+//! without a leading `:` continues the path of the one before it. Errors
+//! go to a queue of [`ERROR_QUEUE_LEN`] entries and end the message. A
+//! message takes up to [`MAX_COMMANDS`] commands.
+//! Commands are checked against the [`Interlock`], so a power level
+//! without RF is a settings conflict right away. Quoted string parameters
+//! are not supported, none of the headers takes one.
+
+use core::fmt::{self, Write};
+
+use heapless::{Deque, Vec};
+
+use crate::interlock::Interlock;
+use crate::outputs::{Command, Levels, OutputId, OUTPUTS};
+
+/// Errors kept for `SYSTem:ERRor?`, the last one is replaced by
+/// [`QUEUE_OVERFLOW`] when it is full
+pub const ERROR_QUEUE_LEN: usize = 8;
+
+/// Output commands of one program message, more are [`TOO_MUCH_DATA`]
+pub const MAX_COMMANDS: usize = 16;
+
+/// Nodes of a header, more than any header of `TREE` has
+const MAX_NODES: usize = 8;
+
+/// An entry of the error queue, with the standard SCPI code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Error {
+    pub code: i16,
+    pub message: &'static str,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},\"{}\"", self.code, self.message)
+    }
+}
+
+const fn error(code: i16, message: &'static str) -> Error {
+    Error { code, message }
+}
+
+pub const NO_ERROR: Error = error(0, "No error");
+pub const SYNTAX_ERROR: Error = error(-102, "Syntax error");
+pub const DATA_TYPE_ERROR: Error = error(-104, "Data type error");
+pub const PARAMETER_NOT_ALLOWED: Error = error(-108, "Parameter not allowed");
+pub const MISSING_PARAMETER: Error = error(-109, "Missing parameter");
+pub const UNDEFINED_HEADER: Error = error(-113, "Undefined header");
+pub const SETTINGS_CONFLICT: Error = error(-221, "Settings conflict");
+pub const DATA_OUT_OF_RANGE: Error = error(-222, "Data out of range");
+pub const TOO_MUCH_DATA: Error = error(-223, "Too much data");
+pub const ILLEGAL_PARAMETER_VALUE: Error = error(-224, "Illegal parameter value");
+pub const HARDWARE_MISSING: Error = error(-241, "Hardware missing");
+pub const QUEUE_OVERFLOW: Error = error(-350, "Queue overflow");
+pub const INPUT_BUFFER_OVERRUN: Error = error(-363, "Input buffer overrun");
+
+/// SCPI for "not a number", the power level while no level is on
+const NOT_A_NUMBER: &str = "9.91E37";
+
+/// Which outputs the SCPI subsystems drive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instrument {
+    /// Answer to `*IDN?`: manufacturer, model, serial number, firmware
+    pub identity: &'static str,
+    /// Switched by `OUTPut:RF`
+    pub rf: Option<OutputId>,
+    /// One output per power level in dBm, for `SOURce:POWer`, exactly one
+    /// of them is on
+    pub power_levels: &'static [(f32, OutputId)],
+}
+
+const IDENTITY: &str = concat!("f7disco-rs,Output panel,0,", env!("CARGO_PKG_VERSION"));
+
+/// The RF amplifier of `screens/med_chamber.toml`
+pub const MED_CHAMBER: Instrument = Instrument {
+    identity: IDENTITY,
+    rf: Some(OutputId(0)),
+    power_levels: &[
+        (43.0, OutputId(1)),
+        (45.0, OutputId(2)),
+        (47.0, OutputId(3)),
+    ],
+};
+
+/// No RF or power outputs, their headers report [`HARDWARE_MISSING`]
+pub const NO_INSTRUMENT: Instrument = Instrument {
+    identity: IDENTITY,
+    rf: None,
+    power_levels: &[],
+};
+
+/// What a header addresses
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Node {
+    Identify,
+    Reset,
+    ClearStatus,
+    OperationComplete,
+    RfState,
+    PowerLevel,
+    NextError,
+    ErrorCount,
+}
+
+/// Headers, `[...]` nodes are optional
+const TREE: &[(&[&str], Node)] = &[
+    (&["*IDN"], Node::Identify),
+    (&["*RST"], Node::Reset),
+    (&["*CLS"], Node::ClearStatus),
+    (&["*OPC"], Node::OperationComplete),
+    (&["OUTPut", "RF", "[STATe]"], Node::RfState),
+    (
+        &["[SOURce]", "POWer", "[LEVel]", "[IMMediate]", "[AMPLitude]"],
+        Node::PowerLevel,
+    ),
+    (&["SYSTem", "ERRor", "[NEXT]"], Node::NextError),
+    (&["SYSTem", "ERRor", "COUNt"], Node::ErrorCount),
+];
+
+/// One connection's parser state and error queue
+#[derive(Clone, Debug)]
+pub struct Scpi {
+    instrument: Instrument,
+    interlock: Interlock,
+    errors: Deque<Error, ERROR_QUEUE_LEN>,
+}
+
+impl Scpi {
+    pub fn new(instrument: Instrument, interlock: Interlock) -> Self {
+        Self {
+            instrument,
+            interlock,
+            errors: Deque::new(),
+        }
+    }
+
+    /// Queues `error` for `SYSTem:ERRor?`
+    pub fn push_error(&mut self, error: Error) {
+        if self.errors.is_full() {
+            self.errors.pop_back();
+            let _ = self.errors.push_back(QUEUE_OVERFLOW);
+        } else {
+            let _ = self.errors.push_back(error);
+        }
+    }
+
+    /// The oldest queued error, [`NO_ERROR`] if there is none
+    pub fn pop_error(&mut self) -> Error {
+        self.errors.pop_front().unwrap_or(NO_ERROR)
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.errors.len()
+    }
+
+    /// Executes one program message with the outputs at `levels`. Query
+    /// responses are written to `reply` as one line, the commands for the
+    /// outputs task are returned.
+    pub fn process(
+        &mut self,
+        message: &str,
+        levels: Levels,
+        reply: &mut impl Write,
+    ) -> Result<Effect, fmt::Error> {
+        let mut run = Run {
+            effect: Effect {
+                commands: Vec::new(),
+                levels,
+            },
+            responses: 0,
+        };
+        // Path of the previous header, for relative headers
+        let mut path: Vec<&str, MAX_NODES> = Vec::new();
+
+        for unit in message.split(';') {
+            let unit = unit.trim();
+            if unit.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.unit(unit, &mut path, &mut run, reply)? {
+                self.push_error(e);
+                break;
+            }
+        }
+
+        if run.responses > 0 {
+            reply.write_char('\n')?;
+        }
+        Ok(run.effect)
+    }
+
+    /// Executes one message unit. Formatting errors are returned outside,
+    /// SCPI errors inside.
+    fn unit<'m>(
+        &mut self,
+        unit: &'m str,
+        path: &mut Vec<&'m str, MAX_NODES>,
+        run: &mut Run,
+        reply: &mut impl Write,
+    ) -> Result<Result<(), Error>, fmt::Error> {
+        let (header, parameters) = match unit.split_once(|c: char| c.is_ascii_whitespace()) {
+            Some((header, parameters)) => (header, Some(parameters.trim())),
+            None => (unit, None),
+        };
+        let (header, query) = match header.strip_suffix('?') {
+            Some(header) => (header, true),
+            None => (header, false),
+        };
+
+        // Common commands leave the path alone
+        let nodes: Vec<&str, MAX_NODES> = if header.starts_with('*') {
+            Vec::from_iter([header])
+        } else {
+            let absolute = header.strip_prefix(':');
+            let relative = absolute.unwrap_or(header);
+            if absolute.is_some() {
+                path.clear();
+            }
+            let mut nodes = path.clone();
+            for node in relative.split(':') {
+                if !is_mnemonic(node) {
+                    return Ok(Err(SYNTAX_ERROR));
+                }
+                // Longer than every header of the tree
+                if nodes.push(node).is_err() {
+                    return Ok(Err(UNDEFINED_HEADER));
+                }
+            }
+            path.clone_from(&nodes);
+            path.pop();
+            nodes
+        };
+
+        let Some(&(_, node)) = TREE.iter().find(|(pattern, _)| matches(pattern, &nodes)) else {
+            return Ok(Err(UNDEFINED_HEADER));
+        };
+        let parameter = match parameters {
+            Some(p) if p.contains(',') => return Ok(Err(PARAMETER_NOT_ALLOWED)),
+            Some("") | None => None,
+            Some(p) => Some(p),
+        };
+
+        if query {
+            if parameter.is_some() {
+                return Ok(Err(PARAMETER_NOT_ALLOWED));
+            }
+            if let Err(e) = self.check_query(node) {
+                return Ok(Err(e));
+            }
+            if run.responses > 0 {
+                reply.write_char(';')?;
+            }
+            run.responses += 1;
+            self.query(node, run.effect.levels, reply)?;
+            return Ok(Ok(()));
+        }
+
+        Ok(self.command(node, parameter, run))
+    }
+
+    /// Whether `node` can be queried, before anything of its response is
+    /// written
+    fn check_query(&self, node: Node) -> Result<(), Error> {
+        match node {
+            Node::RfState if self.instrument.rf.is_none() => Err(HARDWARE_MISSING),
+            Node::PowerLevel if self.instrument.power_levels.is_empty() => Err(HARDWARE_MISSING),
+            Node::Reset | Node::ClearStatus => Err(UNDEFINED_HEADER),
+            _ => Ok(()),
+        }
+    }
+
+    fn query(&mut self, node: Node, levels: Levels, reply: &mut impl Write) -> fmt::Result {
+        match node {
+            Node::Identify => reply.write_str(self.instrument.identity),
+            Node::OperationComplete => reply.write_char('1'),
+            Node::RfState => {
+                let on = self.instrument.rf.is_some_and(|rf| levels.get(rf));
+                reply.write_char(if on { '1' } else { '0' })
+            }
+            Node::PowerLevel => match self
+                .instrument
+                .power_levels
+                .iter()
+                .find(|(_, output)| levels.get(*output))
+            {
+                Some((dbm, _)) => write!(reply, "{dbm}"),
+                None => reply.write_str(NOT_A_NUMBER),
+            },
+            Node::NextError => write!(reply, "{}", self.pop_error()),
+            Node::ErrorCount => write!(reply, "{}", self.error_count()),
+            Node::Reset | Node::ClearStatus => Ok(()),
+        }
+    }
+
+    fn command(&mut self, node: Node, parameter: Option<&str>, run: &mut Run) -> Result<(), Error> {
+        let needs_parameter = matches!(node, Node::RfState | Node::PowerLevel);
+        match (needs_parameter, parameter) {
+            (true, None) => return Err(MISSING_PARAMETER),
+            (false, Some(_)) => return Err(PARAMETER_NOT_ALLOWED),
+            _ => {}
+        }
+
+        match node {
+            Node::Reset => run.send(&self.interlock, Command::SafeState),
+            Node::ClearStatus => {
+                self.errors.clear();
+                Ok(())
+            }
+            Node::RfState => {
+                let rf = self.instrument.rf.ok_or(HARDWARE_MISSING)?;
+                let on = boolean(parameter.unwrap_or_default())?;
+                run.send(
+                    &self.interlock,
+                    if on {
+                        Command::Set(rf)
+                    } else {
+                        Command::Clear(rf)
+                    },
+                )
+            }
+            Node::PowerLevel => {
+                let levels = self.instrument.power_levels;
+                let dbm = power(parameter.unwrap_or_default(), levels)?;
+                let &(_, output) = levels
+                    .iter()
+                    .find(|(level, _)| (level - dbm).abs() < 0.05)
+                    .ok_or(DATA_OUT_OF_RANGE)?;
+
+                // One level at a time, even without an exclusive rule
+                for &(_, other) in levels {
+                    if other != output && run.effect.levels.get(other) {
+                        run.send(&self.interlock, Command::Clear(other))?;
+                    }
+                }
+                run.send(&self.interlock, Command::Set(output))
+            }
+            Node::Identify | Node::OperationComplete | Node::NextError | Node::ErrorCount => {
+                Err(UNDEFINED_HEADER)
+            }
+        }
+    }
+}
+
+/// What a program message does to the outputs
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Effect {
+    /// For the outputs task, in order
+    pub commands: Vec<Command, MAX_COMMANDS>,
+    /// The levels once the outputs task executed them all
+    pub levels: Levels,
+}
+
+/// One message while it is executed
+struct Run {
+    effect: Effect,
+    /// Query responses written so far
+    responses: usize,
+}
+
+impl Run {
+    /// Adds `command` if the interlock allows it at the levels the
+    /// commands before lead to
+    fn send(&mut self, interlock: &Interlock, command: Command) -> Result<(), Error> {
+        if self.effect.commands.is_full() {
+            return Err(TOO_MUCH_DATA);
+        }
+        let steps = interlock
+            .plan(command, self.effect.levels)
+            .map_err(|_| SETTINGS_CONFLICT)?;
+        for step in steps {
+            match step.command {
+                Command::Set(output) | Command::Pulse(output, _) => {
+                    self.effect.levels.set(output, true)
+                }
+                Command::Clear(output) => self.effect.levels.set(output, false),
+                Command::Toggle(output) => self
+                    .effect
+                    .levels
+                    .set(output, !self.effect.levels.get(output)),
+                Command::SafeState => {
+                    for (i, config) in OUTPUTS.iter().enumerate() {
+                        self.effect.levels.set(OutputId(i as u8), config.safe);
+                    }
+                }
+                Command::Read => {}
+            }
+        }
+        // There was room, see above
+        let _ = self.effect.commands.push(command);
+        Ok(())
+    }
+}
+
+fn is_mnemonic(node: &str) -> bool {
+    node.starts_with(|c: char| c.is_ascii_alphabetic())
+        && node.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whether `nodes` name the header `pattern`
+fn matches(pattern: &[&str], nodes: &[&str]) -> bool {
+    match (pattern.split_first(), nodes.split_first()) {
+        (None, None) => true,
+        (None, Some(_)) => false,
+        (Some((first, rest)), _) => {
+            let optional = first.strip_prefix('[').and_then(|p| p.strip_suffix(']'));
+            let keyword = optional.unwrap_or(first);
+            let here = nodes
+                .split_first()
+                .is_some_and(|(node, others)| is_keyword(keyword, node) && matches(rest, others));
+            here || (optional.is_some() && matches(rest, nodes))
+        }
+    }
+}
+
+/// `node` is the short form (the upper case part) or the long form of
+/// `keyword`, in any case
+fn is_keyword(keyword: &str, node: &str) -> bool {
+    let short = keyword
+        .find(|c: char| c.is_ascii_lowercase())
+        .map_or(keyword, |end| &keyword[..end]);
+    node.eq_ignore_ascii_case(short) || node.eq_ignore_ascii_case(keyword)
+}
+
+fn boolean(parameter: &str) -> Result<bool, Error> {
+    if parameter.eq_ignore_ascii_case("ON") || parameter == "1" {
+        Ok(true)
+    } else if parameter.eq_ignore_ascii_case("OFF") || parameter == "0" {
+        Ok(false)
+    } else {
+        Err(ILLEGAL_PARAMETER_VALUE)
+    }
+}
+
+/// A power in dBm, with an optional `DBM` suffix, or `MINimum` and
+/// `MAXimum` of `levels`
+fn power(parameter: &str, levels: &[(f32, OutputId)]) -> Result<f32, Error> {
+    let dbms = levels.iter().map(|&(dbm, _)| dbm);
+    if is_keyword("MINimum", parameter) {
+        return dbms.reduce(f32::min).ok_or(HARDWARE_MISSING);
+    }
+    if is_keyword("MAXimum", parameter) {
+        return dbms.reduce(f32::max).ok_or(HARDWARE_MISSING);
+    }
+    if levels.is_empty() {
+        return Err(HARDWARE_MISSING);
+    }
+
+    let number = match parameter.len().checked_sub(3) {
+        Some(end)
+            if parameter.is_char_boundary(end) && parameter[end..].eq_ignore_ascii_case("DBM") =>
+        {
+            parameter[..end].trim_end()
+        }
+        _ => parameter,
+    };
+    number.parse().map_err(|_| DATA_TYPE_ERROR)
+}
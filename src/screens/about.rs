@@ -0,0 +1,61 @@
+//! Firmware and board information.
+
+use alloc::format;
+
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Alignment;
+
+use crate::framebuffer::{LCD_HEIGHT, LCD_WIDTH};
+use crate::layout::{Align, Insets, Layout, Length};
+use crate::widgets::{Button, Event, Label, Theme, WidgetTree};
+
+use super::navigator::{Action, Screen, Settings};
+
+/// Pushed from the settings, "Back" closes it
+pub struct AboutScreen {
+    ui: WidgetTree,
+}
+
+impl AboutScreen {
+    pub fn new(area: Rectangle) -> Self {
+        let mut ui = WidgetTree::new(Theme::default());
+
+        let mut label = |text| {
+            let label = Label::new(text).with_alignment(Alignment::Center);
+            Layout::leaf(ui.add(None, Rectangle::zero(), label)).height(Length::Px(24))
+        };
+        let lines = [
+            label(format!("f7disco-rs {}", env!("CARGO_PKG_VERSION"))),
+            label("STM32F746G-DISCO".into()),
+            label(format!("RK043FN48H, {LCD_WIDTH} x {LCD_HEIGHT}")),
+        ];
+        let back = ui.add(None, Rectangle::zero(), Button::new("Back"));
+
+        Layout::column(lines.into_iter().chain([Layout::leaf(back).size(120, 50)]))
+            .padding(Insets::all(20))
+            .gap(12)
+            .align(Align::Center)
+            .apply_to(&mut ui, area);
+
+        Self { ui }
+    }
+}
+
+impl Screen for AboutScreen {
+    fn title(&self) -> &str {
+        "About"
+    }
+
+    fn ui(&self) -> &WidgetTree {
+        &self.ui
+    }
+
+    fn ui_mut(&mut self) -> &mut WidgetTree {
+        &mut self.ui
+    }
+
+    fn event(&mut self, _event: Event, _settings: &mut Settings) -> Action {
+        // "Back" is the only button
+        Action::Close(None)
+    }
+}
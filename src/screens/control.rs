@@ -0,0 +1,131 @@
+//! Screen built from a [`ScreenDescription`]: labels, panels and buttons
+//! that drive the outputs.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::image::Image;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::Drawable;
+use tinytga::Tga;
+
+use crate::outputs::{OutputId, OutputLevel};
+use crate::widgets::{
+    AnyWidget, Button, Event, EventKind, Label, Panel, Theme, ToggleButton, WidgetId, WidgetTree,
+};
+
+use super::background_image;
+use super::description::{ScreenDescription, WidgetKind};
+use super::navigator::{Action, Background, Screen, Settings};
+
+/// A widget that drives an output
+struct Binding {
+    id: WidgetId,
+    output: OutputId,
+    /// Asked before the output is switched on
+    confirm: Option<String>,
+}
+
+pub struct ControlScreen {
+    name: String,
+    background: Option<&'static [u8]>,
+    ui: WidgetTree,
+    bindings: Vec<Binding>,
+}
+
+impl ControlScreen {
+    pub fn new(description: &ScreenDescription) -> Self {
+        let mut ui = WidgetTree::new(Theme::default());
+        let mut bindings = Vec::new();
+
+        for widget in &description.widgets {
+            let text = widget.text.clone();
+            let any: AnyWidget = match widget.kind {
+                WidgetKind::Label { alignment } => {
+                    Label::new(text).with_alignment(alignment).into()
+                }
+                WidgetKind::Button => Button::new(text).into(),
+                WidgetKind::Toggle { state_text } => {
+                    let mut toggle = ToggleButton::new(text);
+                    toggle.state_text = state_text;
+                    // A toggle bound to an output shows the level reported
+                    // back by the hardware
+                    toggle.external = widget.output.is_some();
+                    toggle.into()
+                }
+                WidgetKind::Panel { border_width } => Panel::new().with_border(border_width).into(),
+            };
+
+            let id = ui.add(None, widget.bounds, any);
+            if let Some(output) = widget.output {
+                bindings.push(Binding {
+                    id,
+                    output,
+                    confirm: widget.confirm.clone(),
+                });
+            }
+        }
+
+        Self {
+            name: description.name.clone(),
+            // Checked by the parser
+            background: description.background.as_deref().and_then(background_image),
+            ui,
+            bindings,
+        }
+    }
+}
+
+impl Screen for ControlScreen {
+    fn title(&self) -> &str {
+        &self.name
+    }
+
+    fn ui(&self) -> &WidgetTree {
+        &self.ui
+    }
+
+    fn ui_mut(&mut self) -> &mut WidgetTree {
+        &mut self.ui
+    }
+
+    /// Toggles bound to an output only change once the hardware reports
+    /// the new output level through [`Self::apply`]
+    fn event(&mut self, event: Event, _settings: &mut Settings) -> Action {
+        let Some(binding) = self.bindings.iter().find(|b| b.id == event.id) else {
+            return Action::None;
+        };
+
+        // Switching off is always safe
+        let confirm = match event.kind {
+            EventKind::Toggled(false) => None,
+            _ => binding.confirm.clone(),
+        };
+        match confirm {
+            Some(message) => Action::Confirm {
+                message,
+                output: binding.output,
+            },
+            None => Action::Output(binding.output),
+        }
+    }
+
+    /// Shows the actual output level reported by the hardware
+    fn apply(&mut self, level: OutputLevel) {
+        for binding in self.bindings.iter().filter(|b| b.output == level.output) {
+            self.ui.set_on(binding.id, level.on);
+        }
+    }
+
+    /// The background image, or white without one
+    fn draw_background(&self, target: &mut Background<'_, '_>) {
+        target.clear(Rgb565::WHITE).unwrap();
+
+        if let Some(data) = self.background {
+            let tga: Tga<Rgb565> = Tga::from_slice(data).unwrap();
+            Image::new(&tga, Point::new(0, 0)).draw(target).unwrap();
+        }
+    }
+}
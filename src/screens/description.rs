@@ -0,0 +1,694 @@
+//! Screen descriptions: which widgets a screen has, where they are and
+//! which output each button drives.
+//!
+//! The format is a small subset of TOML, so descriptions can live in
+//! flash or on the SD card and be switched without touching the code. A
+//! `[screen]` table names the screen and its background image, every
+//! `[[widget]]` table adds one widget, drawn in file order, and every
+//! `[[rule]]` table an interlock between outputs.
+//!
+//! ```toml
+//! [screen]
+//! name = "Med Chamber"
+//! background = "gui_med_com"
+//!
+//! [[widget]]
+//! type = "toggle"         # label, button, toggle or panel
+//! text = "RF"
+//! rect = [176, 104, 120, 50]
+//! output = "D0"           # from `outputs::OUTPUTS`, buttons and toggles only
+//! confirm = "Enable RF?"  # asked before the output is switched on
+//! state_text = true       # toggles only, appends ": ON"/": OFF"
+//!
+//! [[rule]]                 # see `interlock::Rule`
+//! exclusive = ["D1", "D2"] # or `sequence = [...]`, or `outputs = [...]`
+//! delay_ms = 50            #   with `requires = "D0"`
+//! ```
+//!
+//! Labels take `align = "left" | "center" | "right"`, panels `border = N`.
+//! [`ScreenDescription::parse`] rejects anything else, as well as widgets
+//! outside the panel, overlapping buttons, outputs driven twice and rules
+//! that contradict each other, e.g. a sequence through two outputs of an
+//! exclusive group.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Alignment;
+
+use crate::framebuffer::{LCD_HEIGHT, LCD_WIDTH};
+use crate::interlock::{Interlock, Rule};
+use crate::outputs::{OutputId, OUTPUTS};
+
+use super::background_image;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenDescription {
+    pub name: String,
+    /// Built-in image, see [`super::background_image`]
+    pub background: Option<String>,
+    pub widgets: Vec<WidgetDescription>,
+    /// Interlocks between the outputs, in file order
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WidgetDescription {
+    pub kind: WidgetKind,
+    /// Empty for panels
+    pub text: String,
+    pub bounds: Rectangle,
+    /// Sent when the widget is touched; toggles show the level reported
+    /// back for it
+    pub output: Option<OutputId>,
+    /// Question the operator has to confirm before the output is switched
+    /// on
+    pub confirm: Option<String>,
+    /// Of the `[[widget]]` header, 1-based
+    pub line: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WidgetKind {
+    Label { alignment: Alignment },
+    Button,
+    Toggle { state_text: bool },
+    Panel { border_width: u32 },
+}
+
+impl WidgetKind {
+    fn is_interactive(self) -> bool {
+        matches!(self, WidgetKind::Button | WidgetKind::Toggle { .. })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseError {
+    fn new(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl core::error::Error for ParseError {}
+
+impl ScreenDescription {
+    /// Parses and checks a description for the 480 x 272 panel
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        Self::parse_for(text, Size::new(LCD_WIDTH as u32, LCD_HEIGHT as u32))
+    }
+
+    /// Parses and checks a description for a panel of `size`
+    pub fn parse_for(text: &str, size: Size) -> Result<Self, ParseError> {
+        let tables = tables(text)?;
+
+        let mut screen = None;
+        let mut widgets = Vec::new();
+        let mut rules = Vec::new();
+        for table in tables {
+            match table.name {
+                TableName::Screen => {
+                    if screen.is_some() {
+                        return Err(ParseError::new(table.line, "second [screen] table"));
+                    }
+                    screen = Some(table);
+                }
+                TableName::Widget => widgets.push(widget(table, size)?),
+                TableName::Rule => {
+                    let line = table.line;
+                    rules.push((rule(table)?, line));
+                }
+            }
+        }
+
+        let mut screen = screen.ok_or_else(|| ParseError::new(1, "missing [screen] table"))?;
+        let name = screen.string("name")?;
+        let background = screen.optional_string("background")?;
+        if let Some((name, line)) = &background {
+            if background_image(name).is_none() {
+                return Err(ParseError::new(
+                    *line,
+                    format!("unknown background image `{name}`"),
+                ));
+            }
+        }
+        screen.finish()?;
+
+        check_conflicts(&widgets)?;
+        check_rules(&rules)?;
+
+        Ok(Self {
+            name,
+            background: background.map(|(name, _)| name),
+            widgets,
+            rules: rules.into_iter().map(|(rule, _)| rule).collect(),
+        })
+    }
+
+    /// The interlock of [`Self::rules`]
+    pub fn interlock(&self) -> Interlock {
+        Interlock::new(self.rules.clone())
+    }
+}
+
+fn widget(mut table: Table, size: Size) -> Result<WidgetDescription, ParseError> {
+    let (kind_name, kind_line) = table.take_string("type")?;
+    let kind = match kind_name.as_str() {
+        "label" => {
+            let alignment = match table.optional_string("align")? {
+                None => Alignment::Left,
+                Some((align, line)) => match align.as_str() {
+                    "left" => Alignment::Left,
+                    "center" => Alignment::Center,
+                    "right" => Alignment::Right,
+                    _ => {
+                        return Err(ParseError::new(
+                            line,
+                            "expected `left`, `center` or `right`",
+                        ))
+                    }
+                },
+            };
+            WidgetKind::Label { alignment }
+        }
+        "button" => WidgetKind::Button,
+        "toggle" => WidgetKind::Toggle {
+            state_text: table.optional_bool("state_text")?.unwrap_or(false),
+        },
+        "panel" => WidgetKind::Panel {
+            border_width: table.optional_int("border")?.unwrap_or(0),
+        },
+        _ => {
+            return Err(ParseError::new(
+                kind_line,
+                "expected `label`, `button`, `toggle` or `panel`",
+            ))
+        }
+    };
+
+    let text = match kind {
+        WidgetKind::Panel { .. } => String::new(),
+        _ => table.string("text")?,
+    };
+
+    let bounds = table.rect("rect")?;
+    if bounds.size.width == 0 || bounds.size.height == 0 {
+        return Err(ParseError::new(table.line, "`rect` is empty"));
+    }
+    // Both corners are at most 2 * u16::MAX, no overflow
+    let end = bounds.top_left + bounds.size;
+    if end.x as u32 > size.width || end.y as u32 > size.height {
+        return Err(ParseError::new(
+            table.line,
+            format!("`rect` is outside the {}x{} panel", size.width, size.height),
+        ));
+    }
+
+    let output = match table.optional_string("output")? {
+        None => None,
+        Some((_, line)) if !kind.is_interactive() => {
+            return Err(ParseError::new(
+                line,
+                "only buttons and toggles drive an output",
+            ))
+        }
+        Some((output, line)) => Some(output_id(&output, line)?),
+    };
+
+    let confirm = match table.optional_string("confirm")? {
+        Some((_, line)) if output.is_none() => {
+            return Err(ParseError::new(line, "`confirm` needs an `output`"))
+        }
+        confirm => confirm.map(|(message, _)| message),
+    };
+
+    let line = table.line;
+    table.finish()?;
+
+    Ok(WidgetDescription {
+        kind,
+        text,
+        bounds,
+        output,
+        confirm,
+        line,
+    })
+}
+
+fn rule(mut table: Table) -> Result<Rule, ParseError> {
+    let exclusive = table.optional_outputs("exclusive")?;
+    let sequence = table.optional_outputs("sequence")?;
+    let outputs = table.optional_outputs("outputs")?;
+    let requires = table.optional_string("requires")?;
+    let delay_ms = table.optional_int("delay_ms")?.unwrap_or(0);
+
+    let rule = match (exclusive, sequence, outputs, requires) {
+        (Some((outputs, line)), None, None, None) => {
+            at_least_two(&outputs, "exclusive", line)?;
+            Rule::Exclusive { outputs, delay_ms }
+        }
+        (None, Some((outputs, line)), None, None) => {
+            at_least_two(&outputs, "sequence", line)?;
+            Rule::Sequence { outputs, delay_ms }
+        }
+        (None, None, Some((outputs, _)), Some((requires, line))) => {
+            let requires = output_id(&requires, line)?;
+            if outputs.contains(&requires) {
+                return Err(ParseError::new(
+                    line,
+                    format!("{} cannot require itself", requires.name()),
+                ));
+            }
+            Rule::Requires {
+                outputs,
+                requires,
+                delay_ms,
+            }
+        }
+        _ => {
+            return Err(ParseError::new(
+                table.line,
+                "expected one of `exclusive`, `sequence` or `outputs` with `requires`",
+            ))
+        }
+    };
+
+    table.finish()?;
+    Ok(rule)
+}
+
+fn at_least_two(outputs: &[OutputId], key: &str, line: usize) -> Result<(), ParseError> {
+    if outputs.len() < 2 {
+        return Err(ParseError::new(
+            line,
+            format!("`{key}` needs two outputs or more"),
+        ));
+    }
+    Ok(())
+}
+
+fn output_id(name: &str, line: usize) -> Result<OutputId, ParseError> {
+    OutputId::by_name(name).ok_or_else(|| {
+        let names: Vec<&str> = OUTPUTS.iter().map(|o| o.name).collect();
+        ParseError::new(
+            line,
+            format!("expected one of the outputs {}", names.join(", ")),
+        )
+    })
+}
+
+/// A touch has to hit one button at most, and every output belongs to one
+/// widget so its reported level is shown in one place
+fn check_conflicts(widgets: &[WidgetDescription]) -> Result<(), ParseError> {
+    for (i, widget) in widgets.iter().enumerate() {
+        for other in &widgets[..i] {
+            if let Some(output) = widget.output.filter(|&o| Some(o) == other.output) {
+                return Err(ParseError::new(
+                    widget.line,
+                    format!(
+                        "{} is already driven by the widget on line {}",
+                        output.name(),
+                        other.line
+                    ),
+                ));
+            }
+
+            let overlap = widget.bounds.intersection(&other.bounds).size;
+            if widget.kind.is_interactive()
+                && other.kind.is_interactive()
+                && overlap.width > 0
+                && overlap.height > 0
+            {
+                return Err(ParseError::new(
+                    widget.line,
+                    format!("overlaps the button on line {}", other.line),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rules with their lines have to agree with each other: no output may
+/// depend on itself, and no output may depend on one it is exclusive with.
+/// Checked rule by rule, so the first rule that contradicts the ones
+/// before it is reported.
+fn check_rules(rules: &[(Rule, usize)]) -> Result<(), ParseError> {
+    const N: usize = OUTPUTS.len();
+
+    // needs[a][b]: `a` is only on while `b` is, directly or through other
+    // rules
+    let mut needs = [[false; N]; N];
+    let mut exclusive = Vec::new();
+    for (rule, line) in rules {
+        match rule {
+            Rule::Exclusive { outputs, .. } => exclusive.push((outputs, *line)),
+            Rule::Requires {
+                outputs, requires, ..
+            } => {
+                for output in outputs {
+                    needs[output.index()][requires.index()] = true;
+                }
+            }
+            Rule::Sequence { outputs, .. } => {
+                for (i, later) in outputs.iter().enumerate() {
+                    for earlier in &outputs[..i] {
+                        needs[later.index()][earlier.index()] = true;
+                    }
+                }
+            }
+        }
+        for k in 0..N {
+            for a in 0..N {
+                for b in 0..N {
+                    needs[a][b] |= needs[a][k] && needs[k][b];
+                }
+            }
+        }
+
+        if let Some(a) = (0..N).find(|&a| needs[a][a]) {
+            return Err(ParseError::new(
+                *line,
+                format!("the rules for {} form a loop", OUTPUTS[a].name),
+            ));
+        }
+        for (outputs, exclusive_line) in &exclusive {
+            for a in outputs.iter() {
+                if let Some(b) = outputs.iter().find(|b| needs[a.index()][b.index()]) {
+                    return Err(ParseError::new(
+                        *line,
+                        format!(
+                            "{} needs {}, but the rule on line {} lets only one of them be on",
+                            a.name(),
+                            b.name(),
+                            exclusive_line
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum TableName {
+    Screen,
+    Widget,
+    Rule,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    String(String),
+    Int(i64),
+    Bool(bool),
+    Array(Vec<i64>),
+    Strings(Vec<String>),
+}
+
+struct Entry {
+    key: String,
+    value: Value,
+    line: usize,
+}
+
+/// One `[screen]`, `[[widget]]` or `[[rule]]` with its entries. Getters remove what
+/// they read, [`Table::finish`] complains about whatever is left.
+struct Table {
+    name: TableName,
+    line: usize,
+    entries: Vec<Entry>,
+}
+
+impl Table {
+    fn take(&mut self, key: &str) -> Option<Entry> {
+        let index = self.entries.iter().position(|e| e.key == key)?;
+        Some(self.entries.remove(index))
+    }
+
+    fn missing(&self, key: &str) -> ParseError {
+        ParseError::new(self.line, format!("missing `{key}`"))
+    }
+
+    fn take_string(&mut self, key: &str) -> Result<(String, usize), ParseError> {
+        self.optional_string(key)?.ok_or_else(|| self.missing(key))
+    }
+
+    fn string(&mut self, key: &str) -> Result<String, ParseError> {
+        self.take_string(key).map(|(s, _)| s)
+    }
+
+    fn optional_string(&mut self, key: &str) -> Result<Option<(String, usize)>, ParseError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Entry {
+                value: Value::String(s),
+                line,
+                ..
+            }) => Ok(Some((s, line))),
+            Some(e) => Err(ParseError::new(e.line, format!("`{key}` must be a string"))),
+        }
+    }
+
+    fn optional_bool(&mut self, key: &str) -> Result<Option<bool>, ParseError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Entry {
+                value: Value::Bool(b),
+                ..
+            }) => Ok(Some(b)),
+            Some(e) => Err(ParseError::new(
+                e.line,
+                format!("`{key}` must be true or false"),
+            )),
+        }
+    }
+
+    fn optional_int(&mut self, key: &str) -> Result<Option<u32>, ParseError> {
+        match self.take(key) {
+            None => Ok(None),
+            Some(Entry {
+                value: Value::Int(n),
+                line,
+                ..
+            }) => u32::try_from(n)
+                .map(Some)
+                .map_err(|_| ParseError::new(line, format!("`{key}` is out of range"))),
+            Some(e) => Err(ParseError::new(e.line, format!("`{key}` must be a number"))),
+        }
+    }
+
+    /// `["D0", "D1"]`, each output once
+    fn optional_outputs(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<(Vec<OutputId>, usize)>, ParseError> {
+        let Some(entry) = self.take(key) else {
+            return Ok(None);
+        };
+        let Value::Strings(names) = &entry.value else {
+            return Err(ParseError::new(
+                entry.line,
+                format!("`{key}` must be an array of output names"),
+            ));
+        };
+
+        let mut outputs = Vec::new();
+        for name in names {
+            let output = output_id(name, entry.line)?;
+            if outputs.contains(&output) {
+                return Err(ParseError::new(
+                    entry.line,
+                    format!("{name} is listed twice"),
+                ));
+            }
+            outputs.push(output);
+        }
+        Ok(Some((outputs, entry.line)))
+    }
+
+    /// `[x, y, width, height]`
+    fn rect(&mut self, key: &str) -> Result<Rectangle, ParseError> {
+        let entry = self.take(key).ok_or_else(|| self.missing(key))?;
+        let error = || {
+            ParseError::new(
+                entry.line,
+                format!("expected `{key} = [x, y, width, height]`"),
+            )
+        };
+
+        let Value::Array(values) = &entry.value else {
+            return Err(error());
+        };
+        let &[x, y, width, height] = values.as_slice() else {
+            return Err(error());
+        };
+        // Far larger than any panel, small enough to add up without overflow
+        let value = |v: i64| {
+            u16::try_from(v)
+                .map_err(|_| ParseError::new(entry.line, format!("`{key}` is out of range")))
+        };
+
+        Ok(Rectangle::new(
+            Point::new(value(x)?.into(), value(y)?.into()),
+            Size::new(value(width)?.into(), value(height)?.into()),
+        ))
+    }
+
+    fn finish(self) -> Result<(), ParseError> {
+        match self.entries.first() {
+            None => Ok(()),
+            Some(e) => Err(ParseError::new(e.line, format!("unknown key `{}`", e.key))),
+        }
+    }
+}
+
+/// Splits the text into tables, checking the syntax only
+fn tables(text: &str) -> Result<Vec<Table>, ParseError> {
+    let mut tables: Vec<Table> = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let error = |message: &str| ParseError::new(line_number, message);
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            let header = line.split('#').next().unwrap_or_default().trim_end();
+            let name = match header {
+                "[screen]" => TableName::Screen,
+                "[[widget]]" => TableName::Widget,
+                "[[rule]]" => TableName::Rule,
+                _ => return Err(error("expected `[screen]`, `[[widget]]` or `[[rule]]`")),
+            };
+            tables.push(Table {
+                name,
+                line: line_number,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `key = value`"))?;
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(error("invalid key"));
+        }
+
+        let (value, rest) = parse_value(value.trim_start()).map_err(error)?;
+        let rest = rest.trim_start();
+        if !rest.is_empty() && !rest.starts_with('#') {
+            return Err(error("unexpected text after the value"));
+        }
+
+        let table = tables
+            .last_mut()
+            .ok_or_else(|| error("key outside of a table"))?;
+        if table.entries.iter().any(|e| e.key == key) {
+            return Err(error(&format!("duplicate key `{key}`")));
+        }
+        table.entries.push(Entry {
+            key: key.to_string(),
+            value,
+            line: line_number,
+        });
+    }
+
+    Ok(tables)
+}
+
+/// Parses the value at the start of `s`, returns it and the rest of `s`
+fn parse_value(s: &str) -> Result<(Value, &str), &'static str> {
+    if let Some(s) = s.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = s.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => return Ok((Value::String(value), &s[i + 1..])),
+                '\\' => match chars.next() {
+                    Some((_, '"')) => value.push('"'),
+                    Some((_, '\\')) => value.push('\\'),
+                    Some((_, 'n')) => value.push('\n'),
+                    _ => return Err("unknown escape in string"),
+                },
+                c => value.push(c),
+            }
+        }
+        return Err("unterminated string");
+    }
+
+    if let Some(s) = s.strip_prefix('[') {
+        let (items, rest) = s.split_once(']').ok_or("unterminated array")?;
+        let items: Vec<&str> = items
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect();
+
+        // Strings in arrays are names, without escapes or commas
+        if items.first().is_some_and(|item| item.starts_with('"')) {
+            let strings = items
+                .iter()
+                .map(|item| {
+                    item.strip_prefix('"')
+                        .and_then(|item| item.strip_suffix('"'))
+                        .filter(|item| !item.contains(['"', '\\']))
+                        .map(String::from)
+                        .ok_or("arrays hold either numbers or plain strings")
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok((Value::Strings(strings), rest));
+        }
+
+        let values = items
+            .iter()
+            .map(|item| {
+                item.parse::<i64>()
+                    .map_err(|_| "arrays hold either numbers or plain strings")
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok((Value::Array(values), rest));
+    }
+
+    let end = s
+        .find(|c: char| c.is_whitespace() || c == '#')
+        .unwrap_or(s.len());
+    let (word, rest) = s.split_at(end);
+    let value = match word {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => Value::Int(
+            word.parse()
+                .map_err(|_| "expected a string, number, boolean or array")?,
+        ),
+    };
+    Ok((value, rest))
+}
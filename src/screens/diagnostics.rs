@@ -0,0 +1,73 @@
+//! Live state of the hardware behind the GUI.
+
+use embedded_graphics::primitives::Rectangle;
+
+use crate::layout::{Insets, Layout};
+use crate::outputs::{OutputLevel, OUTPUTS};
+use crate::widgets::{Label, Theme, WidgetId, WidgetTree};
+
+use super::navigator::Screen;
+
+fn level(high: bool) -> &'static str {
+    if high {
+        "HIGH"
+    } else {
+        "LOW"
+    }
+}
+
+/// Pin level of every output as reported by the hardware
+pub struct DiagnosticsScreen {
+    ui: WidgetTree,
+    /// Level label of each output of [`OUTPUTS`]
+    levels: [WidgetId; OUTPUTS.len()],
+}
+
+impl DiagnosticsScreen {
+    pub fn new(area: Rectangle) -> Self {
+        let theme = Theme::default();
+        let mut ui = WidgetTree::new(theme);
+
+        let mut cells = alloc::vec::Vec::new();
+        let levels = OUTPUTS.map(|output| {
+            let name = ui.add(None, Rectangle::zero(), Label::new(output.name));
+            // Cleared on every change, the text gets shorter
+            let value = ui.add(
+                None,
+                Rectangle::zero(),
+                Label::new(level(output.pin_level(output.safe))).with_background(theme.background),
+            );
+            cells.extend([Layout::leaf(name), Layout::leaf(value)]);
+            value
+        });
+
+        Layout::grid(2, OUTPUTS.len() as u16, cells)
+            .padding(Insets::symmetric(60, 20))
+            .gap(8)
+            .apply_to(&mut ui, area);
+
+        Self { ui, levels }
+    }
+}
+
+impl Screen for DiagnosticsScreen {
+    fn title(&self) -> &str {
+        "Diagnostics"
+    }
+
+    fn ui(&self) -> &WidgetTree {
+        &self.ui
+    }
+
+    fn ui_mut(&mut self) -> &mut WidgetTree {
+        &mut self.ui
+    }
+
+    fn apply(&mut self, output: OutputLevel) {
+        let i = output.output.index();
+        if let (Some(&label), Some(config)) = (self.levels.get(i), OUTPUTS.get(i)) {
+            // Inverted outputs are on while the pin is low
+            self.ui.set_text(label, level(config.pin_level(output.on)));
+        }
+    }
+}
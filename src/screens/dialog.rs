@@ -0,0 +1,86 @@
+//! Modal yes/no question in front of the current screen.
+
+use alloc::string::String;
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::text::Alignment;
+
+use crate::framebuffer::{LCD_HEIGHT, LCD_WIDTH};
+use crate::layout::{Align, Insets, Layout, Length};
+use crate::outputs::OutputId;
+use crate::widgets::{Button, Event, Label, Panel, Theme, WidgetId, WidgetTree};
+
+use super::navigator::{Action, Screen, Settings};
+
+const SIZE: Size = Size::new(300, 140);
+
+/// Asks before an output is driven, see [`Action::Confirm`]
+pub struct ConfirmDialog {
+    ui: WidgetTree,
+    yes: WidgetId,
+    output: OutputId,
+}
+
+impl ConfirmDialog {
+    /// `message` centred above "Yes" and "No", the dialog centred on the
+    /// panel
+    pub fn new(message: impl Into<String>, output: OutputId) -> Self {
+        let mut ui = WidgetTree::new(Theme::default());
+
+        let area = Rectangle::new(
+            Point::new(
+                (LCD_WIDTH as i32 - SIZE.width as i32) / 2,
+                (LCD_HEIGHT as i32 - SIZE.height as i32) / 2,
+            ),
+            SIZE,
+        );
+        let frame = ui.add(None, area, Panel::new().with_border(2));
+        let message = ui.add(
+            Some(frame),
+            Rectangle::zero(),
+            Label::new(message).with_alignment(Alignment::Center),
+        );
+        let yes = ui.add(Some(frame), Rectangle::zero(), Button::new("Yes"));
+        let no = ui.add(Some(frame), Rectangle::zero(), Button::new("No"));
+
+        Layout::column([
+            Layout::leaf(message).height(Length::Px(40)),
+            Layout::row([
+                Layout::leaf(yes).size(100, 44),
+                Layout::leaf(no).size(100, 44),
+            ])
+            .size(220, 44)
+            .gap(20),
+        ])
+        .padding(Insets::all(16))
+        .gap(16)
+        .align(Align::Center)
+        .apply_to(&mut ui, area);
+
+        Self { ui, yes, output }
+    }
+}
+
+impl Screen for ConfirmDialog {
+    fn title(&self) -> &str {
+        "Confirm"
+    }
+
+    fn ui(&self) -> &WidgetTree {
+        &self.ui
+    }
+
+    fn ui_mut(&mut self) -> &mut WidgetTree {
+        &mut self.ui
+    }
+
+    fn event(&mut self, event: Event, _settings: &mut Settings) -> Action {
+        // Only the two buttons send events
+        Action::Close((event.id == self.yes).then_some(self.output))
+    }
+
+    fn is_modal(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,50 @@
+//! kolibri "hello world" from `examples/display.rs`.
+//!
+//! kolibri redraws the whole UI every frame, so there is no state to keep.
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Size;
+use embedded_graphics::mono_font::{self, ascii};
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor, WebColors};
+use kolibri_embedded_gui::label::Label;
+use kolibri_embedded_gui::style::{Spacing, Style};
+use kolibri_embedded_gui::ui::Ui;
+
+pub fn medsize_rgb888_style() -> Style<Rgb888> {
+    Style {
+        background_color: Rgb888::new(0x40, 0x80, 0x40), // pretty dark gray
+        item_background_color: Rgb888::new(0x20, 0x40, 0x20), // darker gray
+        highlight_item_background_color: Rgb888::new(0x10, 0x20, 0x10),
+        border_color: Rgb888::WHITE,
+        highlight_border_color: Rgb888::WHITE,
+        primary_color: Rgb888::CSS_DARK_CYAN,
+        secondary_color: Rgb888::YELLOW,
+        icon_color: Rgb888::WHITE,
+        text_color: Rgb888::WHITE,
+        default_widget_height: 16,
+        border_width: 0,
+        highlight_border_width: 1,
+        corner_radius: 1,
+        default_font: mono_font::iso_8859_10::FONT_9X15,
+        spacing: Spacing {
+            item_spacing: Size::new(8, 4),
+            button_padding: Size::new(5, 5),
+            default_padding: Size::new(1, 1),
+            window_border_padding: Size::new(3, 3),
+        },
+    }
+}
+
+/// Draws one frame over the whole of `display`
+pub fn draw<D>(display: &mut D)
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    // create UI (needs to be done each frame)
+    let mut ui = Ui::new_fullscreen(display, medsize_rgb888_style());
+
+    // clear UI background (for non-incremental redrawing framebuffered applications)
+    ui.clear_background().ok();
+
+    ui.add(Label::new("Hello world RUST and Embassy").with_font(ascii::FONT_10X20));
+}
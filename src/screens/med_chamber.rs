@@ -0,0 +1,142 @@
+//! RF control panel of the medical chamber: RF on/off and three power
+//! levels, each toggling one of the Arduino pins D0..D3.
+
+use core::fmt::Debug;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::image::Image;
+use embedded_graphics::pixelcolor::{Gray8, Rgb555, Rgb888, RgbColor};
+use embedded_graphics::primitives::Rectangle;
+use embedded_graphics::Drawable;
+use tinytga::Tga;
+
+use crate::framebuffer::{LCD_HEIGHT, LCD_WIDTH};
+use crate::layout::{Align, Insets, Layout, Length};
+use crate::outputs::{OutputId, OutputLevel};
+use crate::widgets::{Theme, ToggleButton, WidgetId, WidgetTree};
+
+// Should be on SD-card not in heap!
+// Should be less than 1 KiB
+const BACKGROUND: &[u8] = include_bytes!("../image/gui_med_com.tga");
+// const BACKGROUND: &[u8] = include_bytes!("../image/tusur_logo_horizontal_main_color_rgb.tga");
+
+pub struct MedChamber {
+    ui: WidgetTree,
+    /// Button of each output, D0..D3 are the outputs 0..3
+    buttons: [WidgetId; 4],
+}
+
+impl Default for MedChamber {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MedChamber {
+    pub fn new() -> Self {
+        let mut ui = WidgetTree::new(Theme::default());
+        // The buttons show the pin level reported back by the hardware
+        let mut button = |button: ToggleButton| ui.add(None, Rectangle::zero(), button.external());
+
+        let buttons = [
+            button(ToggleButton::new("RF").with_state_text()), // D0
+            button(ToggleButton::new("43 dBm")),               // D1
+            button(ToggleButton::new("45 dBm")),               // D2
+            button(ToggleButton::new("47 dBm")),               // D3
+        ];
+
+        let mut screen = Self { ui, buttons };
+        screen.layout(Rectangle::new(
+            Point::zero(),
+            Size::new(LCD_WIDTH as u32, LCD_HEIGHT as u32),
+        ));
+        screen
+    }
+
+    /// Places the buttons in `area`: RF on top, the power levels in a row
+    /// below. The insets keep them on the slots of the background image.
+    //
+    // Layout for the EMC PA, 2 x 2 buttons:
+    // 0.5 dB (D0) at 86, 98; 1 dB (D1) at 286, 98;
+    // 2 dB (D2) at 86, 168; 4 dB (D3) at 286, 168
+    pub fn layout(&mut self, area: Rectangle) {
+        let [rf, dbm_43, dbm_45, dbm_47] = self.buttons;
+        let button = |id| Layout::leaf(id).size(120, 50);
+
+        Layout::column([
+            button(rf),
+            Layout::row([button(dbm_43), button(dbm_45), button(dbm_47)])
+                .height(Length::Px(50))
+                .gap(16),
+        ])
+        .padding(Insets {
+            top: 104,
+            right: 48,
+            bottom: 16,
+            left: 40,
+        })
+        .gap(52)
+        .align(Align::Center)
+        .apply_to(&mut self.ui, area);
+    }
+
+    /// Widgets of D0..D3
+    pub fn buttons(&self) -> &[WidgetId; 4] {
+        &self.buttons
+    }
+
+    pub fn ui(&self) -> &WidgetTree {
+        &self.ui
+    }
+
+    /// Draws the static background image. On the board it lives on its own
+    /// layer and is drawn once.
+    pub fn draw_background<D>(target: &mut D)
+    where
+        D: DrawTarget,
+        D::Color: From<Gray8> + From<Rgb555> + From<Rgb888>,
+        D::Error: Debug,
+    {
+        let tga: Tga<D::Color> = Tga::from_slice(BACKGROUND).unwrap();
+
+        target.clear(Rgb888::WHITE.into()).unwrap();
+        Image::new(&tga, Point::new(0, 0)).draw(target).unwrap();
+    }
+
+    /// Returns the output of the button at `point`, if any. The buttons do
+    /// not overlap, so there is at most one.
+    ///
+    /// The button itself only changes once the hardware reports the new
+    /// output level through [`Self::apply`].
+    pub fn touch(&mut self, point: Point) -> Option<OutputId> {
+        if point.x <= 0 || point.y <= 0 {
+            return None;
+        }
+
+        let event = self.ui.tap(point)?;
+        let index = self.buttons.iter().position(|&id| id == event.id)?;
+        Some(OutputId(index as u8))
+    }
+
+    /// Shows the actual output level reported by the hardware
+    pub fn apply(&mut self, level: OutputLevel) {
+        if let Some(&button) = self.buttons.get(level.output.index()) {
+            self.ui.set_on(button, level.on);
+        }
+    }
+
+    /// Redraws every button on the next [`Self::draw`]
+    pub fn invalidate(&mut self) {
+        self.ui.invalidate();
+    }
+
+    /// Draws the buttons whose state changed since the last call
+    pub fn draw<D>(&mut self, target: &mut D)
+    where
+        D: DrawTarget<Color = Rgb888>,
+        D::Error: Debug,
+    {
+        self.ui.draw(target).unwrap();
+    }
+}
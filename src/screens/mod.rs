@@ -0,0 +1,69 @@
+//! Screens shown on the LCD.
+//!
+//! They only draw into an embedded-graphics `DrawTarget` and turn touch
+//! points into events, so the same code runs on the board and in the
+//! simulator. A [`Navigator`] switches between them.
+
+pub mod about;
+pub mod control;
+pub mod description;
+pub mod diagnostics;
+pub mod dialog;
+pub mod kolibri_demo;
+pub mod med_chamber;
+pub mod navigator;
+pub mod network;
+pub mod settings;
+
+use alloc::boxed::Box;
+use alloc::vec;
+
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+
+use crate::framebuffer::{LCD_HEIGHT, LCD_WIDTH};
+
+pub use about::AboutScreen;
+pub use control::ControlScreen;
+pub use description::ScreenDescription;
+pub use diagnostics::DiagnosticsScreen;
+pub use dialog::ConfirmDialog;
+pub use med_chamber::MedChamber;
+pub use navigator::{Action, Background, Navigator, Screen, Settings};
+pub use network::NetworkScreen;
+pub use settings::SettingsScreen;
+
+/// Tab bar of [`operator_panel`], left of the logo in the background image
+pub const TAB_BAR: Rectangle = Rectangle::new(Point::zero(), Size::new(380, 22));
+
+/// Below the tab bar, for the pages without a description
+pub const CONTENT: Rectangle = Rectangle::new(
+    Point::new(0, TAB_BAR.size.height as i32),
+    Size::new(LCD_WIDTH as u32, LCD_HEIGHT as u32 - TAB_BAR.size.height),
+);
+
+/// Background images built into the firmware, by the name used in screen
+/// descriptions
+const BACKGROUNDS: &[(&str, &[u8])] =
+    &[("gui_med_com", include_bytes!("../image/gui_med_com.tga"))];
+
+/// TGA data of the built-in background `name`
+pub fn background_image(name: &str) -> Option<&'static [u8]> {
+    BACKGROUNDS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, data)| *data)
+}
+
+/// The firmware GUI: the screen of `description`, settings and
+/// diagnostics, each on its own tab
+pub fn operator_panel(description: &ScreenDescription) -> Navigator {
+    Navigator::with_tabs(
+        vec![
+            Box::new(ControlScreen::new(description)) as Box<dyn Screen>,
+            Box::new(SettingsScreen::new(&Settings::default(), CONTENT)),
+            Box::new(DiagnosticsScreen::new(CONTENT)),
+        ],
+        TAB_BAR,
+    )
+}
@@ -0,0 +1,428 @@
+//! Several screens on one panel: tabs, a stack of pages and modal dialogs.
+//!
+//! Every tab has a root [`Screen`]. Pages are pushed on top of it and
+//! popped again; a modal screen, e.g. a [`ConfirmDialog`], is drawn over
+//! the screen below and takes all input until it closes.
+//!
+//! The overlay is double buffered, so a new screen is drawn completely into
+//! the back buffer and appears at once with the next flip. The background
+//! layer has a single buffer: while it is redrawn, the overlay covers it,
+//! see [`Navigator::draw_cover`].
+//!
+//! ```ignore
+//! let mut nav = Navigator::with_tabs(vec![control, settings], TAB_BAR);
+//! if let Some(output) = nav.touch(point) {
+//!     OUTPUT_COMMANDS.send(Command::Toggle(output)).await;
+//! }
+//! nav.draw(&mut swapchain.back_buffer());
+//! ```
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::{Rgb565, Rgb888, RgbColor};
+use embedded_graphics::primitives::Rectangle;
+
+use crate::framebuffer::{DisplayBuffer, Overlay};
+use crate::layout::Layout;
+use crate::netconfig::{NetConfig, NetState};
+use crate::outputs::{OutputId, OutputLevel};
+use crate::rotation::Rotated;
+use crate::widgets::{Event, Theme, ToggleButton, WidgetId, WidgetTree};
+
+use super::dialog::ConfirmDialog;
+
+/// Shared by all screens of a [`Navigator`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Settings {
+    /// Ask before [`Action::Confirm`] outputs are driven
+    pub confirm_outputs: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            confirm_outputs: true,
+        }
+    }
+}
+
+/// The background layer, rotated like the overlay
+pub type Background<'a, 'b> = Rotated<'a, DisplayBuffer<'b, Rgb565>>;
+
+/// What a screen wants done after one of its widgets sent an event
+pub enum Action {
+    None,
+    /// Drive the output
+    Output(OutputId),
+    /// Drive the output after the operator confirmed `message`
+    Confirm {
+        message: String,
+        output: OutputId,
+    },
+    /// Show another screen on top
+    Push(Box<dyn Screen>),
+    /// Close this screen, then drive the output, if any
+    Close(Option<OutputId>),
+    /// Run the touch calibration, see [`Navigator::take_calibration_request`]
+    Calibrate,
+    /// Store and use new network settings, see
+    /// [`Navigator::take_network_request`]
+    Network(NetConfig),
+}
+
+/// One page of the GUI, built from widgets
+pub trait Screen {
+    /// Shown in the tab bar
+    fn title(&self) -> &str;
+
+    fn ui(&self) -> &WidgetTree;
+
+    fn ui_mut(&mut self) -> &mut WidgetTree;
+
+    /// Handles an event of one of the screen's widgets
+    fn event(&mut self, _event: Event, _settings: &mut Settings) -> Action {
+        Action::None
+    }
+
+    /// Output level reported by the hardware, every open screen gets it
+    fn apply(&mut self, _level: OutputLevel) {}
+
+    /// What the network is doing, every open screen gets it and a new
+    /// screen when it is pushed
+    fn apply_network(&mut self, _network: &NetState) {}
+
+    /// Drawn over the screen below, which stays visible, and takes all
+    /// input including the tab bar's
+    fn is_modal(&self) -> bool {
+        false
+    }
+
+    /// Draws the static background layer, white by default
+    fn draw_background(&self, target: &mut Background<'_, '_>) {
+        target.clear(Rgb565::WHITE).unwrap();
+    }
+}
+
+struct TabBar {
+    ui: WidgetTree,
+    tabs: Vec<WidgetId>,
+}
+
+impl TabBar {
+    fn new(titles: impl Iterator<Item = String>, area: Rectangle) -> Self {
+        // The selected tab is highlighted, the others look like buttons
+        let theme = Theme::default();
+        let mut ui = WidgetTree::new(Theme {
+            on_color: theme.accent_color,
+            off_color: theme.button_color,
+            ..theme
+        });
+
+        let tabs: Vec<WidgetId> = titles
+            .map(|title| ui.add(None, Rectangle::zero(), ToggleButton::new(title).external()))
+            .collect();
+        Layout::row(tabs.iter().map(|&id| Layout::leaf(id))).apply_to(&mut ui, area);
+
+        Self { ui, tabs }
+    }
+
+    fn select(&mut self, index: usize) {
+        for (i, &id) in self.tabs.iter().enumerate() {
+            self.ui.set_on(id, i == index);
+        }
+    }
+}
+
+pub struct Navigator {
+    tabs: Vec<Box<dyn Screen>>,
+    tab: usize,
+    tab_bar: Option<TabBar>,
+    /// Pushed on top of the current tab
+    stack: Vec<Box<dyn Screen>>,
+    settings: Settings,
+    /// Clear the overlay and draw every visible screen
+    redraw: bool,
+    background_changed: bool,
+    calibration_requested: bool,
+    network_request: Option<NetConfig>,
+    /// Last [`Self::apply_network`], for screens pushed later
+    network: NetState,
+}
+
+impl Navigator {
+    /// A single screen without a tab bar
+    pub fn new(root: Box<dyn Screen>) -> Self {
+        Self::build(alloc::vec![root], None)
+    }
+
+    /// One tab per screen, the tab bar is laid out in `bar`. The first
+    /// tab is selected.
+    pub fn with_tabs(tabs: Vec<Box<dyn Screen>>, bar: Rectangle) -> Self {
+        assert!(!tabs.is_empty(), "a navigator needs a screen");
+
+        let mut tab_bar = TabBar::new(tabs.iter().map(|t| String::from(t.title())), bar);
+        tab_bar.select(0);
+        Self::build(tabs, Some(tab_bar))
+    }
+
+    fn build(tabs: Vec<Box<dyn Screen>>, tab_bar: Option<TabBar>) -> Self {
+        Self {
+            tabs,
+            tab: 0,
+            tab_bar,
+            stack: Vec::new(),
+            settings: Settings::default(),
+            redraw: true,
+            background_changed: true,
+            calibration_requested: false,
+            network_request: None,
+            network: NetState::default(),
+        }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    /// Index of the selected tab
+    pub fn tab(&self) -> usize {
+        self.tab
+    }
+
+    /// Screens pushed on top of the tab
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// The screen that gets the input
+    pub fn top(&self) -> &dyn Screen {
+        self.stack.last().unwrap_or(&self.tabs[self.tab]).as_ref()
+    }
+
+    /// Switches to tab `index`, closing everything pushed on the current
+    /// one
+    pub fn select_tab(&mut self, index: usize) {
+        if index >= self.tabs.len() || (index == self.tab && self.stack.is_empty()) {
+            return;
+        }
+
+        self.tab = index;
+        self.stack.clear();
+        if let Some(bar) = &mut self.tab_bar {
+            bar.select(index);
+        }
+        self.redraw = true;
+        self.background_changed = true;
+    }
+
+    pub fn push(&mut self, mut screen: Box<dyn Screen>) {
+        screen.apply_network(&self.network);
+        // A modal screen is just drawn on top, it is new and so redrawn
+        if !screen.is_modal() {
+            self.redraw = true;
+            self.background_changed = true;
+        }
+        self.stack.push(screen);
+    }
+
+    /// Closes the top screen, returns `false` if it is the root of a tab
+    pub fn pop(&mut self) -> bool {
+        let Some(screen) = self.stack.pop() else {
+            return false;
+        };
+        // The screen below has to be repaired where the dialog was
+        self.redraw = true;
+        if !screen.is_modal() {
+            self.background_changed = true;
+        }
+        true
+    }
+
+    /// Routes a touch to the top screen or the tab bar, returns the output
+    /// to toggle, if any
+    pub fn touch(&mut self, point: Point) -> Option<OutputId> {
+        if point.x <= 0 || point.y <= 0 {
+            return None;
+        }
+
+        if !self.top().is_modal() {
+            if let Some(bar) = &mut self.tab_bar {
+                if let Some(event) = bar.ui.tap(point) {
+                    let index = bar.tabs.iter().position(|&id| id == event.id)?;
+                    self.select_tab(index);
+                    return None;
+                }
+            }
+        }
+
+        let screen = match self.stack.last_mut() {
+            Some(screen) => screen,
+            None => &mut self.tabs[self.tab],
+        };
+        let event = screen.ui_mut().tap(point)?;
+        let action = screen.event(event, &mut self.settings);
+        self.perform(action)
+    }
+
+    fn perform(&mut self, action: Action) -> Option<OutputId> {
+        match action {
+            Action::None => None,
+            Action::Output(output) => Some(output),
+            Action::Confirm { message, output } => {
+                if !self.settings.confirm_outputs {
+                    return Some(output);
+                }
+                self.push(Box::new(ConfirmDialog::new(message, output)));
+                None
+            }
+            Action::Push(screen) => {
+                self.push(screen);
+                None
+            }
+            Action::Close(output) => {
+                self.pop();
+                output
+            }
+            Action::Calibrate => {
+                // The calibration covers the whole overlay
+                self.calibration_requested = true;
+                self.redraw = true;
+                None
+            }
+            Action::Network(config) => {
+                self.network_request = Some(config);
+                None
+            }
+        }
+    }
+
+    /// A screen asked for the touch calibration since the last call. The
+    /// overlay is redrawn completely afterwards.
+    pub fn take_calibration_request(&mut self) -> bool {
+        core::mem::take(&mut self.calibration_requested)
+    }
+
+    /// New network settings a screen saved since the last call
+    pub fn take_network_request(&mut self) -> Option<NetConfig> {
+        self.network_request.take()
+    }
+
+    /// Passes the state of the network to every screen
+    pub fn apply_network(&mut self, network: &NetState) {
+        self.network = *network;
+        for screen in self.tabs.iter_mut().chain(&mut self.stack) {
+            screen.apply_network(network);
+        }
+    }
+
+    /// Passes the output level reported by the hardware to every screen
+    pub fn apply(&mut self, level: OutputLevel) {
+        for screen in self.tabs.iter_mut().chain(&mut self.stack) {
+            screen.apply(level);
+        }
+    }
+
+    /// The background layer needs [`Self::draw_background`]
+    pub fn background_changed(&self) -> bool {
+        self.background_changed
+    }
+
+    /// Draws the background of the topmost screen that is not modal
+    pub fn draw_background(&mut self, target: &mut Background<'_, '_>) {
+        let base = self.base();
+        self.screen(base).draw_background(target);
+        self.background_changed = false;
+    }
+
+    /// Fills the overlay with the theme background and the visible screens,
+    /// to hide the background layer while it is redrawn. The next
+    /// [`Self::draw`] makes the overlay transparent again.
+    pub fn draw_cover<D>(&mut self, target: &mut D)
+    where
+        D: Overlay<Color = Rgb888>,
+        D::Error: Debug,
+    {
+        let background = self.top().ui().theme().background;
+        target.clear(background).unwrap();
+        self.invalidate();
+        self.draw_screens(target);
+        self.redraw = true;
+    }
+
+    /// Draws what changed since the last call, everything after the
+    /// screen changed
+    pub fn draw<D>(&mut self, target: &mut D)
+    where
+        D: Overlay<Color = Rgb888>,
+        D::Error: Debug,
+    {
+        if self.redraw {
+            target.clear_transparent();
+            self.invalidate();
+            self.redraw = false;
+        }
+        self.draw_screens(target);
+    }
+
+    fn draw_screens<D>(&mut self, target: &mut D)
+    where
+        D: DrawTarget<Color = Rgb888>,
+        D::Error: Debug,
+    {
+        let base = self.base();
+        self.screen_mut(base).ui_mut().draw(target).unwrap();
+        if let Some(bar) = &mut self.tab_bar {
+            bar.ui.draw(target).unwrap();
+        }
+
+        // Dialogs are small, redrawing them is cheaper than finding out
+        // whether the screen below painted over them
+        for i in base + 1..=self.stack.len() {
+            let ui = self.screen_mut(i).ui_mut();
+            ui.invalidate();
+            ui.draw(target).unwrap();
+        }
+    }
+
+    fn invalidate(&mut self) {
+        for i in self.base()..=self.stack.len() {
+            self.screen_mut(i).ui_mut().invalidate();
+        }
+        if let Some(bar) = &mut self.tab_bar {
+            bar.ui.invalidate();
+        }
+    }
+
+    /// Index of the topmost screen that is not modal, 0 is the tab
+    fn base(&self) -> usize {
+        self.stack
+            .iter()
+            .rposition(|s| !s.is_modal())
+            .map_or(0, |i| i + 1)
+    }
+
+    /// 0 is the tab, then the stack
+    fn screen(&self, index: usize) -> &dyn Screen {
+        match index {
+            0 => self.tabs[self.tab].as_ref(),
+            i => self.stack[i - 1].as_ref(),
+        }
+    }
+
+    fn screen_mut(&mut self, index: usize) -> &mut dyn Screen {
+        match index {
+            0 => self.tabs[self.tab].as_mut(),
+            i => self.stack[i - 1].as_mut(),
+        }
+    }
+}
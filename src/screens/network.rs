@@ -0,0 +1,264 @@
+//! Network settings, pushed from the settings.
+//!
+//! The address, gateway and DNS server are typed on a keypad into the
+//! selected field. "Save" hands the settings to the
+//! [`Navigator`](super::Navigator), which passes them on to be stored and
+//! used, see [`Navigator::take_network_request`](super::Navigator::take_network_request).
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
+
+use embedded_graphics::primitives::Rectangle;
+
+use crate::layout::{Insets, Layout, Length};
+use crate::netconfig::{self, NetConfig, NetSetting, NetState};
+use crate::widgets::{Button, Event, Label, Theme, ToggleButton, WidgetId, WidgetTree};
+
+use super::navigator::{Action, Screen, Settings};
+
+/// Shown before the text of each field
+const FIELDS: [&str; 3] = ["IP", "GW", "DNS"];
+
+/// For the status line if a field is invalid
+const FIELD_NAMES: [&str; 3] = ["address", "gateway", "DNS server"];
+
+/// Longest field text, `255.255.255.255/32`
+const FIELD_LEN: usize = 18;
+
+const KEYS: [char; 12] = ['1', '2', '3', '4', '5', '6', '7', '8', '9', '.', '0', '/'];
+
+pub struct NetworkScreen {
+    ui: WidgetTree,
+    dhcp: WidgetId,
+    /// Address with the prefix length, gateway and DNS server
+    fields: [WidgetId; 3],
+    texts: [String; 3],
+    /// The field the keypad types into
+    selected: Option<usize>,
+    keys: Vec<(WidgetId, char)>,
+    delete: WidgetId,
+    save: WidgetId,
+    back: WidgetId,
+    status: WidgetId,
+    mac: WidgetId,
+    /// The stored settings
+    config: NetConfig,
+    /// Changed and not saved yet, the fields keep the changes
+    edited: bool,
+}
+
+impl NetworkScreen {
+    /// Shows the default settings until the navigator applies the current
+    /// ones
+    pub fn new(area: Rectangle) -> Self {
+        // The selected field is highlighted, the others look like buttons
+        let theme = Theme::default();
+        let theme = Theme {
+            on_color: theme.accent_color,
+            off_color: theme.button_color,
+            ..theme
+        };
+        let mut ui = WidgetTree::new(theme);
+
+        let dhcp = ui.add(
+            None,
+            Rectangle::zero(),
+            ToggleButton::new("DHCP").with_state_text(),
+        );
+        // Selected by the screen, one at a time
+        let fields =
+            FIELDS.map(|_| ui.add(None, Rectangle::zero(), ToggleButton::new("").external()));
+        let save = ui.add(None, Rectangle::zero(), Button::new("Save"));
+        let back = ui.add(None, Rectangle::zero(), Button::new("Back"));
+        let keys: Vec<(WidgetId, char)> = KEYS
+            .iter()
+            .map(|&key| {
+                let id = ui.add(None, Rectangle::zero(), Button::new(String::from(key)));
+                (id, key)
+            })
+            .collect();
+        let delete = ui.add(None, Rectangle::zero(), Button::new("Delete"));
+        // Cleared on every change, the text gets shorter
+        let status = ui.add(
+            None,
+            Rectangle::zero(),
+            Label::new("").with_background(theme.background),
+        );
+        let mac = ui.add(None, Rectangle::zero(), Label::new(""));
+
+        let settings = Layout::column(
+            [Layout::leaf(dhcp)]
+                .into_iter()
+                .chain(fields.map(Layout::leaf))
+                .chain([Layout::row([Layout::leaf(save), Layout::leaf(back)]).gap(6)]),
+        )
+        .gap(6)
+        .width(Length::Px(270));
+        let keypad = Layout::column([
+            Layout::grid(3, 4, keys.iter().map(|&(id, _)| Layout::leaf(id)))
+                .gap(6)
+                .height(Length::Fill(4)),
+            Layout::leaf(delete),
+        ])
+        .gap(6);
+        Layout::column([
+            Layout::row([settings, keypad]).gap(10),
+            Layout::leaf(status).height(Length::Px(24)),
+            Layout::leaf(mac).height(Length::Px(24)),
+        ])
+        .padding(Insets::all(10))
+        .gap(6)
+        .apply_to(&mut ui, area);
+
+        let mut screen = Self {
+            ui,
+            dhcp,
+            fields,
+            texts: Default::default(),
+            selected: None,
+            keys,
+            delete,
+            save,
+            back,
+            status,
+            mac,
+            config: NetConfig::DEFAULT,
+            edited: false,
+        };
+        screen.show_config();
+        screen
+    }
+
+    fn show_config(&mut self) {
+        let config = self.config;
+        let host = |host: Option<Ipv4Addr>| host.map_or(String::new(), |a| format!("{a}"));
+        self.ui.set_on(self.dhcp, config.dhcp);
+        self.texts = [
+            format!("{}/{}", config.address, config.prefix_len),
+            host(config.gateway),
+            host(config.dns),
+        ];
+        for i in 0..FIELDS.len() {
+            self.show_field(i);
+        }
+    }
+
+    fn show_field(&mut self, i: usize) {
+        let text = format!("{:<3} {}", FIELDS[i], self.texts[i]);
+        self.ui.set_text(self.fields[i], text);
+    }
+
+    fn select(&mut self, selected: Option<usize>) {
+        self.selected = selected;
+        for (i, &field) in self.fields.iter().enumerate() {
+            self.ui.set_on(field, selected == Some(i));
+        }
+    }
+
+    /// Changes the selected field with `edit`
+    fn edit(&mut self, edit: impl FnOnce(&mut String)) {
+        let Some(i) = self.selected else {
+            return;
+        };
+        edit(&mut self.texts[i]);
+        self.edited = true;
+        self.show_field(i);
+    }
+
+    /// The settings in the fields, the name of the first invalid field
+    fn entered(&self) -> Result<NetConfig, &'static str> {
+        let dhcp = self.ui.is_on(self.dhcp).unwrap_or(self.config.dhcp);
+        let (address, prefix_len) =
+            netconfig::parse_address(&self.texts[0]).ok_or(FIELD_NAMES[0])?;
+        // Empty for none
+        let host = |i: usize| match self.texts[i].as_str() {
+            "" => Ok(None),
+            text => match netconfig::parse_address(text) {
+                Some((address, None)) => Ok(Some(address)),
+                _ => Err(FIELD_NAMES[i]),
+            },
+        };
+        Ok(self
+            .config
+            .with(NetSetting::Dhcp(dhcp))
+            .with(NetSetting::Address(address, prefix_len))
+            .with(NetSetting::Gateway(host(1)?))
+            .with(NetSetting::Dns(host(2)?)))
+    }
+}
+
+fn status_text(network: &NetState) -> String {
+    match network.address {
+        _ if !network.link_up => String::from("Link down"),
+        None if network.config.dhcp => String::from("Link up, waiting for DHCP"),
+        None => String::from("Link up, no address"),
+        Some((address, prefix_len)) => {
+            let source = match (network.config.dhcp, network.fallback) {
+                (true, false) => " (DHCP)",
+                (true, true) => " (no DHCP lease)",
+                (false, _) => "",
+            };
+            format!("Link up, {address}/{prefix_len}{source}")
+        }
+    }
+}
+
+impl Screen for NetworkScreen {
+    fn title(&self) -> &str {
+        "Network"
+    }
+
+    fn ui(&self) -> &WidgetTree {
+        &self.ui
+    }
+
+    fn ui_mut(&mut self) -> &mut WidgetTree {
+        &mut self.ui
+    }
+
+    fn event(&mut self, event: Event, _settings: &mut Settings) -> Action {
+        let id = event.id;
+        if id == self.dhcp {
+            self.edited = true;
+        } else if let Some(i) = self.fields.iter().position(|&field| field == id) {
+            self.select(Some(i));
+        } else if let Some(&(_, key)) = self.keys.iter().find(|&&(button, _)| button == id) {
+            self.edit(|text| {
+                if text.len() < FIELD_LEN {
+                    text.push(key);
+                }
+            });
+        } else if id == self.delete {
+            self.edit(|text| {
+                text.pop();
+            });
+        } else if id == self.save {
+            match self.entered() {
+                Ok(config) => {
+                    self.select(None);
+                    self.edited = false;
+                    self.ui.set_text(self.status, "Saved");
+                    return Action::Network(config);
+                }
+                Err(field) => self.ui.set_text(self.status, format!("Invalid {field}")),
+            }
+        } else if id == self.back {
+            return Action::Close(None);
+        }
+        Action::None
+    }
+
+    fn apply_network(&mut self, network: &NetState) {
+        self.ui.set_text(self.status, status_text(network));
+        let [a, b, c, d, e, f] = network.mac;
+        let mac = format!("MAC {a:02X}:{b:02X}:{c:02X}:{d:02X}:{e:02X}:{f:02X}");
+        self.ui.set_text(self.mac, mac);
+
+        self.config = network.config;
+        if !self.edited {
+            self.show_config();
+        }
+    }
+}
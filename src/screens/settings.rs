@@ -0,0 +1,85 @@
+//! Operator settings, shared through the [`Navigator`](super::Navigator).
+
+use embedded_graphics::primitives::Rectangle;
+
+use crate::layout::{Align, Insets, Layout};
+use crate::widgets::{Button, Event, EventKind, Theme, ToggleButton, WidgetId, WidgetTree};
+
+use super::about::AboutScreen;
+use super::navigator::{Action, Screen, Settings};
+use super::network::NetworkScreen;
+
+pub struct SettingsScreen {
+    ui: WidgetTree,
+    confirm: WidgetId,
+    about: WidgetId,
+    network: WidgetId,
+    calibrate: WidgetId,
+    area: Rectangle,
+}
+
+impl SettingsScreen {
+    /// Shows `settings` in `area`
+    pub fn new(settings: &Settings, area: Rectangle) -> Self {
+        let mut ui = WidgetTree::new(Theme::default());
+
+        let mut confirm = ToggleButton::new("Confirm outputs").with_state_text();
+        confirm.on = settings.confirm_outputs;
+        let confirm = ui.add(None, Rectangle::zero(), confirm);
+        let about = ui.add(None, Rectangle::zero(), Button::new("About"));
+        let network = ui.add(None, Rectangle::zero(), Button::new("Network"));
+        let calibrate = ui.add(None, Rectangle::zero(), Button::new("Calibrate touch"));
+
+        Layout::column([
+            Layout::leaf(confirm).size(260, 50),
+            Layout::row([Layout::leaf(about), Layout::leaf(network)])
+                .gap(20)
+                .size(260, 50),
+            Layout::leaf(calibrate).size(260, 50),
+        ])
+        .padding(Insets::all(20))
+        .gap(20)
+        .align(Align::Center)
+        .apply_to(&mut ui, area);
+
+        Self {
+            ui,
+            confirm,
+            about,
+            network,
+            calibrate,
+            area,
+        }
+    }
+}
+
+impl Screen for SettingsScreen {
+    fn title(&self) -> &str {
+        "Settings"
+    }
+
+    fn ui(&self) -> &WidgetTree {
+        &self.ui
+    }
+
+    fn ui_mut(&mut self) -> &mut WidgetTree {
+        &mut self.ui
+    }
+
+    fn event(&mut self, event: Event, settings: &mut Settings) -> Action {
+        match event.kind {
+            EventKind::Toggled(on) if event.id == self.confirm => {
+                settings.confirm_outputs = on;
+                Action::None
+            }
+            EventKind::Clicked if event.id == self.about => {
+                Action::Push(alloc::boxed::Box::new(AboutScreen::new(self.area)))
+            }
+            EventKind::Clicked if event.id == self.network => {
+                Action::Push(alloc::boxed::Box::new(NetworkScreen::new(self.area)))
+            }
+            EventKind::Clicked if event.id == self.calibrate => Action::Calibrate,
+            _ => Action::None,
+        }
+    }
+}
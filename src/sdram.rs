@@ -1,52 +1,65 @@
-use embassy_stm32::peripherals::*;
 use embassy_stm32::fmc::Fmc;
+use embassy_stm32::peripherals::*;
+use embassy_stm32::Peri;
+use embedded_alloc::TlsfHeap as Heap;
 use stm32_fmc::Sdram;
 
+// SDRAM driver
+#[global_allocator]
+static HEAP: Heap = Heap::empty();
+
+/// MT48LC4M32B2 is 128 Mbit, but only 16 data lines are routed on the
+/// DISCO board, so 64 Mbit = 8 MByte are usable.
+pub const SDRAM_SIZE: usize = 8 * 1024 * 1024;
+
 pub struct SdramPins {
-    pub a0: PF0,
-    pub a1: PF1,
-    pub a2: PF2,
-    pub a3: PF3,
-    pub a4: PF4,
-    pub a5: PF5,
-    pub a6: PF12,
-    pub a7: PF13,
-    pub a8: PF14,
-    pub a9: PF15,
-    pub a10: PG0,
-    pub a11: PG1,
-    pub ba0: PG4,
-    pub ba1: PG5,
-
-    pub d0: PD14,
-    pub d1: PD15,
-    pub d2: PD0,
-    pub d3: PD1,
-    pub d4: PE7,
-    pub d5: PE8,
-    pub d6: PE9,
-    pub d7: PE10,
-    pub d8: PE11,
-    pub d9: PE12,
-    pub d10: PE13,
-    pub d11: PE14,
-    pub d12: PE15,
-    pub d13: PD8,
-    pub d14: PD9,
-    pub d15: PD10,
-
-    pub nbl0: PE0,
-    pub nbl1: PE1,
-
-    pub sdclk: PG8,
-    pub sdnwe: PG15,
-    pub sdcke0: PH3,
-    pub sdnc: PC3,
-    pub sdne0: PH5,
-    pub sdnras: PF11,
+    pub a0: Peri<'static, PF0>,
+    pub a1: Peri<'static, PF1>,
+    pub a2: Peri<'static, PF2>,
+    pub a3: Peri<'static, PF3>,
+    pub a4: Peri<'static, PF4>,
+    pub a5: Peri<'static, PF5>,
+    pub a6: Peri<'static, PF12>,
+    pub a7: Peri<'static, PF13>,
+    pub a8: Peri<'static, PF14>,
+    pub a9: Peri<'static, PF15>,
+    pub a10: Peri<'static, PG0>,
+    pub a11: Peri<'static, PG1>,
+    pub ba0: Peri<'static, PG4>,
+    pub ba1: Peri<'static, PG5>,
+
+    pub d0: Peri<'static, PD14>,
+    pub d1: Peri<'static, PD15>,
+    pub d2: Peri<'static, PD0>,
+    pub d3: Peri<'static, PD1>,
+    pub d4: Peri<'static, PE7>,
+    pub d5: Peri<'static, PE8>,
+    pub d6: Peri<'static, PE9>,
+    pub d7: Peri<'static, PE10>,
+    pub d8: Peri<'static, PE11>,
+    pub d9: Peri<'static, PE12>,
+    pub d10: Peri<'static, PE13>,
+    pub d11: Peri<'static, PE14>,
+    pub d12: Peri<'static, PE15>,
+    pub d13: Peri<'static, PD8>,
+    pub d14: Peri<'static, PD9>,
+    pub d15: Peri<'static, PD10>,
+
+    pub nbl0: Peri<'static, PE0>,
+    pub nbl1: Peri<'static, PE1>,
+
+    pub sdcke0: Peri<'static, PC3>,
+    pub sdclk: Peri<'static, PG8>,
+    pub sdncas: Peri<'static, PG15>,
+    pub sdne0: Peri<'static, PH3>,
+    pub sdnras: Peri<'static, PF11>,
+    pub sdnwe: Peri<'static, PH5>,
 }
 
-mod mt48lc4m32b2_6 {
+// --------------------------------------------------
+// SDRAM chip definition
+// --------------------------------------------------
+pub mod mt48lc4m32b2_6 {
     use stm32_fmc::{SdramChip, SdramConfiguration, SdramTiming};
 
     const BURST_LENGTH_1: u16 = 0x0000;
@@ -90,22 +103,49 @@ mod mt48lc4m32b2_6 {
     }
 }
 
-pub fn init_sdram<'d>(
-    fmc: FMC,
+/// Configures the FMC for the on-board SDRAM. The chip still has to be
+/// initialised with `Sdram::init` before use.
+#[rustfmt::skip]
+pub fn init_sdram(
+    fmc: Peri<'static, FMC>,
     pins: SdramPins,
-) -> Sdram<Fmc<'d, FMC>, mt48lc4m32b2_6::Mt48lc4m32b2> {
+) -> Sdram<Fmc<'static, FMC>, mt48lc4m32b2_6::Mt48lc4m32b2> {
     Fmc::sdram_a12bits_d16bits_4banks_bank1(
         fmc,
+        // A0-A11
         pins.a0, pins.a1, pins.a2, pins.a3, pins.a4, pins.a5,
-        pins.a6, pins.a7, pins.a8, pins.a9,
-        pins.a10, pins.a11,
+        pins.a6, pins.a7, pins.a8, pins.a9, pins.a10, pins.a11,
+        // BA0-BA1
         pins.ba0, pins.ba1,
-        pins.d0, pins.d1, pins.d2, pins.d3,
-        pins.d4, pins.d5, pins.d6, pins.d7,
-        pins.d8, pins.d9, pins.d10, pins.d11,
-        pins.d12, pins.d13, pins.d14, pins.d15,
+        // D0-D15
+        pins.d0, pins.d1, pins.d2, pins.d3, pins.d4, pins.d5, pins.d6, pins.d7,
+        pins.d8, pins.d9, pins.d10, pins.d11, pins.d12, pins.d13, pins.d14, pins.d15,
+        // NBL0 - NBL1
         pins.nbl0, pins.nbl1,
-        pins.sdnc, pins.sdclk, pins.sdnwe, pins.sdcke0, pins.sdnras, pins.sdne0,
+        pins.sdcke0, // SDCKE0
+        pins.sdclk,  // SDCLK
+        pins.sdncas, // SDNCAS
+        pins.sdne0,  // SDNE0 (!CS)
+        pins.sdnras, // SDRAS
+        pins.sdnwe,  // SDNWE
         mt48lc4m32b2_6::Mt48lc4m32b2,
     )
 }
+
+/// Initialises the SDRAM and moves the global heap onto it.
+///
+/// Must be called once, before the first allocation.
+pub fn init_heap(fmc: Peri<'static, FMC>, pins: SdramPins) {
+    let mut sdram = init_sdram(fmc, pins);
+    let mut delay = embassy_time::Delay;
+
+    unsafe {
+        // Initialise controller and SDRAM
+        let ram_ptr: *mut u32 = sdram.init(&mut delay) as *mut _;
+
+        defmt::info!("SDRAM Initialized at {:x}", ram_ptr as usize);
+
+        // Move Heap to SDRAM!
+        HEAP.init(ram_ptr as usize, SDRAM_SIZE)
+    };
+}
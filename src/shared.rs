@@ -0,0 +1,115 @@
+#[cfg(feature = "hw")]
+use core::cell::Cell;
+
+#[cfg(feature = "hw")]
+use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, ThreadModeRawMutex};
+#[cfg(feature = "hw")]
+use embassy_sync::blocking_mutex::Mutex;
+#[cfg(feature = "hw")]
+use embassy_sync::channel::Channel;
+#[cfg(feature = "hw")]
+use embassy_sync::pubsub::PubSubChannel;
+#[cfg(feature = "hw")]
+use embassy_sync::signal::Signal;
+#[cfg(feature = "hw")]
+use embassy_sync::watch::{Receiver, Watch};
+#[cfg(feature = "hw")]
+use embedded_graphics::geometry::Point;
+
+#[cfg(feature = "hw")]
+use crate::calibration::{Calibration, Transform};
+#[cfg(feature = "hw")]
+use crate::gesture::Gesture;
+#[cfg(feature = "hw")]
+use crate::multitouch::TouchEvent;
+use crate::netconfig::NetState;
+#[cfg(feature = "hw")]
+use crate::netconfig::{NetChange, NetConfig};
+#[cfg(feature = "hw")]
+use crate::outputs::Command;
+use crate::outputs::Levels;
+
+/// Maps the touch controller's points to screen coordinates, replaced
+/// after a calibration
+#[cfg(feature = "hw")]
+pub static TOUCH_TRANSFORM: Mutex<ThreadModeRawMutex, Cell<Transform>> =
+    Mutex::new(Cell::new(Transform::DEFAULT));
+
+/// Tapped points, for the GUI
+#[cfg(feature = "hw")]
+pub static TOUCH_POINTS: Channel<ThreadModeRawMutex, Point, 1> = Channel::new();
+
+/// Down, move and up of every finger. Published without waiting, a slow
+/// subscriber misses the oldest events.
+#[cfg(feature = "hw")]
+pub static TOUCH_EVENTS: PubSubChannel<ThreadModeRawMutex, TouchEvent, 16, 2, 1> =
+    PubSubChannel::new();
+
+/// Recognized gestures, published like [`TOUCH_EVENTS`]
+#[cfg(feature = "hw")]
+pub static GESTURES: PubSubChannel<ThreadModeRawMutex, Gesture, 4, 2, 1> = PubSubChannel::new();
+
+/// Commands for the outputs task, from the GUI and every other interface.
+/// The outputs task runs in an interrupt, hence the critical section.
+#[cfg(feature = "hw")]
+pub static OUTPUT_COMMANDS: Channel<CriticalSectionRawMutex, Command, 32> = Channel::new();
+
+/// New network settings for the network task, which applies them to its
+/// own, stores and uses the result
+#[cfg(feature = "hw")]
+pub static NET_SETTINGS: Channel<ThreadModeRawMutex, NetChange, 1> = Channel::new();
+
+/// A new touch calibration for the storage task. Only the latest is kept,
+/// like for [`STORE_NETWORK`].
+#[cfg(feature = "hw")]
+pub static STORE_CALIBRATION: Signal<ThreadModeRawMutex, Calibration> = Signal::new();
+
+/// New network settings for the storage task
+#[cfg(feature = "hw")]
+pub static STORE_NETWORK: Signal<ThreadModeRawMutex, NetConfig> = Signal::new();
+
+/// Whether the outputs task is timing something, a pulse or the delays of
+/// an interlock plan. The storage task keeps off the flash meanwhile.
+#[cfg(feature = "hw")]
+pub static OUTPUTS_BUSY: Watch<CriticalSectionRawMutex, bool, 1> = Watch::new();
+
+/// What the tasks share about the application. The hardware side writes
+/// it with [`update_state`], the GUI observes it through [`STATE`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct AppState {
+    /// Levels of the outputs, read back after every command
+    pub outputs: Levels,
+    pub network: NetState,
+}
+
+/// Receivers [`STATE`] hands out: the GUI, both USB serial ports and the
+/// eight TCP sessions
+pub const STATE_OBSERVERS: usize = 12;
+
+/// The current [`AppState`]. Up to [`STATE_OBSERVERS`] observers take a
+/// receiver with `STATE.receiver()` and wake on every change. Written from
+/// the outputs task's interrupt as well.
+#[cfg(feature = "hw")]
+pub static STATE: Watch<CriticalSectionRawMutex, AppState, STATE_OBSERVERS> = Watch::new();
+
+#[cfg(feature = "hw")]
+pub type StateReceiver = Receiver<'static, CriticalSectionRawMutex, AppState, STATE_OBSERVERS>;
+
+/// The current state, the default before anything was written
+#[cfg(feature = "hw")]
+pub fn state() -> AppState {
+    STATE.try_get().unwrap_or_default()
+}
+
+/// Changes the state with `f`. Observers only wake if something changed.
+#[cfg(feature = "hw")]
+pub fn update_state(f: impl Fn(&mut AppState)) {
+    STATE.sender().send_if_modified(|current| {
+        let mut state = current.unwrap_or_default();
+        f(&mut state);
+        let changed = *current != Some(state);
+        *current = Some(state);
+        changed
+    });
+}
@@ -0,0 +1,598 @@
+//! Host-side stand-in for the LTDC.
+//!
+//! [`SimDisplay`] owns the same two layers the board uses, an RGB565
+//! background and an ARGB8888 overlay, and blends them into a [`Frame`]
+//! that can be saved as PNG or PPM. Touches are fed from a small script,
+//! see [`parse_script`], and frames can be compared against reference
+//! images with [`Frame::compare`].
+
+use core::convert::Infallible;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+use embedded_graphics::geometry::Point;
+use embedded_graphics::pixelcolor::{Rgb565, Rgb888};
+use embedded_hal::digital::{ErrorType, OutputPin, StatefulOutputPin};
+
+use crate::color::DirectColor;
+use crate::framebuffer::{DisplayBuffer, LCD_HEIGHT, LCD_WIDTH};
+use crate::interlock::Interlock;
+use crate::netconfig::NetState;
+use crate::outputs::{Command, OutputBank, OutputId, OutputLevel, OUTPUTS};
+use crate::rotation::{Rotated, Rotation};
+use crate::screens::{MedChamber, Navigator};
+
+const PIXELS: usize = LCD_WIDTH as usize * LCD_HEIGHT as usize;
+
+/// Both LTDC layers in host memory
+pub struct SimDisplay {
+    background: Vec<u16>,
+    overlay: Vec<u32>,
+}
+
+impl Default for SimDisplay {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimDisplay {
+    /// Black background and a fully transparent overlay, like freshly
+    /// zeroed buffers on the board
+    pub fn new() -> Self {
+        Self {
+            background: std::vec![0; PIXELS],
+            overlay: std::vec![0; PIXELS],
+        }
+    }
+
+    /// Layer 0
+    pub fn background(&mut self) -> DisplayBuffer<'_, Rgb565> {
+        DisplayBuffer::new(&mut self.background, LCD_WIDTH as i32, LCD_HEIGHT as i32)
+    }
+
+    /// Layer 1
+    pub fn overlay(&mut self) -> DisplayBuffer<'_, Rgb888> {
+        DisplayBuffer::new(&mut self.overlay, LCD_WIDTH as i32, LCD_HEIGHT as i32)
+    }
+
+    /// What the panel shows: the overlay blended over the background with
+    /// its pixel alpha, as configured in `main`
+    pub fn frame(&self) -> Frame {
+        let pixels = self
+            .background
+            .iter()
+            .zip(&self.overlay)
+            .map(|(&bg, &fg)| over(fg, Rgb565::word_to_argb8888(bg)))
+            .collect();
+
+        Frame {
+            width: LCD_WIDTH as u32,
+            height: LCD_HEIGHT as u32,
+            pixels,
+        }
+    }
+}
+
+/// `fg` over an opaque `bg`, both ARGB8888
+fn over(fg: u32, bg: u32) -> u32 {
+    let a = fg >> 24;
+    let channel = |shift: u32| {
+        let f = (fg >> shift) & 0xFF;
+        let b = (bg >> shift) & 0xFF;
+        ((f * a + b * (255 - a)) / 255) << shift
+    };
+
+    0xFF00_0000 | channel(16) | channel(8) | channel(0)
+}
+
+/// An output pin on the host, it just keeps its level
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SimPin {
+    pub high: bool,
+}
+
+impl ErrorType for SimPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for SimPin {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.high = false;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.high = true;
+        Ok(())
+    }
+}
+
+impl StatefulOutputPin for SimPin {
+    fn is_set_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.high)
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.high)
+    }
+}
+
+/// The outputs of [`OUTPUTS`] on host pins, as `tasks::outputs_task`
+/// drives them
+pub type SimOutputs = OutputBank<SimPin, { OUTPUTS.len() }>;
+
+fn sim_outputs() -> SimOutputs {
+    OutputBank::new([SimPin::default(); OUTPUTS.len()], OUTPUTS)
+}
+
+/// Toggles `output` like a touch on the board does, returns the level the
+/// screens get
+fn toggle(outputs: &mut SimOutputs, output: OutputId) -> Option<OutputLevel> {
+    outputs.execute(Command::Toggle(output), 0).ok()?;
+    Some(OutputLevel {
+        output,
+        on: outputs.get(output)?,
+    })
+}
+
+/// [`MedChamber`] as it runs on the board, with the outputs on
+/// [`SimPin`]s
+pub struct MedChamberSim {
+    pub display: SimDisplay,
+    pub screen: MedChamber,
+    outputs: SimOutputs,
+}
+
+impl Default for MedChamberSim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MedChamberSim {
+    /// Draws the background and the buttons in their initial state
+    pub fn new() -> Self {
+        let mut display = SimDisplay::new();
+        MedChamber::draw_background(&mut display.background());
+
+        let mut screen = MedChamber::new();
+        screen.draw(&mut display.overlay());
+
+        Self {
+            display,
+            screen,
+            outputs: sim_outputs(),
+        }
+    }
+
+    /// Pin level of D0..D3
+    pub fn pins(&self) -> [bool; 4] {
+        self.outputs.pins().map(|pin| pin.high)
+    }
+
+    /// Touches `point` and redraws, returns the new output level if a
+    /// button was hit
+    pub fn touch(&mut self, point: Point) -> Option<OutputLevel> {
+        let output = self.screen.touch(point)?;
+        let level = toggle(&mut self.outputs, output)?;
+        self.screen.apply(level);
+        self.screen.draw(&mut self.display.overlay());
+        Some(level)
+    }
+
+    pub fn frame(&self) -> Frame {
+        self.display.frame()
+    }
+}
+
+/// A [`Navigator`] as it runs on the board, see [`MedChamberSim`]
+pub struct NavigatorSim {
+    pub display: SimDisplay,
+    pub navigator: Navigator,
+    rotation: Rotation,
+    outputs: SimOutputs,
+    interlock: Interlock,
+    network: NetState,
+}
+
+impl NavigatorSim {
+    /// Draws the background and the screen in its initial state
+    pub fn new(navigator: Navigator) -> Self {
+        Self::with_rotation(navigator, Rotation::Deg0)
+    }
+
+    /// Like [`Self::new`] with the picture rotated on the panel. Touch
+    /// points are in the rotated coordinates.
+    pub fn with_rotation(mut navigator: Navigator, rotation: Rotation) -> Self {
+        let mut display = SimDisplay::new();
+        navigator.draw_background(&mut Rotated::new(&mut display.background(), rotation));
+        navigator.draw(&mut Rotated::new(&mut display.overlay(), rotation));
+
+        Self {
+            display,
+            navigator,
+            rotation,
+            outputs: sim_outputs(),
+            interlock: Interlock::default(),
+            network: NetState::default(),
+        }
+    }
+
+    /// Plans every toggle with `interlock`, as `tasks::outputs_task` does.
+    /// The delays between the steps are skipped.
+    pub fn with_interlock(mut self, interlock: Interlock) -> Self {
+        self.interlock = interlock;
+        self
+    }
+
+    /// Pin level of D0..D3
+    pub fn pins(&self) -> [bool; 4] {
+        self.outputs.pins().map(|pin| pin.high)
+    }
+
+    /// Touches `point` and redraws, returns the new level of the touched
+    /// output if it was driven. The interlock may drive others as well.
+    /// Saved network settings are used at once, without a link.
+    pub fn touch(&mut self, point: Point) -> Option<OutputLevel> {
+        let level = self
+            .navigator
+            .touch(point)
+            .and_then(|output| self.toggle(output));
+        if let Some(config) = self.navigator.take_network_request() {
+            self.network.config = config;
+            self.navigator.apply_network(&self.network);
+        }
+        self.redraw();
+        level
+    }
+
+    /// Shows `network` as if the network task reported it, and redraws
+    pub fn set_network(&mut self, network: NetState) {
+        self.network = network;
+        self.navigator.apply_network(&network);
+        self.redraw();
+    }
+
+    fn redraw(&mut self) {
+        // Nothing shows the background while it is redrawn here, no need
+        // for a cover
+        if self.navigator.background_changed() {
+            self.navigator.draw_background(&mut Rotated::new(
+                &mut self.display.background(),
+                self.rotation,
+            ));
+        }
+        self.navigator.draw(&mut Rotated::new(
+            &mut self.display.overlay(),
+            self.rotation,
+        ));
+    }
+
+    /// Executes the plan for toggling `output`, the screens get every
+    /// level that changed
+    fn toggle(&mut self, output: OutputId) -> Option<OutputLevel> {
+        let before = self.outputs.levels();
+        let steps = self.interlock.plan(Command::Toggle(output), before).ok()?;
+        for step in steps {
+            self.outputs.execute(step.command, 0).ok()?;
+        }
+
+        let after = self.outputs.levels();
+        for level in after.changes_since(before) {
+            self.navigator.apply(level);
+        }
+        after
+            .changes_since(before)
+            .find(|level| level.output == output)
+    }
+
+    pub fn frame(&self) -> Frame {
+        self.display.frame()
+    }
+}
+
+/// A rendered image, one ARGB8888 word per pixel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u32>,
+}
+
+impl Frame {
+    /// Copies a single buffer, for screens that are not split into layers
+    pub fn from_buffer<C: DirectColor>(buffer: &DisplayBuffer<'_, C>) -> Self {
+        let len = (buffer.width * buffer.height) as usize;
+        Self {
+            width: buffer.width as u32,
+            height: buffer.height as u32,
+            pixels: buffer.buf[..len]
+                .iter()
+                .map(|&word| C::word_to_argb8888(word))
+                .collect(),
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> u32 {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Compares with a reference image, ignoring alpha. On a mismatch the
+    /// returned [`Mismatch`] carries an image of the differences.
+    pub fn compare(&self, expected: &Frame, tolerance: Tolerance) -> Result<(), Mismatch> {
+        if (self.width, self.height) != (expected.width, expected.height) {
+            return Err(Mismatch::Size {
+                actual: (self.width, self.height),
+                expected: (expected.width, expected.height),
+            });
+        }
+
+        let mut pixels = 0;
+        let mut max_delta = 0;
+        let diff = self
+            .pixels
+            .iter()
+            .zip(&expected.pixels)
+            .map(|(&actual, &expected)| {
+                let delta = channel_delta(actual, expected);
+                max_delta = max_delta.max(delta);
+                if delta > tolerance.channel {
+                    pixels += 1;
+                    DIFF_COLOR
+                } else {
+                    faded(expected)
+                }
+            })
+            .collect();
+
+        if pixels > tolerance.pixels {
+            return Err(Mismatch::Pixels {
+                pixels,
+                max_delta,
+                diff: Frame {
+                    width: self.width,
+                    height: self.height,
+                    pixels: diff,
+                },
+            });
+        }
+        Ok(())
+    }
+
+    /// Binary PPM (P6), alpha is dropped
+    pub fn write_ppm<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for &argb in &self.pixels {
+            let [b, g, r, _] = argb.to_le_bytes();
+            w.write_all(&[r, g, b])?;
+        }
+        w.flush()
+    }
+
+    /// 8-bit RGBA PNG
+    pub fn write_png<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let data: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|&argb| {
+                let [b, g, r, a] = argb.to_le_bytes();
+                [r, g, b, a]
+            })
+            .collect();
+
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&data).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+
+    /// Reads an 8-bit RGB or RGBA PNG, as written by [`Frame::write_png`]
+    pub fn read_png<R: Read>(r: R) -> io::Result<Self> {
+        let mut decoder = png::Decoder::new(r);
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let mut reader = decoder.read_info().map_err(io::Error::other)?;
+
+        let mut data = std::vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).map_err(io::Error::other)?;
+
+        let pixels = match info.color_type {
+            png::ColorType::Rgba => data[..info.buffer_size()]
+                .chunks_exact(4)
+                .map(|p| u32::from_le_bytes([p[2], p[1], p[0], p[3]]))
+                .collect(),
+            png::ColorType::Rgb => data[..info.buffer_size()]
+                .chunks_exact(3)
+                .map(|p| u32::from_le_bytes([p[2], p[1], p[0], 0xFF]))
+                .collect(),
+            other => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    std::format!("unsupported PNG color type {other:?}"),
+                ))
+            }
+        };
+
+        Ok(Self {
+            width: info.width,
+            height: info.height,
+            pixels,
+        })
+    }
+
+    /// Writes a PPM if `path` ends in `.ppm`, a PNG otherwise
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ppm") => self.write_ppm(file),
+            _ => self.write_png(file),
+        }
+    }
+
+    pub fn load_png(path: &Path) -> io::Result<Self> {
+        Self::read_png(BufReader::new(File::open(path)?))
+    }
+}
+
+/// How far a frame may be from its reference and still match
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tolerance {
+    /// Largest difference in any color channel for a pixel to count as
+    /// equal
+    pub channel: u8,
+    /// Pixels allowed to differ by more than `channel`
+    pub pixels: usize,
+}
+
+impl Tolerance {
+    pub const EXACT: Self = Self {
+        channel: 0,
+        pixels: 0,
+    };
+}
+
+#[derive(Clone, Debug)]
+pub enum Mismatch {
+    Size {
+        actual: (u32, u32),
+        expected: (u32, u32),
+    },
+    Pixels {
+        /// Pixels outside the tolerance
+        pixels: usize,
+        /// Largest channel difference seen anywhere
+        max_delta: u8,
+        /// Differing pixels in red over a faded copy of the reference
+        diff: Frame,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::Size { actual, expected } => write!(
+                f,
+                "size is {}x{}, expected {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            Mismatch::Pixels {
+                pixels, max_delta, ..
+            } => write!(
+                f,
+                "{pixels} pixels differ, by up to {max_delta} per channel"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+const DIFF_COLOR: u32 = 0xFFFF_0000;
+
+/// Largest difference of the red, green and blue channels
+fn channel_delta(a: u32, b: u32) -> u8 {
+    [16, 8, 0]
+        .into_iter()
+        .map(|shift| ((a >> shift) & 0xFF).abs_diff((b >> shift) & 0xFF))
+        .max()
+        .unwrap_or(0) as u8
+}
+
+/// Grey, light version of `argb` as the backdrop of a diff image
+fn faded(argb: u32) -> u32 {
+    let [b, g, r, _] = argb.to_le_bytes();
+    let luma = (r as u32 * 3 + g as u32 * 6 + b as u32) / 10;
+    let v = 0xC0 + luma / 4;
+    0xFF00_0000 | v << 16 | v << 8 | v
+}
+
+/// One line of a touch script
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    /// `touch X Y`: a finger at `X`, `Y` in screen coordinates
+    Touch(Point),
+    /// `snapshot NAME`: saves the current frame as `NAME`
+    Snapshot(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptError {
+    /// 1-based
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Parses a touch script, one step per line. Empty lines and everything
+/// after `#` are ignored.
+///
+/// ```text
+/// # RF on, then 45 dBm
+/// touch 236 129
+/// touch 236 231
+/// snapshot rf_45dbm
+/// ```
+pub fn parse_script(script: &str) -> Result<Vec<Step>, ScriptError> {
+    let mut steps = Vec::new();
+
+    for (i, line) in script.lines().enumerate() {
+        let error = |message: &str| ScriptError {
+            line: i + 1,
+            message: message.to_string(),
+        };
+
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            continue;
+        };
+
+        let step = match command {
+            "touch" => {
+                let mut coord = || {
+                    words
+                        .next()
+                        .and_then(|w| w.parse::<i32>().ok())
+                        .ok_or_else(|| error("expected `touch X Y`"))
+                };
+                let x = coord()?;
+                let y = coord()?;
+                Step::Touch(Point::new(x, y))
+            }
+            "snapshot" => {
+                let name = words
+                    .next()
+                    .ok_or_else(|| error("expected `snapshot NAME`"))?;
+                Step::Snapshot(name.to_string())
+            }
+            _ => return Err(error("unknown command, expected `touch` or `snapshot`")),
+        };
+
+        if words.next().is_some() {
+            return Err(error("unexpected trailing words"));
+        }
+        steps.push(step);
+    }
+
+    Ok(steps)
+}
+
+/// Reads and parses a script file
+pub fn load_script(path: &Path) -> io::Result<Vec<Step>> {
+    parse_script(&fs::read_to_string(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
@@ -0,0 +1,157 @@
+//! Settings kept in the last sector of the internal flash.
+//!
+//! The records sit at fixed offsets in a small block. Flash is erased a
+//! whole sector at a time, which takes a second or more and stalls every
+//! read from flash, code included. So the sector is a row of slots, each
+//! write puts the whole block into the next free one and only a full
+//! sector is erased. The last written slot holds the current records.
+//!
+//! [`Storage`] keeps a copy of the block: records are changed in the copy
+//! and [`Storage::flush`] writes it, only if it differs from the flash.
+
+use defmt::warn;
+use embassy_stm32::flash::{Blocking, Error, Flash};
+use embassy_stm32::peripherals::FLASH;
+use embassy_stm32::Peri;
+
+use crate::calibration::{self, Calibration};
+use crate::netconfig::{self, NetConfig};
+
+/// Sector 7, 256 KiB at 0x080C_0000. `memory.x` leaves it out of the
+/// firmware's flash, the linker fails if the firmware would reach it.
+const SECTOR_OFFSET: u32 = 0xC_0000;
+const SECTOR_SIZE: u32 = 256 * 1024;
+
+/// Bytes of the block, one slot
+const BLOCK_LEN: usize = 256;
+
+/// Writes between two erases
+const SLOTS: u32 = SECTOR_SIZE / BLOCK_LEN as u32;
+
+/// Place of one record in the block
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct Record {
+    pub offset: usize,
+    pub len: usize,
+}
+
+pub const CALIBRATION: Record = Record {
+    offset: 0,
+    len: calibration::STORED_LEN,
+};
+
+pub const NETWORK: Record = Record {
+    offset: 32,
+    len: netconfig::STORED_LEN,
+};
+
+pub struct Storage {
+    flash: Flash<'static, Blocking>,
+    /// The block with the changed records
+    block: [u8; BLOCK_LEN],
+    /// The block as it is in flash
+    stored: [u8; BLOCK_LEN],
+    /// The first free slot, [`SLOTS`] if the sector is full
+    next: u32,
+}
+
+impl Storage {
+    /// Reads the last written block. The records stay erased if reading
+    /// fails, the next write erases the sector.
+    pub fn new(flash: Peri<'static, FLASH>) -> Self {
+        let mut storage = Self {
+            flash: Flash::new_blocking(flash),
+            block: [0xFF; BLOCK_LEN],
+            stored: [0xFF; BLOCK_LEN],
+            next: SLOTS,
+        };
+        match storage.find_next() {
+            Ok(next) => {
+                storage.next = next;
+                if next > 0 {
+                    storage.stored = storage.read_slot(next - 1).unwrap_or([0xFF; BLOCK_LEN]);
+                }
+            }
+            Err(e) => warn!("Error {} reading the stored settings", e),
+        }
+        storage.block = storage.stored;
+        storage
+    }
+
+    fn read_slot(&mut self, slot: u32) -> Result<[u8; BLOCK_LEN], Error> {
+        let mut block = [0; BLOCK_LEN];
+        let offset = SECTOR_OFFSET + slot * BLOCK_LEN as u32;
+        self.flash.blocking_read(offset, &mut block)?;
+        Ok(block)
+    }
+
+    /// Slots are written in order, so the used ones come first. Finds the
+    /// first free one by bisection.
+    fn find_next(&mut self) -> Result<u32, Error> {
+        let (mut used, mut free) = (0, SLOTS);
+        while used < free {
+            let middle = (used + free) / 2;
+            if self.read_slot(middle)?.iter().all(|&b| b == 0xFF) {
+                free = middle;
+            } else {
+                used = middle + 1;
+            }
+        }
+        Ok(free)
+    }
+
+    /// `record` as changed, not necessarily written yet. Erased flash
+    /// reads as 0xFF.
+    pub fn read(&self, record: Record) -> &[u8] {
+        &self.block[record.offset..record.offset + record.len]
+    }
+
+    /// Replaces `record` with `data` until the next [`Storage::flush`]
+    pub fn write(&mut self, record: Record, data: &[u8]) {
+        self.block[record.offset..record.offset + record.len].copy_from_slice(&data[..record.len]);
+    }
+
+    /// Writes the changed records, returns whether there were any. Takes
+    /// about a millisecond, and a second or more for every [`SLOTS`]th
+    /// write, which erases the sector first.
+    pub fn flush(&mut self) -> Result<bool, Error> {
+        if self.block == self.stored {
+            return Ok(false);
+        }
+        if self.next == SLOTS {
+            // Erased until written again, whatever fails next
+            self.stored = [0xFF; BLOCK_LEN];
+            self.flash
+                .blocking_erase(SECTOR_OFFSET, SECTOR_OFFSET + SECTOR_SIZE)?;
+            self.next = 0;
+        }
+        let offset = SECTOR_OFFSET + self.next * BLOCK_LEN as u32;
+        if let Err(e) = self.flash.blocking_write(offset, &self.block) {
+            // The slot may be half written, erase before the next try so the
+            // used slots stay in one piece
+            self.next = SLOTS;
+            return Err(e);
+        }
+        self.next += 1;
+        self.stored = self.block;
+        Ok(true)
+    }
+
+    /// The stored touch calibration, `None` if there is none yet
+    pub fn calibration(&self) -> Option<Calibration> {
+        Calibration::from_bytes(self.read(CALIBRATION))
+    }
+
+    pub fn set_calibration(&mut self, calibration: &Calibration) {
+        self.write(CALIBRATION, &calibration.to_bytes())
+    }
+
+    /// The stored network settings, `None` if there are none yet
+    pub fn network(&self) -> Option<NetConfig> {
+        NetConfig::from_bytes(self.read(NETWORK))
+    }
+
+    pub fn set_network(&mut self, config: &NetConfig) {
+        self.write(NETWORK, &config.to_bytes())
+    }
+}
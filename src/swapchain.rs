@@ -0,0 +1,195 @@
+//! VSYNC-synchronized double buffering on one LTDC layer.
+//!
+//! The new front buffer is latched with vertical-blanking reload (VBR), so
+//! the panel never shows half of one frame and half of the next. The LTDC
+//! interrupts wake the waiting task instead of a fixed sleep.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use embassy_stm32::interrupt;
+use embassy_stm32::interrupt::typelevel::{Binding, Interrupt};
+use embassy_stm32::pac::ltdc::vals::{Cfuif, Clif, Crrif, Cterrif};
+use embassy_stm32::pac::LTDC;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embedded_graphics::geometry::Dimensions;
+use embedded_graphics::pixelcolor::Rgb888;
+
+use crate::color::FrameColor;
+use crate::damage::{Damage, DamagedBuffer};
+use crate::display::DisplayBuffer;
+use crate::layer::{self, LtdcLayer, Reload};
+
+static RELOAD_DONE: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+static LINE_REACHED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+static FIFO_UNDERRUNS: AtomicU32 = AtomicU32::new(0);
+static TRANSFER_ERRORS: AtomicU32 = AtomicU32::new(0);
+
+/// Handles both LTDC interrupts, bind it to `LTDC` and `LTDC_ER`
+pub struct InterruptHandler;
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::LTDC> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        on_interrupt()
+    }
+}
+
+impl interrupt::typelevel::Handler<interrupt::typelevel::LTDC_ER> for InterruptHandler {
+    unsafe fn on_interrupt() {
+        on_interrupt()
+    }
+}
+
+fn on_interrupt() {
+    let isr = LTDC.isr().read();
+
+    if isr.rrif() {
+        LTDC.icr().write(|w| w.set_crrif(Crrif::CLEAR));
+        RELOAD_DONE.signal(());
+    }
+
+    if isr.lif() {
+        LTDC.icr().write(|w| w.set_clif(Clif::CLEAR));
+        // One shot, re-armed by the next `wait_for_line`
+        LTDC.ier().modify(|w| w.set_lie(false));
+        LINE_REACHED.signal(());
+    }
+
+    if isr.fuif() {
+        LTDC.icr().write(|w| w.set_cfuif(Cfuif::CLEAR));
+        FIFO_UNDERRUNS.fetch_add(1, Ordering::Relaxed);
+    }
+
+    if isr.terrif() {
+        LTDC.icr().write(|w| w.set_cterrif(Cterrif::CLEAR));
+        TRANSFER_ERRORS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Error counters since boot
+#[derive(Clone, Copy, Debug, PartialEq, Eq, defmt::Format)]
+pub struct LtdcErrors {
+    /// The LTDC FIFO ran empty, usually SDRAM bandwidth is exhausted
+    pub fifo_underruns: u32,
+    /// Bus error while fetching a frame buffer
+    pub transfer_errors: u32,
+}
+
+pub fn errors() -> LtdcErrors {
+    LtdcErrors {
+        fifo_underruns: FIFO_UNDERRUNS.load(Ordering::Relaxed),
+        transfer_errors: TRANSFER_ERRORS.load(Ordering::Relaxed),
+    }
+}
+
+/// Waits until the LTDC starts scanning out `line` of the active area.
+pub async fn wait_for_line(line: u16) {
+    let avbp = LTDC.bpcr().read().avbp();
+
+    LINE_REACHED.reset();
+    LTDC.lipcr().write(|w| w.set_lipos(avbp + 1 + line));
+    LTDC.ier().modify(|w| w.set_lie(true));
+
+    LINE_REACHED.wait().await;
+}
+
+/// Two frame buffers on one layer. Draw into [`Self::back_buffer`], then
+/// [`Self::present`] it.
+///
+/// Only what was drawn since the last present is copied over to the other
+/// buffer, so both buffers hold the same image between frames and a small
+/// change costs only its own area.
+pub struct FrameBufferSwapchain<C: FrameColor = Rgb888> {
+    layer: LtdcLayer,
+    buffers: [DisplayBuffer<'static, C>; 2],
+    front: usize,
+    /// Drawn into the back buffer since the last present
+    damage: Damage,
+}
+
+impl<C: FrameColor> FrameBufferSwapchain<C> {
+    /// Shows `front` on `layer` right away and enables the LTDC interrupts.
+    ///
+    /// `back` is overwritten with the contents of `front`.
+    pub fn new(
+        mut layer: LtdcLayer,
+        front: DisplayBuffer<'static, C>,
+        mut back: DisplayBuffer<'static, C>,
+        _irqs: impl Binding<interrupt::typelevel::LTDC, InterruptHandler>
+            + Binding<interrupt::typelevel::LTDC_ER, InterruptHandler>,
+    ) -> Self {
+        back.copy_area_from(&front, &front.bounding_box());
+
+        layer.set_framebuffer(&front);
+        layer.enable();
+        layer::reload(Reload::Immediate);
+
+        LTDC.ier().modify(|w| {
+            w.set_rrie(true);
+            w.set_terrie(true);
+            w.set_fuie(true);
+        });
+
+        interrupt::typelevel::LTDC::unpend();
+        interrupt::typelevel::LTDC_ER::unpend();
+        unsafe {
+            interrupt::typelevel::LTDC::enable();
+            interrupt::typelevel::LTDC_ER::enable();
+        }
+
+        Self {
+            layer,
+            buffers: [front, back],
+            front: 0,
+            damage: Damage::new(),
+        }
+    }
+
+    pub fn layer(&mut self) -> &mut LtdcLayer {
+        &mut self.layer
+    }
+
+    /// Buffer currently scanned out, do not draw into it
+    pub fn front_buffer(&self) -> &DisplayBuffer<'static, C> {
+        &self.buffers[self.front]
+    }
+
+    /// The next frame, starting out as a copy of the visible one
+    pub fn back_buffer(&mut self) -> DamagedBuffer<'_, C> {
+        DamagedBuffer::new(&mut self.buffers[1 - self.front], &mut self.damage)
+    }
+
+    /// Areas drawn since the last present
+    pub fn damage(&self) -> &Damage {
+        &self.damage
+    }
+
+    /// Makes the back buffer visible at the next vertical blanking and
+    /// waits until the LTDC has switched over. Returns right away if
+    /// nothing was drawn.
+    ///
+    /// Afterwards the damaged areas are copied to the old front buffer,
+    /// which becomes the new back buffer.
+    pub async fn present(&mut self) {
+        if self.damage.is_empty() {
+            return;
+        }
+
+        let back = 1 - self.front;
+
+        RELOAD_DONE.reset();
+        self.layer.set_framebuffer(&self.buffers[back]);
+        layer::reload(Reload::VerticalBlanking);
+        RELOAD_DONE.wait().await;
+
+        self.front = back;
+
+        let [a, b] = &mut self.buffers;
+        let (front, back) = if self.front == 0 { (a, b) } else { (b, a) };
+        for rect in self.damage.rects() {
+            back.copy_area_from(front, rect);
+        }
+        self.damage.clear();
+    }
+}
@@ -0,0 +1,387 @@
+use core::future::pending;
+use defmt::*;
+
+use embassy_futures::select::{select, select4, Either, Either4};
+use embassy_net::tcp::TcpSocket;
+use embassy_net::{ConfigV4, Runner, Stack};
+use embassy_stm32::gpio::Output;
+use embassy_time::{Duration, Instant, Timer};
+use embassy_usb::class::cdc_acm::CdcAcmClass;
+use embassy_usb::UsbDevice;
+
+use crate::gesture::{Gesture, Recognizer};
+use crate::interlock::Interlock;
+use crate::link::{self, TcpLink};
+use crate::multitouch::{Contact, Contacts, TraceLine, Tracker};
+use crate::net::{self, Device, Protocol, TcpServer, TCP_SESSIONS};
+use crate::netconfig::{NetConfig, NetState, DHCP_TIMEOUT_SECS};
+use crate::outputs::{OutputBank, OUTPUTS};
+use crate::scpi::{Instrument, Scpi};
+use crate::shared::{
+    update_state, GESTURES, NET_SETTINGS, OUTPUTS_BUSY, OUTPUT_COMMANDS, STATE, STORE_CALIBRATION,
+    STORE_NETWORK, TOUCH_EVENTS, TOUCH_POINTS, TOUCH_TRANSFORM,
+};
+use crate::storage::Storage;
+use crate::touch::Touch;
+use crate::usb::UsbDriver;
+
+/// Sleeps until the touch panel's interrupt line reports a finger, then
+/// reads the panel every 10 ms until all fingers are lifted. Publishes the
+/// touch events and gestures and sends taps to the GUI.
+#[embassy_executor::task]
+pub async fn catch_touch(mut touch: Touch) {
+    let mut tracker = Tracker::default();
+    let mut recognizer = Recognizer::default();
+
+    loop {
+        touch.wait_for_touch().await;
+
+        loop {
+            match touch.read_contacts().await {
+                Err(e) => error!("Error {} reading the touch points", e),
+                Ok(contacts) => {
+                    update(&mut tracker, &mut recognizer, &contacts).await;
+                    // The read without contacts has lifted every finger
+                    if contacts.is_empty() && !touch.is_touched() {
+                        break;
+                    }
+                }
+            }
+
+            Timer::after_millis(10).await;
+        }
+    }
+}
+
+fn now_ms() -> u32 {
+    Instant::now().as_millis() as u32
+}
+
+async fn update(tracker: &mut Tracker, recognizer: &mut Recognizer, raw: &Contacts) {
+    let now = now_ms();
+
+    let transform = TOUCH_TRANSFORM.lock(|t| t.get());
+    let contacts: Contacts = raw
+        .iter()
+        .map(|c| Contact {
+            point: transform.apply(c.point),
+            ..*c
+        })
+        .collect();
+    // Recorded traces for the gesture tests come from here
+    trace!("{}", TraceLine(now, &contacts));
+
+    for event in tracker.update(&contacts) {
+        TOUCH_EVENTS.immediate_publisher().publish_immediate(event);
+        if let Some(gesture) = recognizer.event(event, now) {
+            report(gesture).await;
+        }
+    }
+    if let Some(gesture) = recognizer.poll(now) {
+        report(gesture).await;
+    }
+}
+
+async fn report(gesture: Gesture) {
+    debug!("{}", gesture);
+    GESTURES.immediate_publisher().publish_immediate(gesture);
+
+    // For the buttons a double tap is two taps
+    if let Gesture::Tap(point) | Gesture::DoubleTap(point) = gesture {
+        TOUCH_POINTS.send(point).await;
+    }
+}
+
+/// The outputs of [`OUTPUTS`] on the board's pins
+pub type Outputs = OutputBank<Output<'static>, { OUTPUTS.len() }>;
+
+/// Executes the commands of [`OUTPUT_COMMANDS`] as the `interlock` plans
+/// them and ends pulses in time. Commands that break a rule are dropped.
+/// After each, the levels read back from the pins go to the shared
+/// [`AppState`](crate::shared::AppState). The firmware runs it on an
+/// interrupt executor, so a task that blocks the others does not delay it.
+/// While a pulse or a plan runs it reports [`OUTPUTS_BUSY`].
+#[embassy_executor::task]
+pub async fn outputs_task(mut bank: Outputs, interlock: Interlock) {
+    loop {
+        let levels = bank.levels();
+        update_state(|state| state.outputs = levels);
+        set_outputs_busy(bank.next_deadline().is_some());
+
+        let command = match bank.next_deadline() {
+            None => Some(OUTPUT_COMMANDS.receive().await),
+            Some(end) => {
+                // Already due if the deadline passed
+                let wait = (end.wrapping_sub(now_ms()) as i32).max(0) as u64;
+                match select(OUTPUT_COMMANDS.receive(), Timer::after_millis(wait)).await {
+                    Either::First(command) => Some(command),
+                    Either::Second(()) => None,
+                }
+            }
+        };
+
+        let Some(command) = command else {
+            bank.poll(now_ms());
+            continue;
+        };
+        info!("{}", command);
+
+        let steps = match interlock.plan(command, bank.levels()) {
+            Ok(steps) => steps,
+            Err(violation) => {
+                warn!("{} rejected: {}", command, violation);
+                continue;
+            }
+        };
+        set_outputs_busy(true);
+        for (i, step) in steps.iter().enumerate() {
+            if i > 0 {
+                // Shown while waiting, e.g. a relay already opened
+                let levels = bank.levels();
+                update_state(|state| state.outputs = levels);
+            }
+            if step.delay_ms > 0 {
+                Timer::after_millis(step.delay_ms.into()).await;
+            }
+
+            let now = now_ms();
+            if let Err(e) = bank.execute(step.command, now) {
+                warn!("{}", e);
+            }
+            bank.poll(now);
+        }
+    }
+}
+
+fn set_outputs_busy(busy: bool) {
+    OUTPUTS_BUSY.sender().send_if_modified(|current| {
+        let changed = *current != Some(busy);
+        *current = Some(busy);
+        changed
+    });
+}
+
+/// Runs the USB device, the serial port does nothing without it
+#[embassy_executor::task]
+pub async fn usb_task(mut device: UsbDevice<'static, UsbDriver>) -> ! {
+    device.run().await
+}
+
+/// Serves the remote control protocol of [`crate::remote`] on the USB
+/// serial port
+#[embassy_executor::task]
+pub async fn remote_task(mut serial: CdcAcmClass<'static, UsbDriver>) {
+    let mut state_changes = unwrap!(STATE.receiver());
+    loop {
+        serial.wait_connection().await;
+        info!("USB serial connected");
+        // Ends when the host closes the port
+        let _ = link::serve_remote(&mut serial, &mut state_changes).await;
+        info!("USB serial disconnected");
+    }
+}
+
+/// Serves SCPI on the second USB serial port. Every connection starts
+/// with an empty error queue.
+#[embassy_executor::task]
+pub async fn scpi_task(
+    mut serial: CdcAcmClass<'static, UsbDriver>,
+    instrument: Instrument,
+    interlock: Interlock,
+) {
+    let mut state_changes = unwrap!(STATE.receiver());
+    loop {
+        serial.wait_connection().await;
+        info!("SCPI connected");
+        let mut scpi = Scpi::new(instrument, interlock.clone());
+        let _ = link::serve_scpi(&mut serial, &mut scpi, &mut state_changes).await;
+        info!("SCPI disconnected");
+    }
+}
+
+/// Runs the network stack, no socket works without it
+#[embassy_executor::task]
+pub async fn net_task(mut runner: Runner<'static, Device>) -> ! {
+    runner.run().await
+}
+
+/// Keeps the network in the shared [`AppState`](crate::shared::AppState)
+/// and applies new settings from [`NET_SETTINGS`], which the storage task
+/// stores.
+/// With DHCP the static settings are used if no lease came within
+/// [`DHCP_TIMEOUT_SECS`] of the link coming up, until the link goes down.
+#[embassy_executor::task]
+pub async fn network_task(stack: Stack<'static>, mut config: NetConfig, mac: [u8; 6]) {
+    let mut fallback = false;
+
+    loop {
+        let link_up = stack.is_link_up();
+        let address = stack
+            .config_v4()
+            .map(|v4| (v4.address.address(), v4.address.prefix_len()));
+        let network = NetState {
+            mac,
+            config,
+            link_up,
+            address,
+            fallback,
+        };
+        update_state(|state| state.network = network);
+
+        let link_change = async {
+            if link_up {
+                stack.wait_link_down().await
+            } else {
+                stack.wait_link_up().await
+            }
+        };
+        let address_change = async {
+            if address.is_some() {
+                stack.wait_config_down().await
+            } else {
+                stack.wait_config_up().await
+            }
+        };
+        let waiting_for_lease = config.dhcp && link_up && address.is_none() && !fallback;
+        let dhcp_timeout = async {
+            if waiting_for_lease {
+                Timer::after_secs(DHCP_TIMEOUT_SECS).await
+            } else {
+                pending().await
+            }
+        };
+
+        let event = select4(
+            NET_SETTINGS.receive(),
+            link_change,
+            address_change,
+            dhcp_timeout,
+        )
+        .await;
+        match event {
+            Either4::First(change) => {
+                let new = config.changed(change);
+                info!("Network settings {}", new);
+                STORE_NETWORK.signal(new);
+                config = new;
+                fallback = false;
+                stack.set_config_v4(net::config(&config).ipv4);
+            }
+            Either4::Second(()) if link_up => {
+                info!("Ethernet link down");
+                // The next link may be to a network with a DHCP server
+                if fallback {
+                    fallback = false;
+                    stack.set_config_v4(net::config(&config).ipv4);
+                }
+            }
+            Either4::Second(()) => info!("Ethernet link up"),
+            Either4::Third(()) => match stack.config_v4() {
+                Some(v4) => info!("IP address {}", v4.address),
+                None => info!("IP address released"),
+            },
+            Either4::Fourth(()) => {
+                warn!(
+                    "No DHCP lease after {} s, using {}",
+                    DHCP_TIMEOUT_SECS, config
+                );
+                fallback = true;
+                stack.set_config_v4(ConfigV4::Static(net::static_v4(&config)));
+            }
+        }
+    }
+}
+
+/// Settings have to be the same for this long before they are stored
+pub const STORE_DELAY_SECS: u64 = 5;
+
+/// Stores new settings from [`STORE_CALIBRATION`] and [`STORE_NETWORK`].
+/// Every write uses up a slot of the flash sector and the sector is erased
+/// when they run out, see [`crate::storage`]. So changes in quick
+/// succession, e.g. several `NET` lines, are collected until none came for
+/// [`STORE_DELAY_SECS`] and then written together. Nothing is written if
+/// the settings are the stored ones.
+///
+/// The F746 has a single flash bank, a write or erase stalls every code
+/// fetch and with it the outputs task. So the settings are only written
+/// while [`OUTPUTS_BUSY`] is clear, commands that arrive meanwhile wait.
+#[embassy_executor::task]
+pub async fn storage_task(mut storage: Storage) {
+    let next_change = || select(STORE_CALIBRATION.wait(), STORE_NETWORK.wait());
+    let mut outputs_busy = unwrap!(OUTPUTS_BUSY.receiver());
+    loop {
+        let mut change = next_change().await;
+        loop {
+            match change {
+                Either::First(calibration) => storage.set_calibration(&calibration),
+                Either::Second(config) => storage.set_network(&config),
+            }
+            match select(next_change(), Timer::after_secs(STORE_DELAY_SECS)).await {
+                Either::First(next) => change = next,
+                Either::Second(()) => break,
+            }
+        }
+
+        // Checked again with the interrupts off, the outputs task cannot
+        // start a pulse between the check and the write
+        let result = loop {
+            outputs_busy.get_and(|busy| !busy).await;
+            let result = critical_section::with(|_| {
+                let busy = OUTPUTS_BUSY.try_get().unwrap_or(false);
+                (!busy).then(|| storage.flush())
+            });
+            if let Some(result) = result {
+                break result;
+            }
+        };
+        match result {
+            Ok(true) => info!("Settings stored"),
+            Ok(false) => {}
+            Err(e) => warn!("Error {} storing the settings", e),
+        }
+    }
+}
+
+/// One connection at a time to `server`. Spawn `server.sessions` of them
+/// for concurrent clients, [`TCP_SESSIONS`] in all. After a connection closes, or the
+/// network went down, the session listens again.
+#[embassy_executor::task(pool_size = TCP_SESSIONS)]
+pub async fn tcp_session_task(stack: Stack<'static>, server: TcpServer, interlock: Interlock) {
+    let mut rx_buffer = [0; 1024];
+    let mut tx_buffer = [0; 1024];
+    let mut state_changes = unwrap!(STATE.receiver());
+
+    loop {
+        stack.wait_config_up().await;
+
+        let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+        // A client that vanished without closing, e.g. with the cable
+        // pulled, fails the keep-alive and frees the session
+        socket.set_keep_alive(Some(Duration::from_secs(10)));
+        socket.set_timeout(Some(Duration::from_secs(30)));
+        if let Err(e) = socket.accept(server.port).await {
+            warn!("Accept on TCP port {} failed: {}", server.port, e);
+            Timer::after_secs(1).await;
+            continue;
+        }
+        info!(
+            "TCP port {} connected to {}",
+            server.port,
+            socket.remote_endpoint()
+        );
+
+        let mut connection = TcpLink::new(socket, server.idle_timeout);
+        let _ = match server.protocol {
+            Protocol::Remote => link::serve_remote(&mut connection, &mut state_changes).await,
+            Protocol::Scpi(instrument) => {
+                let mut scpi = Scpi::new(instrument, interlock.clone());
+                link::serve_scpi(&mut connection, &mut scpi, &mut state_changes).await
+            }
+            Protocol::Http => {
+                link::serve_http(&mut connection, &interlock, &mut state_changes).await
+            }
+        };
+        connection.close().await;
+        info!("TCP port {} disconnected", server.port);
+    }
+}
@@ -0,0 +1,87 @@
+use defmt::warn;
+use embassy_stm32::exti::ExtiInput;
+use embassy_stm32::gpio::Pull;
+use embassy_stm32::i2c::{self, I2c};
+use embassy_stm32::mode::Async;
+use embassy_stm32::peripherals::*;
+use embassy_stm32::time::Hertz;
+use embassy_stm32::{bind_interrupts, Peri};
+
+use crate::multitouch::{parse_registers, Contacts, REGISTERS_LEN, REGISTERS_START};
+
+bind_interrupts!(struct Irqs {
+    I2C3_EV => i2c::EventInterruptHandler<I2C3>;
+    I2C3_ER => i2c::ErrorInterruptHandler<I2C3>;
+});
+
+/// I2C address of the FT5336 touch controller
+pub const FT5336_ADDR: u8 = 0x38;
+
+/// How the INT line reports touches
+const G_MODE: u8 = 0xA4;
+/// INT is held low as long as a finger is on the panel
+const G_MODE_INTERRUPT_POLLING: u8 = 0x00;
+
+pub struct TouchPins {
+    pub scl: Peri<'static, PH7>,
+    pub sda: Peri<'static, PH8>,
+    /// LCD_INT, driven by the FT5336
+    pub int: Peri<'static, PI13>,
+    pub int_exti: Peri<'static, EXTI13>,
+}
+
+/// DMA streams of I2C3
+pub struct TouchDma {
+    pub tx: Peri<'static, DMA1_CH4>,
+    pub rx: Peri<'static, DMA1_CH2>,
+}
+
+/// FT5336 capacitive touch panel together with the I2C bus it sits on
+/// and its interrupt line.
+pub struct Touch {
+    pub i2c: I2c<'static, Async>,
+    pub int: ExtiInput<'static>,
+}
+
+/// Initializes I2C3 and puts the FT5336 in interrupt mode
+pub fn init_touch(i2c: Peri<'static, I2C3>, pins: TouchPins, dma: TouchDma) -> Touch {
+    // A read of all five points takes about 3 ms at 100 kHz
+    let mut i2c = I2c::new(
+        i2c,
+        pins.scl,
+        pins.sda,
+        Irqs,
+        dma.tx,
+        dma.rx,
+        Hertz(100_000),
+        Default::default(),
+    );
+
+    if let Err(e) = i2c.blocking_write(FT5336_ADDR, &[G_MODE, G_MODE_INTERRUPT_POLLING]) {
+        warn!("Error {} setting the touch interrupt mode", e);
+    }
+    let int = ExtiInput::new(pins.int, pins.int_exti, Pull::Up);
+
+    Touch { i2c, int }
+}
+
+impl Touch {
+    /// Waits until a finger is on the panel, the CPU sleeps meanwhile
+    pub async fn wait_for_touch(&mut self) {
+        self.int.wait_for_low().await;
+    }
+
+    /// The controller reports a finger on the panel
+    pub fn is_touched(&self) -> bool {
+        self.int.is_low()
+    }
+
+    /// Reads every finger on the panel in one DMA transfer
+    pub async fn read_contacts(&mut self) -> Result<Contacts, i2c::Error> {
+        let mut registers = [0; REGISTERS_LEN];
+        self.i2c
+            .write_read(FT5336_ADDR, &[REGISTERS_START], &mut registers)
+            .await?;
+        Ok(parse_registers(&registers))
+    }
+}
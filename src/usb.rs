@@ -0,0 +1,84 @@
+//! USB OTG FS as two CDC ACM serial ports: the first one for the remote
+//! control protocol of [`crate::remote`], the second one for
+//! [`crate::scpi`].
+//!
+//! Needs the 48 MHz USB clock from PLLQ, e.g. [`crate::rcc::MAX_PERFORMANCE`].
+
+use embassy_stm32::usb::{self, Driver};
+use embassy_stm32::{bind_interrupts, peripherals};
+use embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+use embassy_usb::{Builder, UsbDevice};
+use static_cell::StaticCell;
+
+use crate::board::UsbPeripherals;
+
+bind_interrupts!(struct Irqs {
+    OTG_FS => usb::InterruptHandler<peripherals::USB_OTG_FS>;
+});
+
+pub type UsbDriver = Driver<'static, peripherals::USB_OTG_FS>;
+
+/// Full speed bulk endpoints carry at most 64 bytes per packet
+pub const MAX_PACKET_SIZE: u16 = 64;
+
+/// The running device and its serial ports
+pub struct Usb {
+    /// Has to be run for the ports to work, see `tasks::usb_task`
+    pub device: UsbDevice<'static, UsbDriver>,
+    pub serial: CdcAcmClass<'static, UsbDriver>,
+    pub scpi: CdcAcmClass<'static, UsbDriver>,
+}
+
+/// Builds the USB device with both CDC ACM ports. May be called once.
+pub fn init_usb(p: UsbPeripherals) -> Usb {
+    static EP_OUT_BUFFER: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONFIG_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static BOS_DESCRIPTOR: StaticCell<[u8; 256]> = StaticCell::new();
+    static CONTROL_BUF: StaticCell<[u8; 64]> = StaticCell::new();
+    static SERIAL_STATE: StaticCell<State> = StaticCell::new();
+    static SCPI_STATE: StaticCell<State> = StaticCell::new();
+
+    // Without VBUS detection, which works on every board. A self-powered
+    // device should have it to comply with the USB spec.
+    let mut config = usb::Config::default();
+    config.vbus_detection = false;
+
+    let driver = Driver::new_fs(
+        p.usb,
+        Irqs,
+        p.dp,
+        p.dm,
+        EP_OUT_BUFFER.init([0; 256]),
+        config,
+    );
+
+    let mut config = embassy_usb::Config::new(0xc0de, 0xcafe);
+    config.manufacturer = Some("f7disco-rs");
+    config.product = Some("Output panel");
+    // Two functions, grouped by interface association descriptors
+    config.device_class = 0xEF;
+    config.device_sub_class = 0x02;
+    config.device_protocol = 0x01;
+    config.composite_with_iads = true;
+
+    let mut builder = Builder::new(
+        driver,
+        config,
+        CONFIG_DESCRIPTOR.init([0; 256]),
+        BOS_DESCRIPTOR.init([0; 256]),
+        &mut [], // no msos descriptors
+        CONTROL_BUF.init([0; 64]),
+    );
+    let serial = CdcAcmClass::new(
+        &mut builder,
+        SERIAL_STATE.init(State::new()),
+        MAX_PACKET_SIZE,
+    );
+    let scpi = CdcAcmClass::new(&mut builder, SCPI_STATE.init(State::new()), MAX_PACKET_SIZE);
+
+    Usb {
+        device: builder.build(),
+        serial,
+        scpi,
+    }
+}
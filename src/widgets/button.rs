@@ -0,0 +1,52 @@
+//! Push button that sends [`EventKind::Clicked`] when released.
+
+use alloc::string::String;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{draw_box, DrawContext, EventKind, Touch, Widget};
+
+pub struct Button {
+    pub text: String,
+}
+
+impl Button {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self { text: text.into() }
+    }
+}
+
+impl Widget for Button {
+    fn draw<D>(&self, ctx: &DrawContext, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+    {
+        let theme = ctx.theme;
+        let (fill, text) = if ctx.state.disabled {
+            (theme.disabled_color, theme.disabled_text_color)
+        } else if ctx.state.pressed {
+            (theme.pressed_color, theme.text_color)
+        } else {
+            (theme.button_color, theme.text_color)
+        };
+        draw_box(&ctx.bounds, fill, &self.text, text, theme, target)
+    }
+
+    /// Lifting the finger outside the button cancels the click
+    fn touch(&mut self, bounds: &Rectangle, touch: Touch) -> Option<EventKind> {
+        match touch {
+            Touch::Up(point) if bounds.contains(point) => Some(EventKind::Clicked),
+            _ => None,
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+
+    fn activate(&mut self) -> Option<EventKind> {
+        Some(EventKind::Clicked)
+    }
+}
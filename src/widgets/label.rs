@@ -0,0 +1,89 @@
+//! Static or changing text.
+
+use alloc::string::String;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Point;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle};
+use embedded_graphics::text::{Alignment, Baseline, Text, TextStyleBuilder};
+use embedded_graphics::Drawable;
+
+use super::{DrawContext, Widget};
+
+pub struct Label {
+    pub text: String,
+    pub alignment: Alignment,
+    /// Cleared before the text is drawn. Without one, old text stays
+    /// visible when the text changes, unless a parent panel is redrawn.
+    pub background: Option<Rgb888>,
+    pub color: Option<Rgb888>,
+}
+
+impl Label {
+    /// Left-aligned, vertically centred text in the theme color
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            alignment: Alignment::Left,
+            background: None,
+            color: None,
+        }
+    }
+
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn with_background(mut self, background: Rgb888) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    pub fn with_color(mut self, color: Rgb888) -> Self {
+        self.color = Some(color);
+        self
+    }
+}
+
+impl Widget for Label {
+    fn draw<D>(&self, ctx: &DrawContext, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+    {
+        let bounds = ctx.bounds;
+        if let Some(background) = self.background {
+            bounds
+                .into_styled(PrimitiveStyle::with_fill(background))
+                .draw(target)?;
+        }
+
+        let color = match (ctx.state.disabled, self.color) {
+            (true, _) => ctx.theme.disabled_text_color,
+            (false, Some(color)) => color,
+            (false, None) => ctx.theme.text_color,
+        };
+
+        let y = bounds.top_left.y + bounds.size.height as i32 / 2;
+        let x = match self.alignment {
+            Alignment::Left => bounds.top_left.x,
+            Alignment::Center => bounds.top_left.x + bounds.size.width as i32 / 2,
+            Alignment::Right => bounds.top_left.x + bounds.size.width as i32 - 1,
+        };
+
+        let text_style = TextStyleBuilder::new()
+            .alignment(self.alignment)
+            .baseline(Baseline::Middle)
+            .build();
+        Text::with_text_style(
+            &self.text,
+            Point::new(x, y),
+            MonoTextStyle::new(ctx.theme.font, color),
+            text_style,
+        )
+        .draw(target)?;
+        Ok(())
+    }
+}
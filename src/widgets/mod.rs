@@ -0,0 +1,549 @@
+//! Retained-mode widgets.
+//!
+//! A [`WidgetTree`] owns every widget of a screen together with its
+//! rectangle and [`State`]. Touches are routed to the widget under the
+//! finger, which turns them into [`Event`]s; those are returned and passed
+//! to the callbacks registered with [`WidgetTree::on_event`]. Drawing only
+//! repaints widgets that changed since the last pass.
+//!
+//! ```ignore
+//! let mut ui = WidgetTree::new(Theme::default());
+//! let rf = ui.add(
+//!     None,
+//!     Rectangle::new(Point::new(176, 104), Size::new(120, 50)),
+//!     ToggleButton::new("RF").with_state_text(),
+//! );
+//! ui.on_event(rf, |event| info!("{}", event));
+//! ```
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Dimensions, Point};
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::Text;
+use embedded_graphics::Drawable;
+
+pub mod button;
+pub mod label;
+pub mod numeric_field;
+pub mod panel;
+pub mod progress_bar;
+pub mod slider;
+pub mod theme;
+pub mod toggle_button;
+
+pub use button::Button;
+pub use label::Label;
+pub use numeric_field::NumericField;
+pub use panel::Panel;
+pub use progress_bar::ProgressBar;
+pub use slider::Slider;
+pub use theme::Theme;
+pub use toggle_button::ToggleButton;
+
+/// Handle of a widget in its [`WidgetTree`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct WidgetId(pub u16);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct State {
+    /// Selected for [`WidgetTree::activate`]
+    pub focused: bool,
+    /// A finger is down on the widget
+    pub pressed: bool,
+    /// Drawn greyed out and ignores input
+    pub disabled: bool,
+}
+
+/// One phase of a touch
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum Touch {
+    Down(Point),
+    Move(Point),
+    Up(Point),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub enum EventKind {
+    /// A [`Button`] was released over itself
+    Clicked,
+    /// A [`ToggleButton`] asks for this state
+    Toggled(bool),
+    /// A [`Slider`] or [`NumericField`] has a new value
+    Changed(i32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "hw", derive(defmt::Format))]
+pub struct Event {
+    pub id: WidgetId,
+    pub kind: EventKind,
+}
+
+/// Everything a widget needs to draw itself
+pub struct DrawContext<'a> {
+    pub bounds: Rectangle,
+    pub state: State,
+    pub theme: &'a Theme,
+}
+
+/// Behaviour shared by all widgets
+pub trait Widget {
+    fn draw<D>(&self, ctx: &DrawContext, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>;
+
+    /// Handles a touch inside `bounds`, or a `Move`/`Up` after a `Down`
+    /// inside it
+    fn touch(&mut self, _bounds: &Rectangle, _touch: Touch) -> Option<EventKind> {
+        None
+    }
+
+    /// Takes touches and focus
+    fn is_interactive(&self) -> bool {
+        false
+    }
+
+    /// The focused widget was activated without touch, e.g. by a hardware
+    /// button
+    fn activate(&mut self) -> Option<EventKind> {
+        None
+    }
+}
+
+/// Any widget in a tree
+pub enum AnyWidget {
+    Label(Label),
+    Button(Button),
+    ToggleButton(ToggleButton),
+    Slider(Slider),
+    ProgressBar(ProgressBar),
+    NumericField(NumericField),
+    Panel(Panel),
+}
+
+macro_rules! dispatch {
+    ($self:expr, $w:ident => $body:expr) => {
+        match $self {
+            AnyWidget::Label($w) => $body,
+            AnyWidget::Button($w) => $body,
+            AnyWidget::ToggleButton($w) => $body,
+            AnyWidget::Slider($w) => $body,
+            AnyWidget::ProgressBar($w) => $body,
+            AnyWidget::NumericField($w) => $body,
+            AnyWidget::Panel($w) => $body,
+        }
+    };
+}
+
+macro_rules! any_widget_from {
+    ($($ty:ident),*) => {
+        $(impl From<$ty> for AnyWidget {
+            fn from(widget: $ty) -> Self {
+                AnyWidget::$ty(widget)
+            }
+        })*
+    };
+}
+
+any_widget_from!(
+    Label,
+    Button,
+    ToggleButton,
+    Slider,
+    ProgressBar,
+    NumericField,
+    Panel
+);
+
+impl Widget for AnyWidget {
+    fn draw<D>(&self, ctx: &DrawContext, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+    {
+        dispatch!(self, w => w.draw(ctx, target))
+    }
+
+    fn touch(&mut self, bounds: &Rectangle, touch: Touch) -> Option<EventKind> {
+        dispatch!(self, w => w.touch(bounds, touch))
+    }
+
+    fn is_interactive(&self) -> bool {
+        dispatch!(self, w => w.is_interactive())
+    }
+
+    fn activate(&mut self) -> Option<EventKind> {
+        dispatch!(self, w => w.activate())
+    }
+}
+
+type Callback = Box<dyn FnMut(&Event)>;
+
+struct Node {
+    parent: Option<WidgetId>,
+    bounds: Rectangle,
+    state: State,
+    widget: AnyWidget,
+    dirty: bool,
+    callback: Option<Callback>,
+}
+
+/// All widgets of a screen.
+///
+/// Widgets are kept in the order they were added, which is also the
+/// drawing order, so a parent is always drawn below its children.
+pub struct WidgetTree {
+    theme: Theme,
+    nodes: Vec<Node>,
+    focus: Option<WidgetId>,
+    /// Widget that got the last `Touch::Down`
+    captured: Option<WidgetId>,
+}
+
+impl WidgetTree {
+    pub fn new(theme: Theme) -> Self {
+        Self {
+            theme,
+            nodes: Vec::new(),
+            focus: None,
+            captured: None,
+        }
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Adds `widget` at `bounds` on top of everything added before.
+    ///
+    /// A widget with a `parent` is redrawn whenever the parent is.
+    pub fn add(
+        &mut self,
+        parent: Option<WidgetId>,
+        bounds: Rectangle,
+        widget: impl Into<AnyWidget>,
+    ) -> WidgetId {
+        let id = WidgetId(self.nodes.len() as u16);
+        self.nodes.push(Node {
+            parent,
+            bounds,
+            state: State::default(),
+            widget: widget.into(),
+            dirty: true,
+            callback: None,
+        });
+        id
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn ids(&self) -> impl DoubleEndedIterator<Item = WidgetId> {
+        (0..self.nodes.len() as u16).map(WidgetId)
+    }
+
+    /// Calls `callback` for every event of `id`
+    pub fn on_event(&mut self, id: WidgetId, callback: impl FnMut(&Event) + 'static) {
+        self.nodes[id.0 as usize].callback = Some(Box::new(callback));
+    }
+
+    pub fn widget(&self, id: WidgetId) -> &AnyWidget {
+        &self.nodes[id.0 as usize].widget
+    }
+
+    /// Changes a widget and redraws it on the next pass
+    pub fn widget_mut(&mut self, id: WidgetId) -> &mut AnyWidget {
+        let node = &mut self.nodes[id.0 as usize];
+        node.dirty = true;
+        &mut node.widget
+    }
+
+    pub fn bounds(&self, id: WidgetId) -> Rectangle {
+        self.nodes[id.0 as usize].bounds
+    }
+
+    pub fn set_bounds(&mut self, id: WidgetId, bounds: Rectangle) {
+        let node = &mut self.nodes[id.0 as usize];
+        node.bounds = bounds;
+        node.dirty = true;
+    }
+
+    pub fn parent(&self, id: WidgetId) -> Option<WidgetId> {
+        self.nodes[id.0 as usize].parent
+    }
+
+    pub fn state(&self, id: WidgetId) -> State {
+        self.nodes[id.0 as usize].state
+    }
+
+    fn update_state(&mut self, id: WidgetId, f: impl FnOnce(&mut State)) {
+        let node = &mut self.nodes[id.0 as usize];
+        let old = node.state;
+        f(&mut node.state);
+        node.dirty |= node.state != old;
+    }
+
+    pub fn set_disabled(&mut self, id: WidgetId, disabled: bool) {
+        self.update_state(id, |s| {
+            s.disabled = disabled;
+            s.pressed &= !disabled;
+        });
+        if disabled && self.focus == Some(id) {
+            self.set_focus(None);
+        }
+    }
+
+    /// Text of a [`Label`], [`Button`] or [`ToggleButton`]
+    pub fn set_text(&mut self, id: WidgetId, text: impl Into<String>) {
+        match self.widget_mut(id) {
+            AnyWidget::Label(w) => w.text = text.into(),
+            AnyWidget::Button(w) => w.text = text.into(),
+            AnyWidget::ToggleButton(w) => w.text = text.into(),
+            _ => {}
+        }
+    }
+
+    /// State of a [`ToggleButton`]
+    pub fn is_on(&self, id: WidgetId) -> Option<bool> {
+        match self.widget(id) {
+            AnyWidget::ToggleButton(w) => Some(w.on),
+            _ => None,
+        }
+    }
+
+    /// Shows a [`ToggleButton`] as on or off without sending an event
+    pub fn set_on(&mut self, id: WidgetId, on: bool) {
+        if self.is_on(id).is_some_and(|current| current != on) {
+            if let AnyWidget::ToggleButton(w) = self.widget_mut(id) {
+                w.on = on;
+            }
+        }
+    }
+
+    /// Value of a [`Slider`], [`ProgressBar`] or [`NumericField`]
+    pub fn value(&self, id: WidgetId) -> Option<i32> {
+        match self.widget(id) {
+            AnyWidget::Slider(w) => Some(w.value()),
+            AnyWidget::ProgressBar(w) => Some(w.value()),
+            AnyWidget::NumericField(w) => Some(w.value()),
+            _ => None,
+        }
+    }
+
+    /// Sets the value of a [`Slider`], [`ProgressBar`] or [`NumericField`]
+    /// without sending an event, clamped to its range
+    pub fn set_value(&mut self, id: WidgetId, value: i32) {
+        if self.value(id).is_none_or(|current| current == value) {
+            return;
+        }
+        match self.widget_mut(id) {
+            AnyWidget::Slider(w) => w.set_value(value),
+            AnyWidget::ProgressBar(w) => w.set_value(value),
+            AnyWidget::NumericField(w) => w.set_value(value),
+            _ => {}
+        }
+    }
+
+    fn accepts_input(&self, id: WidgetId) -> bool {
+        let node = &self.nodes[id.0 as usize];
+        node.widget.is_interactive() && !node.state.disabled
+    }
+
+    /// Topmost interactive widget at `point`
+    pub fn hit(&self, point: Point) -> Option<WidgetId> {
+        self.ids()
+            .rev()
+            .find(|&id| self.accepts_input(id) && self.bounds(id).contains(point))
+    }
+
+    fn emit(&mut self, id: WidgetId, kind: Option<EventKind>) -> Option<Event> {
+        let event = Event { id, kind: kind? };
+        let node = &mut self.nodes[id.0 as usize];
+        node.dirty = true;
+        if let Some(callback) = node.callback.as_mut() {
+            callback(&event);
+        }
+        Some(event)
+    }
+
+    /// Routes one touch phase. The widget hit by `Down` gets the following
+    /// `Move`s and the `Up`, even outside its bounds.
+    pub fn touch(&mut self, touch: Touch) -> Option<Event> {
+        let id = match touch {
+            Touch::Down(point) => {
+                let id = self.hit(point)?;
+                self.captured = Some(id);
+                self.update_state(id, |s| s.pressed = true);
+                id
+            }
+            Touch::Move(_) => self.captured?,
+            Touch::Up(_) => {
+                let id = self.captured.take()?;
+                self.update_state(id, |s| s.pressed = false);
+                id
+            }
+        };
+
+        if !self.accepts_input(id) {
+            return None;
+        }
+        let node = &mut self.nodes[id.0 as usize];
+        let kind = node.widget.touch(&node.bounds, touch);
+        self.emit(id, kind)
+    }
+
+    /// A short touch at `point`, as reported by the touch task
+    pub fn tap(&mut self, point: Point) -> Option<Event> {
+        let down = self.touch(Touch::Down(point));
+        let up = self.touch(Touch::Up(point));
+        up.or(down)
+    }
+
+    pub fn focus(&self) -> Option<WidgetId> {
+        self.focus
+    }
+
+    pub fn set_focus(&mut self, id: Option<WidgetId>) {
+        if let Some(old) = self.focus {
+            self.update_state(old, |s| s.focused = false);
+        }
+        self.focus = id.filter(|&id| self.accepts_input(id));
+        if let Some(new) = self.focus {
+            self.update_state(new, |s| s.focused = true);
+        }
+    }
+
+    /// Moves the focus to the next interactive widget, wrapping around
+    pub fn focus_next(&mut self) {
+        let n = self.nodes.len() as u16;
+        let start = self.focus.map_or(0, |id| id.0 + 1);
+        let next = (0..n)
+            .map(|i| WidgetId((start + i) % n))
+            .find(|&id| self.accepts_input(id));
+        self.set_focus(next);
+    }
+
+    /// Moves the focus to the previous interactive widget, wrapping around
+    pub fn focus_prev(&mut self) {
+        let n = self.nodes.len() as u16;
+        let start = self.focus.map_or(0, |id| id.0);
+        let prev = (1..=n)
+            .map(|i| WidgetId((start + n - i) % n))
+            .find(|&id| self.accepts_input(id));
+        self.set_focus(prev);
+    }
+
+    /// Activates the focused widget
+    pub fn activate(&mut self) -> Option<Event> {
+        let id = self.focus?;
+        let kind = self.nodes[id.0 as usize].widget.activate();
+        self.emit(id, kind)
+    }
+
+    /// Redraws everything on the next pass
+    pub fn invalidate(&mut self) {
+        for node in &mut self.nodes {
+            node.dirty = true;
+        }
+    }
+
+    /// Draws the widgets that changed since the last call, and everything
+    /// inside a redrawn parent
+    pub fn draw<D>(&mut self, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+    {
+        let mut redrawn = vec![false; self.nodes.len()];
+
+        for (i, node) in self.nodes.iter_mut().enumerate() {
+            let parent_redrawn = node.parent.is_some_and(|p| redrawn[p.0 as usize]);
+            if !node.dirty && !parent_redrawn {
+                continue;
+            }
+
+            let ctx = DrawContext {
+                bounds: node.bounds,
+                state: node.state,
+                theme: &self.theme,
+            };
+            node.widget.draw(&ctx, target)?;
+            if node.state.focused {
+                draw_focus(&ctx, target)?;
+            }
+
+            node.dirty = false;
+            redrawn[i] = true;
+        }
+        Ok(())
+    }
+}
+
+fn draw_focus<D>(ctx: &DrawContext, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    ctx.bounds
+        .into_styled(PrimitiveStyle::with_stroke(ctx.theme.focus_color, 2))
+        .draw(target)
+}
+
+/// Draws `text` centred in `area`
+pub(crate) fn draw_centered_text<D>(
+    text: &str,
+    area: &Rectangle,
+    style: MonoTextStyle<'_, Rgb888>,
+    target: &mut D,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    let text_bounds = Text::new(text, Point::zero(), style).bounding_box();
+    let free_width = area.size.width as i32 - text_bounds.size.width as i32;
+    let free_height = area.size.height as i32 - text_bounds.size.height as i32;
+
+    // `Text` is positioned by its baseline. For the 50 px high buttons of
+    // the original GUI this is where the text always was.
+    let position = Point::new(
+        area.top_left.x + free_width / 2,
+        area.top_left.y + free_height / 2 + style.font.baseline as i32,
+    );
+    Text::new(text, position, style).draw(target)?;
+    Ok(())
+}
+
+/// Fills `area` with `fill` and draws `text` centred on it, like a button
+pub(crate) fn draw_box<D>(
+    area: &Rectangle,
+    fill: Rgb888,
+    text: &str,
+    text_color: Rgb888,
+    theme: &Theme,
+    target: &mut D,
+) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = Rgb888>,
+{
+    area.into_styled(PrimitiveStyle::with_fill(fill))
+        .draw(target)?;
+    draw_centered_text(
+        text,
+        area,
+        MonoTextStyle::new(theme.font, text_color),
+        target,
+    )
+}
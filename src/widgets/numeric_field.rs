@@ -0,0 +1,142 @@
+//! Number with "-" and "+" areas on either side to step it.
+
+use alloc::format;
+use alloc::string::String;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyleBuilder, Rectangle};
+use embedded_graphics::Drawable;
+
+use super::{draw_box, DrawContext, EventKind, Touch, Widget};
+
+pub struct NumericField {
+    min: i32,
+    max: i32,
+    step: i32,
+    value: i32,
+    /// Shown after the value, e.g. " dBm"
+    pub unit: String,
+}
+
+impl NumericField {
+    /// Panics if `min > max`
+    pub fn new(min: i32, max: i32) -> Self {
+        assert!(min <= max);
+        Self {
+            min,
+            max,
+            step: 1,
+            value: min,
+            unit: String::new(),
+        }
+    }
+
+    pub fn with_step(mut self, step: i32) -> Self {
+        self.step = step.max(1);
+        self
+    }
+
+    pub fn with_value(mut self, value: i32) -> Self {
+        self.set_value(value);
+        self
+    }
+
+    pub fn with_unit(mut self, unit: impl Into<String>) -> Self {
+        self.unit = unit.into();
+        self
+    }
+
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+
+    pub fn set_value(&mut self, value: i32) {
+        self.value = value.clamp(self.min, self.max);
+    }
+
+    fn step_by(&mut self, delta: i32) -> Option<EventKind> {
+        let old = self.value;
+        self.set_value(self.value.saturating_add(delta));
+        (self.value != old).then_some(EventKind::Changed(self.value))
+    }
+
+    /// The "-" and "+" areas, each as wide as the field is high
+    fn buttons(bounds: &Rectangle) -> (Rectangle, Rectangle) {
+        let side = bounds.size.height.min(bounds.size.width / 3);
+        let size = Size::new(side, bounds.size.height);
+        let minus = Rectangle::new(bounds.top_left, size);
+        let plus = Rectangle::new(
+            Point::new(
+                bounds.top_left.x + (bounds.size.width - side) as i32,
+                bounds.top_left.y,
+            ),
+            size,
+        );
+        (minus, plus)
+    }
+}
+
+impl Widget for NumericField {
+    fn draw<D>(&self, ctx: &DrawContext, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+    {
+        let theme = ctx.theme;
+        let (fill, text_color) = if ctx.state.disabled {
+            (theme.disabled_color, theme.disabled_text_color)
+        } else {
+            (theme.background, theme.text_color)
+        };
+        let (minus, plus) = Self::buttons(&ctx.bounds);
+
+        let text = format!("{}{}", self.value, self.unit);
+        draw_box(&ctx.bounds, fill, &text, text_color, theme, target)?;
+
+        let button_fill = if ctx.state.disabled {
+            theme.disabled_color
+        } else {
+            theme.button_color
+        };
+        draw_box(&minus, button_fill, "-", text_color, theme, target)?;
+        draw_box(&plus, button_fill, "+", text_color, theme, target)?;
+
+        ctx.bounds
+            .into_styled(
+                PrimitiveStyleBuilder::new()
+                    .stroke_color(theme.border_color)
+                    .stroke_width(1)
+                    .build(),
+            )
+            .draw(target)
+    }
+
+    fn touch(&mut self, bounds: &Rectangle, touch: Touch) -> Option<EventKind> {
+        let Touch::Up(point) = touch else {
+            return None;
+        };
+        let (minus, plus) = Self::buttons(bounds);
+        if minus.contains(point) {
+            self.step_by(-self.step)
+        } else if plus.contains(point) {
+            self.step_by(self.step)
+        } else {
+            None
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+
+    /// Steps up, wrapping to the minimum after the maximum
+    fn activate(&mut self) -> Option<EventKind> {
+        if self.value >= self.max {
+            self.value = self.min;
+            Some(EventKind::Changed(self.value))
+        } else {
+            self.step_by(self.step)
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Filled, optionally framed area that groups other widgets.
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyleBuilder};
+use embedded_graphics::Drawable;
+
+use super::{DrawContext, Widget};
+
+pub struct Panel {
+    /// `None` uses the theme background
+    pub fill: Option<Rgb888>,
+    /// Width of the frame in the theme border color, 0 for none
+    pub border_width: u32,
+}
+
+impl Default for Panel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Panel {
+    pub fn new() -> Self {
+        Self {
+            fill: None,
+            border_width: 0,
+        }
+    }
+
+    pub fn with_fill(mut self, fill: Rgb888) -> Self {
+        self.fill = Some(fill);
+        self
+    }
+
+    pub fn with_border(mut self, width: u32) -> Self {
+        self.border_width = width;
+        self
+    }
+}
+
+impl Widget for Panel {
+    fn draw<D>(&self, ctx: &DrawContext, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+    {
+        let style = PrimitiveStyleBuilder::new()
+            .fill_color(self.fill.unwrap_or(ctx.theme.background))
+            .stroke_color(ctx.theme.border_color)
+            .stroke_width(self.border_width)
+            .build();
+        ctx.bounds.into_styled(style).draw(target)
+    }
+}
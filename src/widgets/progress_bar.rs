@@ -0,0 +1,72 @@
+//! Read-only bar filled in proportion to a value.
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::Size;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle};
+use embedded_graphics::Drawable;
+
+use super::{DrawContext, Widget};
+
+pub struct ProgressBar {
+    min: i32,
+    max: i32,
+    value: i32,
+}
+
+impl ProgressBar {
+    /// Panics if `min > max`
+    pub fn new(min: i32, max: i32) -> Self {
+        assert!(min <= max);
+        Self {
+            min,
+            max,
+            value: min,
+        }
+    }
+
+    pub fn with_value(mut self, value: i32) -> Self {
+        self.set_value(value);
+        self
+    }
+
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+
+    pub fn set_value(&mut self, value: i32) {
+        self.value = value.clamp(self.min, self.max);
+    }
+}
+
+impl Widget for ProgressBar {
+    fn draw<D>(&self, ctx: &DrawContext, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+    {
+        let theme = ctx.theme;
+        let bounds = ctx.bounds;
+        let fill = if ctx.state.disabled {
+            theme.disabled_text_color
+        } else {
+            theme.accent_color
+        };
+
+        bounds
+            .into_styled(
+                PrimitiveStyleBuilder::new()
+                    .fill_color(theme.track_color)
+                    .stroke_color(theme.border_color)
+                    .stroke_width(1)
+                    .build(),
+            )
+            .draw(target)?;
+
+        let inner = bounds.offset(-1);
+        let span = (self.max - self.min).max(1) as i64;
+        let width = (self.value - self.min) as i64 * inner.size.width as i64 / span;
+        Rectangle::new(inner.top_left, Size::new(width as u32, inner.size.height))
+            .into_styled(PrimitiveStyle::with_fill(fill))
+            .draw(target)
+    }
+}
@@ -0,0 +1,148 @@
+//! Horizontal slider, dragged or tapped to a value.
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::primitives::{Primitive, PrimitiveStyle, Rectangle};
+use embedded_graphics::Drawable;
+
+use super::{DrawContext, EventKind, Touch, Widget};
+
+/// Width of the knob in pixels
+const KNOB_WIDTH: u32 = 12;
+
+pub struct Slider {
+    min: i32,
+    max: i32,
+    step: i32,
+    value: i32,
+}
+
+impl Slider {
+    /// Panics if `min > max`
+    pub fn new(min: i32, max: i32) -> Self {
+        assert!(min <= max);
+        Self {
+            min,
+            max,
+            step: 1,
+            value: min,
+        }
+    }
+
+    /// Values snap to multiples of `step` above `min`
+    pub fn with_step(mut self, step: i32) -> Self {
+        self.step = step.max(1);
+        self
+    }
+
+    pub fn with_value(mut self, value: i32) -> Self {
+        self.set_value(value);
+        self
+    }
+
+    pub fn value(&self) -> i32 {
+        self.value
+    }
+
+    pub fn range(&self) -> (i32, i32) {
+        (self.min, self.max)
+    }
+
+    pub fn set_value(&mut self, value: i32) {
+        let steps = (value.clamp(self.min, self.max) - self.min + self.step / 2) / self.step;
+        self.value = (self.min + steps * self.step).min(self.max);
+    }
+
+    /// Range of x positions the knob centre moves along
+    fn travel(bounds: &Rectangle) -> (i32, i32) {
+        let start = bounds.top_left.x + KNOB_WIDTH as i32 / 2;
+        let len = bounds.size.width.saturating_sub(KNOB_WIDTH) as i32;
+        (start, len.max(1))
+    }
+
+    fn value_at(&self, bounds: &Rectangle, x: i32) -> i32 {
+        let (start, len) = Self::travel(bounds);
+        let offset = (x - start).clamp(0, len) as i64;
+        let span = (self.max - self.min) as i64;
+        self.min + ((offset * span + len as i64 / 2) / len as i64) as i32
+    }
+
+    fn knob_x(&self, bounds: &Rectangle) -> i32 {
+        let (start, len) = Self::travel(bounds);
+        let span = (self.max - self.min).max(1) as i64;
+        start + ((self.value - self.min) as i64 * len as i64 / span) as i32
+    }
+}
+
+impl Widget for Slider {
+    fn draw<D>(&self, ctx: &DrawContext, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+    {
+        let theme = ctx.theme;
+        let bounds = ctx.bounds;
+        let (accent, track) = if ctx.state.disabled {
+            (theme.disabled_text_color, theme.disabled_color)
+        } else {
+            (theme.accent_color, theme.track_color)
+        };
+
+        // Clear the old knob
+        bounds
+            .into_styled(PrimitiveStyle::with_fill(theme.background))
+            .draw(target)?;
+
+        let track_height = (bounds.size.height / 3).max(2);
+        let track_y = bounds.top_left.y + (bounds.size.height - track_height) as i32 / 2;
+        let knob_x = self.knob_x(&bounds);
+
+        Rectangle::new(
+            Point::new(bounds.top_left.x, track_y),
+            Size::new(bounds.size.width, track_height),
+        )
+        .into_styled(PrimitiveStyle::with_fill(track))
+        .draw(target)?;
+
+        Rectangle::with_corners(
+            Point::new(bounds.top_left.x, track_y),
+            Point::new(knob_x, track_y + track_height as i32 - 1),
+        )
+        .into_styled(PrimitiveStyle::with_fill(accent))
+        .draw(target)?;
+
+        let knob_color = if ctx.state.pressed {
+            theme.pressed_color
+        } else {
+            theme.border_color
+        };
+        Rectangle::new(
+            Point::new(knob_x - KNOB_WIDTH as i32 / 2, bounds.top_left.y),
+            Size::new(KNOB_WIDTH, bounds.size.height),
+        )
+        .into_styled(PrimitiveStyle::with_fill(knob_color))
+        .draw(target)
+    }
+
+    fn touch(&mut self, bounds: &Rectangle, touch: Touch) -> Option<EventKind> {
+        let (Touch::Down(point) | Touch::Move(point) | Touch::Up(point)) = touch;
+
+        let old = self.value;
+        self.set_value(self.value_at(bounds, point.x));
+        (self.value != old).then_some(EventKind::Changed(self.value))
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+
+    /// Steps up, wrapping to the minimum after the maximum
+    fn activate(&mut self) -> Option<EventKind> {
+        if self.value >= self.max {
+            self.value = self.min;
+        } else {
+            self.set_value(self.value + self.step);
+        }
+        Some(EventKind::Changed(self.value))
+    }
+}
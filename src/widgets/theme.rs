@@ -0,0 +1,51 @@
+//! Colors and font shared by all widgets of a tree.
+
+use embedded_graphics::mono_font::iso_8859_14::FONT_10X20;
+use embedded_graphics::mono_font::MonoFont;
+use embedded_graphics::pixelcolor::{Rgb888, RgbColor};
+
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub font: &'static MonoFont<'static>,
+    pub text_color: Rgb888,
+    /// Behind widgets that clear their area, e.g. sliders
+    pub background: Rgb888,
+    /// Button at rest
+    pub button_color: Rgb888,
+    /// Button while a finger is on it
+    pub pressed_color: Rgb888,
+    /// Toggle button that is on
+    pub on_color: Rgb888,
+    /// Toggle button that is off
+    pub off_color: Rgb888,
+    /// Filled part of sliders and progress bars
+    pub accent_color: Rgb888,
+    /// Empty part of sliders and progress bars
+    pub track_color: Rgb888,
+    pub border_color: Rgb888,
+    pub focus_color: Rgb888,
+    pub disabled_color: Rgb888,
+    pub disabled_text_color: Rgb888,
+}
+
+impl Default for Theme {
+    /// Looks like the original on-screen buttons: black text, green when
+    /// on, red when off
+    fn default() -> Self {
+        Self {
+            font: &FONT_10X20,
+            text_color: Rgb888::BLACK,
+            background: Rgb888::WHITE,
+            button_color: Rgb888::new(0xC0, 0xC0, 0xC0),
+            pressed_color: Rgb888::new(0x80, 0x80, 0x80),
+            on_color: Rgb888::GREEN,
+            off_color: Rgb888::RED,
+            accent_color: Rgb888::new(0x00, 0x80, 0xFF),
+            track_color: Rgb888::new(0xD0, 0xD0, 0xD0),
+            border_color: Rgb888::BLACK,
+            focus_color: Rgb888::BLUE,
+            disabled_color: Rgb888::new(0xE0, 0xE0, 0xE0),
+            disabled_text_color: Rgb888::new(0x90, 0x90, 0x90),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Button with an on and an off state, green and red in the default theme.
+
+use alloc::format;
+use alloc::string::String;
+
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::primitives::Rectangle;
+
+use super::{draw_box, DrawContext, EventKind, Touch, Widget};
+
+pub struct ToggleButton {
+    pub text: String,
+    pub on: bool,
+    /// Appends ": ON" or ": OFF" to the text
+    pub state_text: bool,
+    /// Only [`super::WidgetTree::set_on`] changes the state, a touch just
+    /// asks for it. For buttons that mirror something outside, e.g. an
+    /// output pin that reports its level back.
+    pub external: bool,
+}
+
+impl ToggleButton {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            on: false,
+            state_text: false,
+            external: false,
+        }
+    }
+
+    pub fn with_state_text(mut self) -> Self {
+        self.state_text = true;
+        self
+    }
+
+    pub fn external(mut self) -> Self {
+        self.external = true;
+        self
+    }
+
+    fn toggle(&mut self) -> Option<EventKind> {
+        let requested = !self.on;
+        if !self.external {
+            self.on = requested;
+        }
+        Some(EventKind::Toggled(requested))
+    }
+}
+
+impl Widget for ToggleButton {
+    fn draw<D>(&self, ctx: &DrawContext, target: &mut D) -> Result<(), D::Error>
+    where
+        D: DrawTarget<Color = Rgb888>,
+    {
+        let theme = ctx.theme;
+        let (fill, text_color) = match (ctx.state.disabled, self.on) {
+            (true, _) => (theme.disabled_color, theme.disabled_text_color),
+            (false, true) => (theme.on_color, theme.text_color),
+            (false, false) => (theme.off_color, theme.text_color),
+        };
+
+        if self.state_text {
+            let state = if self.on { "ON" } else { "OFF" };
+            let text = format!("{}: {}", self.text, state);
+            draw_box(&ctx.bounds, fill, &text, text_color, theme, target)
+        } else {
+            draw_box(&ctx.bounds, fill, &self.text, text_color, theme, target)
+        }
+    }
+
+    fn touch(&mut self, bounds: &Rectangle, touch: Touch) -> Option<EventKind> {
+        match touch {
+            Touch::Up(point) if bounds.contains(point) => self.toggle(),
+            _ => None,
+        }
+    }
+
+    fn is_interactive(&self) -> bool {
+        true
+    }
+
+    fn activate(&mut self) -> Option<EventKind> {
+        self.toggle()
+    }
+}
@@ -0,0 +1,196 @@
+//! Touch calibration and display rotation.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+
+use std::fs;
+use std::path::PathBuf;
+
+use embedded_graphics::geometry::{Point, Size};
+
+use f7disco_rs::calibration::{Calibration, CalibrationRoutine, Transform, PANEL_SIZE};
+use f7disco_rs::rotation::Rotation;
+use f7disco_rs::screens::{self, ScreenDescription};
+use f7disco_rs::sim::NavigatorSim;
+
+const ROTATIONS: [Rotation; 4] = [
+    Rotation::Deg0,
+    Rotation::Deg90,
+    Rotation::Deg180,
+    Rotation::Deg270,
+];
+
+/// How a slightly skewed panel reports a finger at a panel pixel: axes
+/// swapped like the FT5336, scaled, shifted and a bit sheared
+const SENSOR: Calibration = Calibration {
+    a: 1_500,
+    b: 70_000,
+    c: -400_000,
+    d: 62_000,
+    e: -1_200,
+    f: 900_000,
+};
+
+fn grid() -> impl Iterator<Item = Point> {
+    (0..PANEL_SIZE.width as i32).step_by(40).flat_map(|x| {
+        (0..PANEL_SIZE.height as i32)
+            .step_by(30)
+            .map(move |y| Point::new(x, y))
+    })
+}
+
+fn assert_close(actual: Point, expected: Point, what: &str) {
+    let delta = actual - expected;
+    assert!(
+        delta.x.abs() <= 1 && delta.y.abs() <= 1,
+        "{what}: {actual} instead of {expected}"
+    );
+}
+
+#[test]
+fn three_samples_give_the_map() {
+    let raw = [
+        Point::new(100, 50),
+        Point::new(3000, 1800),
+        Point::new(1500, 3500),
+    ];
+    let panel = raw.map(|p| SENSOR.apply(p));
+
+    let calibration = Calibration::from_samples(raw, panel).unwrap();
+    for p in [
+        Point::new(0, 0),
+        Point::new(4000, 4000),
+        Point::new(2000, 700),
+    ] {
+        assert_close(calibration.apply(p), SENSOR.apply(p), "solved map");
+    }
+
+    // On a line, nothing to solve
+    let line = [Point::new(0, 0), Point::new(100, 100), Point::new(200, 200)];
+    assert_eq!(Calibration::from_samples(line, panel), None);
+}
+
+#[test]
+fn routine_corrects_the_current_calibration() {
+    // The panel of SENSOR read with the default calibration: the taps miss
+    // by a lot, the new calibration undoes the panel
+    let inverse = Calibration::from_samples(
+        [Point::new(0, 0), Point::new(479, 0), Point::new(0, 271)].map(|p| SENSOR.apply(p)),
+        [Point::new(0, 0), Point::new(479, 0), Point::new(0, 271)],
+    )
+    .unwrap();
+
+    for rotation in ROTATIONS {
+        let current = Transform {
+            calibration: Calibration::DEFAULT,
+            rotation,
+        };
+        let mut routine = CalibrationRoutine::new(rotation);
+        while let Some(target) = routine.target() {
+            let finger = rotation.to_physical(target, PANEL_SIZE);
+            let raw = inverse.apply(finger);
+            routine.tap(current.apply(raw));
+        }
+
+        let calibration = routine.finish(&current.calibration).unwrap();
+        let fixed = Transform {
+            calibration,
+            rotation,
+        };
+        for finger in grid() {
+            let raw = inverse.apply(finger);
+            assert_close(
+                fixed.apply(raw),
+                rotation.to_logical(finger, PANEL_SIZE),
+                &format!("{rotation:?}"),
+            );
+        }
+    }
+}
+
+#[test]
+fn routine_needs_three_taps() {
+    let mut routine = CalibrationRoutine::new(Rotation::Deg0);
+    routine.tap(Point::new(48, 40));
+    routine.tap(Point::new(432, 136));
+    assert!(routine.target().is_some());
+    assert_eq!(routine.finish(&Calibration::DEFAULT), None);
+
+    // All three on the same spot
+    routine.tap(Point::new(48, 40));
+    routine.restart();
+    for _ in 0..3 {
+        routine.tap(Point::new(100, 100));
+    }
+    assert_eq!(routine.target(), None);
+    assert_eq!(routine.finish(&Calibration::DEFAULT), None);
+}
+
+#[test]
+fn stored_calibration() {
+    let bytes = SENSOR.to_bytes();
+    assert_eq!(Calibration::from_bytes(&bytes), Some(SENSOR));
+
+    // Erased flash
+    assert_eq!(Calibration::from_bytes(&[0xFF; 32]), None);
+
+    let mut damaged = bytes;
+    damaged[10] ^= 0x01;
+    assert_eq!(Calibration::from_bytes(&damaged), None);
+}
+
+#[test]
+fn rotation_round_trip() {
+    for rotation in ROTATIONS {
+        let size = rotation.size(PANEL_SIZE);
+        for p in grid() {
+            let logical = rotation.to_logical(p, PANEL_SIZE);
+            assert!(
+                logical.x >= 0
+                    && logical.y >= 0
+                    && logical.x < size.width as i32
+                    && logical.y < size.height as i32,
+                "{rotation:?}: {p} -> {logical}"
+            );
+            assert_eq!(rotation.to_physical(logical, PANEL_SIZE), p, "{rotation:?}");
+        }
+    }
+    assert_eq!(Rotation::Deg90.size(PANEL_SIZE), Size::new(272, 480));
+}
+
+fn med_chamber(rotation: Rotation) -> NavigatorSim {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("screens/med_chamber.toml");
+    let description = ScreenDescription::parse(&fs::read_to_string(path).unwrap()).unwrap();
+    NavigatorSim::with_rotation(screens::operator_panel(&description), rotation)
+}
+
+#[test]
+fn upside_down() {
+    let upright = med_chamber(Rotation::Deg0).frame();
+    let mut sim = med_chamber(Rotation::Deg180);
+
+    let frame = sim.frame();
+    let (w, h) = (frame.width, frame.height);
+    for y in 0..h {
+        for x in 0..w {
+            assert_eq!(
+                frame.pixel(x, y),
+                upright.pixel(w - 1 - x, h - 1 - y),
+                "pixel {x}, {y}"
+            );
+        }
+    }
+
+    // A finger on RF and then "Yes" as they appear on the turned panel,
+    // through a touch transform with the same rotation
+    let transform = Transform {
+        calibration: Calibration::IDENTITY,
+        rotation: Rotation::Deg180,
+    };
+    for button in [Point::new(236, 129), Point::new(180, 164)] {
+        let finger = Rotation::Deg180.to_physical(button, PANEL_SIZE);
+        sim.touch(transform.apply(finger));
+    }
+    assert_eq!(sim.pins(), [true, false, false, false]);
+}
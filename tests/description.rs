@@ -0,0 +1,65 @@
+//! Widget errors of screen descriptions, as reported by `screen-check`.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+
+use embedded_graphics::geometry::Size;
+
+use f7disco_rs::screens::ScreenDescription;
+
+const SCREEN: &str = "[screen]\nname = \"Widgets\"\n";
+
+fn button(rect: &str) -> String {
+    format!("{SCREEN}[[widget]]\ntype = \"button\"\ntext = \"A\"\nrect = {rect}\n")
+}
+
+fn error(text: &str, size: Size) -> Option<String> {
+    ScreenDescription::parse_for(text, size)
+        .err()
+        .map(|e| e.to_string())
+}
+
+#[test]
+fn rects() {
+    let board = Size::new(480, 272);
+    for rect in ["[0, 0, 480, 272]", "[470, 262, 10, 10]"] {
+        assert_eq!(error(&button(rect), board), None, "{rect}");
+    }
+
+    for (rect, message) in [
+        (
+            "[0, 0, 10]",
+            "line 6: expected `rect = [x, y, width, height]`",
+        ),
+        ("[0, 0, 0, 10]", "line 3: `rect` is empty"),
+        (
+            "[471, 0, 10, 10]",
+            "line 3: `rect` is outside the 480x272 panel",
+        ),
+        (
+            "[0, 0, 10, 273]",
+            "line 3: `rect` is outside the 480x272 panel",
+        ),
+        ("[-1, 0, 10, 10]", "line 6: `rect` is out of range"),
+        ("[2147483647, 0, 10, 10]", "line 6: `rect` is out of range"),
+        ("[0, 0, 4294967296, 10]", "line 6: `rect` is out of range"),
+    ] {
+        assert_eq!(
+            error(&button(rect), board),
+            Some(message.to_string()),
+            "{rect}"
+        );
+    }
+}
+
+#[test]
+fn panel_sizes() {
+    let rect = button("[65535, 65535, 65535, 65535]");
+    assert_eq!(
+        error(&rect, Size::new(800, 480)),
+        Some("line 3: `rect` is outside the 800x480 panel".to_string())
+    );
+    // No overflow either way
+    assert_eq!(error(&rect, Size::new(u32::MAX, u32::MAX)), None);
+}
@@ -0,0 +1,202 @@
+//! Gesture recognition against touch traces in `tests/traces/`.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+//!
+//! A trace has one line per read of the touch panel, `t id:x,y ...`, with
+//! the time in milliseconds and every finger down. The touch task logs
+//! these lines at trace level, so new traces are recorded on the board with
+//! `DEFMT_LOG=f7disco_rs::tasks=trace`.
+
+use std::fs;
+use std::path::PathBuf;
+
+use embedded_graphics::geometry::Point;
+
+use f7disco_rs::calibration::Transform;
+use f7disco_rs::gesture::{Direction, Gesture, Recognizer};
+use f7disco_rs::multitouch::{
+    parse_registers, Contact, Contacts, TouchEvent, Tracker, REGISTERS_LEN,
+};
+
+fn load(name: &str) -> Vec<(u32, Contacts)> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/traces")
+        .join(name)
+        .with_extension("trace");
+    let text = fs::read_to_string(&path).unwrap();
+
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            let t = fields.next().unwrap().parse().unwrap();
+            let contacts = fields
+                .map(|field| {
+                    let (id, xy) = field.split_once(':').unwrap();
+                    let (x, y) = xy.split_once(',').unwrap();
+                    Contact {
+                        id: id.parse().unwrap(),
+                        point: Point::new(x.parse().unwrap(), y.parse().unwrap()),
+                    }
+                })
+                .collect();
+            (t, contacts)
+        })
+        .collect()
+}
+
+/// Plays a trace like the touch task does
+fn replay(name: &str) -> (Vec<TouchEvent>, Vec<Gesture>) {
+    let mut tracker = Tracker::default();
+    let mut recognizer = Recognizer::default();
+    let mut events = Vec::new();
+    let mut gestures = Vec::new();
+
+    for (now, contacts) in load(name) {
+        for event in tracker.update(&contacts) {
+            events.push(event);
+            gestures.extend(recognizer.event(event, now));
+        }
+        gestures.extend(recognizer.poll(now));
+    }
+    (events, gestures)
+}
+
+fn gestures(name: &str) -> Vec<Gesture> {
+    replay(name).1
+}
+
+#[test]
+fn events_have_ids() {
+    let (events, _) = replay("pinch_out");
+
+    let downs: Vec<_> = events
+        .iter()
+        .filter(|e| matches!(e, TouchEvent::Down { .. }))
+        .map(|e| e.id())
+        .collect();
+    let ups: Vec<_> = events
+        .iter()
+        .filter(|e| matches!(e, TouchEvent::Up { .. }))
+        .map(|e| e.id())
+        .collect();
+    assert_eq!(downs, [0, 1]);
+    assert_eq!(ups, [0, 1]);
+}
+
+#[test]
+fn tap() {
+    let gestures = gestures("tap");
+    assert!(
+        matches!(gestures[..], [Gesture::Tap(p)] if (p - Point::new(236, 128)).x.abs() <= 1),
+        "{gestures:?}"
+    );
+}
+
+#[test]
+fn double_tap() {
+    let gestures = gestures("double_tap");
+    assert!(
+        matches!(
+            gestures[..],
+            [Gesture::Tap(_), Gesture::DoubleTap(_), Gesture::Tap(_)]
+        ),
+        "{gestures:?}"
+    );
+}
+
+#[test]
+fn long_press() {
+    let (events, gestures) = replay("long_press");
+    assert!(
+        matches!(gestures[..], [Gesture::LongPress(_)]),
+        "{gestures:?}"
+    );
+    // The jitter of the held finger still comes through as moves
+    assert!(events.iter().any(|e| matches!(e, TouchEvent::Move { .. })));
+}
+
+#[test]
+fn swipes() {
+    for (trace, expected) in [
+        ("swipe_left", Direction::Left),
+        ("swipe_down", Direction::Down),
+    ] {
+        let gestures = gestures(trace);
+        assert!(
+            matches!(gestures[..], [Gesture::Swipe { direction, .. }] if direction == expected),
+            "{trace}: {gestures:?}"
+        );
+    }
+}
+
+#[test]
+fn drag_is_no_gesture() {
+    assert_eq!(gestures("drag"), []);
+}
+
+#[test]
+fn pinches() {
+    for (trace, zoom_in) in [("pinch_out", true), ("pinch_in", false)] {
+        let gestures = gestures(trace);
+        assert!(!gestures.is_empty(), "{trace}: no pinch");
+
+        // Lifting one finger after the other is no tap
+        let mut last = None;
+        for gesture in &gestures {
+            let Gesture::Pinch {
+                center,
+                start_distance,
+                distance,
+            } = *gesture
+            else {
+                panic!("{trace}: {gestures:?}");
+            };
+            assert!(
+                (center - Point::new(240, 136)).x.abs() <= 4,
+                "{trace}: {center}"
+            );
+            assert_eq!(distance > start_distance, zoom_in, "{trace}: {gesture:?}");
+            last = Some((start_distance, distance));
+        }
+
+        let (start, end) = last.unwrap();
+        if zoom_in {
+            assert!(end > 3 * start, "{trace}: {start} -> {end}");
+        } else {
+            assert!(2 * end < start, "{trace}: {start} -> {end}");
+        }
+    }
+}
+
+#[test]
+fn registers() {
+    // ID 0 in contact, ID 2 just pressed down, then a point being lifted
+    let mut registers = [0xFF; REGISTERS_LEN];
+    registers[0] = 0x03;
+    registers[1..7].copy_from_slice(&[0x80, 0x88, 0x01, 0x2C, 0x10, 0x00]);
+    registers[7..13].copy_from_slice(&[0x00, 0x40, 0x20, 0x64, 0x20, 0x00]);
+    registers[13..19].copy_from_slice(&[0x40, 0x10, 0x10, 0x10, 0x00, 0x00]);
+
+    // In controller coordinates, x and y swapped against the LCD
+    let contacts = parse_registers(&registers);
+    assert_eq!(
+        contacts[..],
+        [
+            Contact {
+                id: 0,
+                point: Point::new(136, 300)
+            },
+            Contact {
+                id: 2,
+                point: Point::new(64, 100)
+            },
+        ]
+    );
+    assert_eq!(
+        Transform::DEFAULT.apply(contacts[0].point),
+        Point::new(300, 136)
+    );
+}
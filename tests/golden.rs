@@ -0,0 +1,272 @@
+//! Golden-image tests: every screen is rendered on the host and compared
+//! with the reference PNG in `tests/golden/`.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+//!
+//! On a mismatch the rendering and a diff image (differences in red) are
+//! written to `target/golden/`. After an intended change, regenerate the
+//! references with `GOLDEN_BLESS=1` and review them before committing.
+
+use std::env;
+use std::fs;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+
+use embedded_graphics::geometry::Point;
+
+use f7disco_rs::framebuffer::{DisplayBuffer, LCD_HEIGHT, LCD_WIDTH};
+use f7disco_rs::netconfig::{NetConfig, NetState};
+use f7disco_rs::screens::{self, kolibri_demo, ControlScreen, Navigator, ScreenDescription};
+use f7disco_rs::sim::{Frame, MedChamberSim, Mismatch, NavigatorSim, Tolerance};
+
+/// Font rendering is exact, a few pixels of slack keep the tests from
+/// failing on rounding in the background image conversion
+const TOLERANCE: Tolerance = Tolerance {
+    channel: 8,
+    pixels: 16,
+};
+
+// Centres of the Med Chamber buttons
+const RF: Point = Point::new(236, 129);
+const DBM_43: Point = Point::new(100, 231);
+const DBM_45: Point = Point::new(236, 231);
+const DBM_47: Point = Point::new(372, 231);
+
+// Centres of the "Enable RF?" dialog buttons, the tabs and the settings of
+// the operator panel
+const YES: Point = Point::new(180, 164);
+const NO: Point = Point::new(300, 164);
+const TAB_CONTROL: Point = Point::new(63, 11);
+const TAB_SETTINGS: Point = Point::new(189, 11);
+const TAB_DIAGNOSTICS: Point = Point::new(316, 11);
+const CONFIRM_OUTPUTS: Point = Point::new(240, 77);
+const ABOUT: Point = Point::new(170, 147);
+const NETWORK: Point = Point::new(310, 147);
+
+// Centres of the network settings' gateway field and keypad
+const GATEWAY: Point = Point::new(145, 118);
+const KEY_2: Point = Point::new(380, 46);
+const KEY_4: Point = Point::new(318, 80);
+const KEY_5: Point = Point::new(380, 80);
+const DELETE: Point = Point::new(380, 185);
+const SAVE: Point = Point::new(76, 187);
+
+// Centres of the EMC PA buttons
+const DB_0_5: Point = Point::new(146, 123);
+const DB_2: Point = Point::new(146, 193);
+
+fn reference_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name)
+        .with_extension("png")
+}
+
+fn output_path(name: &str, suffix: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/golden");
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(format!("{name}{suffix}.png"))
+}
+
+fn check(name: &str, actual: &Frame) {
+    let reference = reference_path(name);
+
+    if env::var_os("GOLDEN_BLESS").is_some_and(|v| !v.is_empty()) {
+        fs::create_dir_all(reference.parent().unwrap()).unwrap();
+        actual.save(&reference).unwrap();
+        return;
+    }
+
+    let expected = match Frame::load_png(&reference) {
+        Ok(expected) => expected,
+        Err(e) => {
+            let out = output_path(name, "");
+            actual.save(&out).unwrap();
+            panic!(
+                "{name}: no reference at {} ({e}), rendering written to {}. \
+                 Rerun with GOLDEN_BLESS=1 to accept it.",
+                reference.display(),
+                out.display()
+            );
+        }
+    };
+
+    if let Err(mismatch) = actual.compare(&expected, TOLERANCE) {
+        let out = output_path(name, ".actual");
+        actual.save(&out).unwrap();
+        if let Mismatch::Pixels { diff, .. } = &mismatch {
+            diff.save(&output_path(name, ".diff")).unwrap();
+        }
+        panic!("{name}: {mismatch}, rendering written to {}", out.display());
+    }
+}
+
+fn med_chamber(touches: &[Point]) -> Frame {
+    let mut chamber = MedChamberSim::new();
+    for &point in touches {
+        chamber.touch(point);
+    }
+    chamber.frame()
+}
+
+#[test]
+fn med_chamber_initial() {
+    check("med_chamber_initial", &med_chamber(&[]));
+}
+
+#[test]
+fn med_chamber_rf_on() {
+    check("med_chamber_rf_on", &med_chamber(&[RF]));
+}
+
+#[test]
+fn med_chamber_43_dbm() {
+    check("med_chamber_43_dbm", &med_chamber(&[RF, DBM_43]));
+}
+
+#[test]
+fn med_chamber_45_dbm() {
+    check("med_chamber_45_dbm", &med_chamber(&[RF, DBM_45]));
+}
+
+#[test]
+fn med_chamber_47_dbm() {
+    check("med_chamber_47_dbm", &med_chamber(&[RF, DBM_47]));
+}
+
+fn description(file: &str) -> ScreenDescription {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("screens")
+        .join(file);
+    let text = fs::read_to_string(&path).unwrap();
+    ScreenDescription::parse(&text).unwrap_or_else(|e| panic!("{}: {e}", path.display()))
+}
+
+/// The screen of `file` alone, without tabs
+fn described(file: &str, touches: &[Point]) -> Frame {
+    let screen = ControlScreen::new(&description(file));
+    let mut sim = NavigatorSim::new(Navigator::new(Box::new(screen)));
+    for &point in touches {
+        sim.touch(point);
+    }
+    sim.frame()
+}
+
+/// The firmware GUI for the Med Chamber
+fn operator_panel(touches: &[Point]) -> NavigatorSim {
+    let mut sim = NavigatorSim::new(screens::operator_panel(&description("med_chamber.toml")));
+    for &point in touches {
+        sim.touch(point);
+    }
+    sim
+}
+
+/// The description has to reproduce the hand-written screen exactly, once
+/// RF is confirmed
+#[test]
+fn med_chamber_description() {
+    for touches in [
+        &[][..],
+        &[RF, YES],
+        &[RF, YES, DBM_43],
+        &[RF, YES, DBM_45, DBM_47],
+    ] {
+        let without_dialog: Vec<Point> = touches.iter().copied().filter(|&p| p != YES).collect();
+        assert!(
+            described("med_chamber.toml", touches) == med_chamber(&without_dialog),
+            "screens/med_chamber.toml differs from MedChamber after {touches:?}"
+        );
+    }
+}
+
+#[test]
+fn emc_pa_initial() {
+    check("emc_pa_initial", &described("emc_pa.toml", &[]));
+}
+
+#[test]
+fn emc_pa_2_5_db() {
+    check("emc_pa_2_5_db", &described("emc_pa.toml", &[DB_0_5, DB_2]));
+}
+
+#[test]
+fn operator_panel_confirm_rf() {
+    check("operator_panel_confirm_rf", &operator_panel(&[RF]).frame());
+}
+
+#[test]
+fn operator_panel_settings() {
+    check(
+        "operator_panel_settings",
+        &operator_panel(&[TAB_SETTINGS]).frame(),
+    );
+}
+
+#[test]
+fn operator_panel_about() {
+    check(
+        "operator_panel_about",
+        &operator_panel(&[TAB_SETTINGS, ABOUT]).frame(),
+    );
+}
+
+/// The gateway changed to .254 and saved, with a DHCP lease
+#[test]
+fn operator_panel_network() {
+    let mut panel = operator_panel(&[]);
+    panel.set_network(NetState {
+        mac: [0x02, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E],
+        config: NetConfig::DEFAULT,
+        link_up: true,
+        address: Some((Ipv4Addr::new(192, 168, 1, 37), 24)),
+        fallback: false,
+    });
+    for point in [
+        TAB_SETTINGS,
+        NETWORK,
+        GATEWAY,
+        DELETE,
+        KEY_2,
+        KEY_5,
+        KEY_4,
+        SAVE,
+    ] {
+        panel.touch(point);
+    }
+    check("operator_panel_network", &panel.frame());
+}
+
+#[test]
+fn operator_panel_diagnostics() {
+    let panel = operator_panel(&[RF, YES, DBM_45, TAB_DIAGNOSTICS]);
+    check("operator_panel_diagnostics", &panel.frame());
+}
+
+/// Not an image, but cheap to check next to them
+#[test]
+fn rf_needs_confirmation() {
+    assert_eq!(operator_panel(&[RF]).pins(), [false; 4]);
+    assert_eq!(operator_panel(&[RF, NO]).pins(), [false; 4]);
+    assert_eq!(
+        operator_panel(&[RF, YES]).pins(),
+        [true, false, false, false]
+    );
+    // Switching off needs no confirmation
+    assert_eq!(operator_panel(&[RF, YES, RF]).pins(), [false; 4]);
+    // Unless confirmations are off in the settings
+    let panel = operator_panel(&[TAB_SETTINGS, CONFIRM_OUTPUTS, TAB_CONTROL, RF]);
+    assert_eq!(panel.pins(), [true, false, false, false]);
+}
+
+#[test]
+fn kolibri_demo() {
+    let mut pixels = vec![0u32; LCD_WIDTH as usize * LCD_HEIGHT as usize];
+    let mut buffer: DisplayBuffer =
+        DisplayBuffer::new(&mut pixels, LCD_WIDTH as i32, LCD_HEIGHT as i32);
+
+    kolibri_demo::draw(&mut buffer);
+
+    check("kolibri_demo", &Frame::from_buffer(&buffer));
+}
@@ -0,0 +1,308 @@
+//! HTTP request parser and router against requests in `tests/requests/`.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+//!
+//! The requests were captured from curl, Firefox on the web page and
+//! Python's urllib, byte for byte with their CRLF line ends.
+
+use std::fs;
+use std::path::PathBuf;
+
+use f7disco_rs::http::{self, Error, Method, Request, Route};
+use f7disco_rs::interlock::{Interlock, Rule, Violation};
+use f7disco_rs::outputs::{Levels, OutputId};
+
+const D0: OutputId = OutputId(0);
+const D1: OutputId = OutputId(1);
+const D2: OutputId = OutputId(2);
+
+fn load(name: &str) -> Vec<u8> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/requests")
+        .join(name)
+        .with_extension("http");
+    fs::read(path).unwrap()
+}
+
+/// Parses all of `bytes` as one request
+fn parse(bytes: &[u8]) -> Request<'_> {
+    let (request, len) = Request::parse(bytes).unwrap().unwrap();
+    assert_eq!(len, bytes.len());
+    request
+}
+
+/// Answers `bytes` for the outputs at `levels`, returns the status line
+/// and the body
+fn serve(bytes: &[u8], levels: Levels, interlock: &Interlock) -> (String, String) {
+    let request = parse(bytes);
+    let route = http::route(&request, levels, interlock);
+    let mut json = heapless::String::<256>::new();
+    let response = http::respond(route, request.path, levels, &mut json).unwrap();
+    let mut head = String::new();
+    response.write_head(request.keep_alive, &mut head).unwrap();
+    let status_line = head.lines().next().unwrap().to_string();
+    (
+        status_line,
+        String::from_utf8(response.body.to_vec()).unwrap(),
+    )
+}
+
+#[test]
+fn captured_requests() {
+    let no_rules = Interlock::default();
+    for (name, method, path, keep_alive, route) in [
+        (
+            "curl_outputs",
+            Method::Get,
+            "/api/outputs",
+            true,
+            Ok(Route::Outputs),
+        ),
+        (
+            "curl_set_d1",
+            Method::Put,
+            "/api/outputs/d1",
+            true,
+            Ok(Route::SetOutput(D1, true)),
+        ),
+        ("curl_head_page", Method::Head, "/", true, Ok(Route::Page)),
+        ("firefox_page", Method::Get, "/", true, Ok(Route::Page)),
+        (
+            "firefox_favicon",
+            Method::Get,
+            "/favicon.ico",
+            true,
+            Err(Error::Status(http::NOT_FOUND)),
+        ),
+        (
+            "firefox_poll",
+            Method::Get,
+            "/api/outputs",
+            true,
+            Ok(Route::Outputs),
+        ),
+        (
+            "firefox_toggle_d2",
+            Method::Put,
+            "/api/outputs/d2",
+            true,
+            Ok(Route::SetOutput(D2, false)),
+        ),
+        (
+            "python_output_d0",
+            Method::Get,
+            "/api/outputs/D0",
+            false,
+            Ok(Route::Output(D0)),
+        ),
+    ] {
+        let bytes = load(name);
+        let request = parse(&bytes);
+        assert_eq!(
+            (request.method, request.path, request.keep_alive),
+            (method, path, keep_alive),
+            "{name}"
+        );
+        assert_eq!(http::route(&request, Levels(0), &no_rules), route, "{name}");
+    }
+}
+
+#[test]
+fn incomplete_requests() {
+    for name in ["firefox_page", "firefox_toggle_d2"] {
+        let bytes = load(name);
+        for len in 0..bytes.len() {
+            assert_eq!(Request::parse(&bytes[..len]), Ok(None), "{name} {len}");
+        }
+    }
+}
+
+#[test]
+fn pipelined_requests() {
+    let mut bytes = load("curl_set_d1");
+    let first = bytes.len();
+    bytes.extend(load("curl_outputs"));
+
+    let (request, len) = Request::parse(&bytes).unwrap().unwrap();
+    assert_eq!((len, request.body), (first, &b"{\"on\":true}"[..]));
+    let (request, len) = Request::parse(&bytes[first..]).unwrap().unwrap();
+    assert_eq!((len, request.path), (bytes.len() - first, "/api/outputs"));
+}
+
+#[test]
+fn lenient_parsing() {
+    // HTTP/1.0 closes unless asked not to, lines may end with LF only,
+    // and an empty line before the request is skipped
+    let request = parse(b"\r\nGET / HTTP/1.0\nconnection: Keep-Alive\n\n");
+    assert_eq!((request.path, request.keep_alive), ("/", true));
+    let request = parse(b"GET / HTTP/1.0\r\n\r\n");
+    assert!(!request.keep_alive);
+    let request = parse(b"DELETE /api/outputs/d1 HTTP/1.1\r\nConnection: TE, close\r\n\r\n");
+    assert_eq!((request.method, request.keep_alive), (Method::Other, false));
+}
+
+#[test]
+fn invalid_requests() {
+    for (bytes, status) in [
+        (&b"GET /\r\n\r\n"[..], http::BAD_REQUEST),
+        (b"GET  / HTTP/1.1\r\n\r\n", http::BAD_REQUEST),
+        (b"GET api HTTP/1.1\r\n\r\n", http::BAD_REQUEST),
+        (b"GET / HTTP/2.0\r\n\r\n", http::VERSION_NOT_SUPPORTED),
+        (b"GET / HTTP/1.1\r\nHost\r\n\r\n", http::BAD_REQUEST),
+        (b"GET / HTTP/1.1\r\nHost : panel\r\n\r\n", http::BAD_REQUEST),
+        (b"GET / HTTP/1.1\r\nX: \xff\r\n\r\n", http::BAD_REQUEST),
+        (
+            b"PUT /api/outputs/d1 HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 3\r\n\r\n",
+            http::BAD_REQUEST,
+        ),
+        (
+            b"PUT /api/outputs/d1 HTTP/1.1\r\nContent-Length: -1\r\n\r\n",
+            http::BAD_REQUEST,
+        ),
+        (
+            b"PUT /api/outputs/d1 HTTP/1.1\r\nContent-Length: 100000\r\n\r\n",
+            http::PAYLOAD_TOO_LARGE,
+        ),
+        (
+            b"PUT /api/outputs/d1 HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n",
+            http::NOT_IMPLEMENTED,
+        ),
+    ] {
+        assert_eq!(
+            Request::parse(bytes),
+            Err(status),
+            "{}",
+            String::from_utf8_lossy(bytes)
+        );
+    }
+}
+
+#[test]
+fn router() {
+    let no_rules = Interlock::default();
+    let route = |request: &[u8]| http::route(&parse(request), Levels(0), &no_rules);
+    let status = |status| Err(Error::Status(status));
+
+    assert_eq!(route(b"GET /index.html HTTP/1.1\r\n\r\n"), Ok(Route::Page));
+    assert_eq!(
+        route(b"GET /api/outputs/D3 HTTP/1.1\r\n\r\n"),
+        Ok(Route::Output(OutputId(3)))
+    );
+    assert_eq!(
+        route(b"GET /api/outputs/d9 HTTP/1.1\r\n\r\n"),
+        status(http::NOT_FOUND)
+    );
+    assert_eq!(
+        route(b"GET /api/outputs/ HTTP/1.1\r\n\r\n"),
+        status(http::NOT_FOUND)
+    );
+    assert_eq!(
+        route(b"PUT / HTTP/1.1\r\n\r\n"),
+        status(http::METHOD_NOT_ALLOWED)
+    );
+    assert_eq!(
+        route(b"DELETE /api/outputs/d1 HTTP/1.1\r\n\r\n"),
+        status(http::METHOD_NOT_ALLOWED)
+    );
+
+    for body in ["", "on", "{\"on\":1}", "{\"off\":true}", "{\"on\":true"] {
+        let request = format!(
+            "PUT /api/outputs/d1 HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        assert_eq!(
+            route(request.as_bytes()),
+            status(http::BAD_REQUEST),
+            "{body}"
+        );
+    }
+    let request = b"PUT /api/outputs/d1 HTTP/1.1\r\nContent-Length: 16\r\n\r\n { \"on\" : true }";
+    assert_eq!(route(request), Ok(Route::SetOutput(D1, true)));
+}
+
+#[test]
+fn responses() {
+    let no_rules = Interlock::default();
+    let levels = Levels(0b0010);
+
+    assert_eq!(
+        serve(&load("curl_outputs"), levels, &no_rules),
+        (
+            "HTTP/1.1 200 OK".to_string(),
+            "{\"outputs\":[{\"name\":\"D0\",\"on\":false},{\"name\":\"D1\",\"on\":true},\
+             {\"name\":\"D2\",\"on\":false},{\"name\":\"D3\",\"on\":false}]}"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        serve(&load("curl_set_d1"), levels, &no_rules),
+        (
+            "HTTP/1.1 200 OK".to_string(),
+            "{\"name\":\"D1\",\"on\":true}".to_string()
+        )
+    );
+    let (status, body) = serve(&load("firefox_page"), levels, &no_rules);
+    assert_eq!(
+        (status.as_str(), body.as_str()),
+        ("HTTP/1.1 200 OK", http::PAGE)
+    );
+    assert_eq!(
+        serve(&load("firefox_favicon"), levels, &no_rules),
+        (
+            "HTTP/1.1 404 Not Found".to_string(),
+            "{\"error\":\"Not Found\"}".to_string()
+        )
+    );
+}
+
+#[test]
+fn response_head() {
+    let request = parse(b"POST /api/outputs/d1 HTTP/1.1\r\nConnection: close\r\n\r\n");
+    let route = http::route(&request, Levels(0), &Interlock::default());
+    let mut json = heapless::String::<256>::new();
+    let response = http::respond(route, request.path, Levels(0), &mut json).unwrap();
+    let mut head = String::new();
+    response.write_head(request.keep_alive, &mut head).unwrap();
+    assert_eq!(
+        head,
+        "HTTP/1.1 405 Method Not Allowed\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: 30\r\n\
+         Cache-Control: no-store\r\n\
+         Allow: GET, HEAD, PUT\r\n\
+         Connection: close\r\n\
+         \r\n"
+    );
+    assert_eq!(response.body, b"{\"error\":\"Method Not Allowed\"}");
+}
+
+#[test]
+fn interlock_conflicts() {
+    let interlock = Interlock::new(vec![Rule::Requires {
+        outputs: vec![D1, D2],
+        requires: D0,
+        delay_ms: 100,
+    }]);
+    let bytes = load("curl_set_d1");
+    let request = parse(&bytes);
+    assert_eq!(
+        http::route(&request, Levels(0), &interlock),
+        Err(Error::Interlock(Violation::Requires {
+            output: D1,
+            requires: D0
+        }))
+    );
+    assert_eq!(
+        http::route(&request, Levels(0b0001), &interlock),
+        Ok(Route::SetOutput(D1, true))
+    );
+    assert_eq!(
+        serve(&bytes, Levels(0), &interlock),
+        (
+            "HTTP/1.1 409 Conflict".to_string(),
+            "{\"error\":\"D1 requires D0\"}".to_string()
+        )
+    );
+}
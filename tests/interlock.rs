@@ -0,0 +1,324 @@
+//! Interlock rules between outputs.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+
+use std::fs;
+use std::path::PathBuf;
+
+use embedded_graphics::geometry::Point;
+
+use f7disco_rs::interlock::{Interlock, Rule, Step, Violation};
+use f7disco_rs::outputs::{Command, Levels, OutputId};
+use f7disco_rs::screens::{self, ScreenDescription};
+use f7disco_rs::sim::NavigatorSim;
+
+const RF: OutputId = OutputId(0);
+const DBM_43: OutputId = OutputId(1);
+const DBM_45: OutputId = OutputId(2);
+const DBM_47: OutputId = OutputId(3);
+
+const POWER: [OutputId; 3] = [DBM_43, DBM_45, DBM_47];
+
+/// The rules of `screens/med_chamber.toml`
+fn med_chamber() -> Interlock {
+    Interlock::new(vec![
+        Rule::Exclusive {
+            outputs: POWER.to_vec(),
+            delay_ms: 50,
+        },
+        Rule::Requires {
+            outputs: POWER.to_vec(),
+            requires: RF,
+            delay_ms: 100,
+        },
+    ])
+}
+
+fn levels(on: &[OutputId]) -> Levels {
+    let mut levels = Levels::default();
+    for &output in on {
+        levels.set(output, true);
+    }
+    levels
+}
+
+fn step(delay_ms: u32, command: Command) -> Step {
+    Step { delay_ms, command }
+}
+
+#[test]
+fn without_rules() {
+    let interlock = Interlock::default();
+    assert_eq!(
+        interlock.plan(Command::Toggle(DBM_45), Levels::default()),
+        Ok(vec![step(0, Command::Set(DBM_45))])
+    );
+    assert_eq!(
+        interlock.plan(Command::Set(DBM_45), levels(&[DBM_45])),
+        Ok(vec![])
+    );
+}
+
+#[test]
+fn exclusive_breaks_before_make() {
+    let interlock = med_chamber();
+    assert_eq!(
+        interlock.plan(Command::Set(DBM_45), levels(&[RF, DBM_43])),
+        Ok(vec![
+            step(0, Command::Clear(DBM_43)),
+            step(50, Command::Set(DBM_45)),
+        ])
+    );
+}
+
+#[test]
+fn requires() {
+    let interlock = med_chamber();
+    assert_eq!(
+        interlock.plan(Command::Toggle(DBM_47), Levels::default()),
+        Err(Violation::Requires {
+            output: DBM_47,
+            requires: RF,
+        })
+    );
+    assert_eq!(
+        interlock.plan(Command::Set(DBM_47), levels(&[RF])),
+        Ok(vec![step(0, Command::Set(DBM_47))])
+    );
+    // Switching off is always allowed
+    assert_eq!(
+        interlock.plan(Command::Clear(DBM_47), levels(&[DBM_47])),
+        Ok(vec![step(0, Command::Clear(DBM_47))])
+    );
+}
+
+#[test]
+fn required_output_goes_off_last() {
+    let interlock = med_chamber();
+    assert_eq!(
+        interlock.plan(Command::Toggle(RF), levels(&[RF, DBM_45])),
+        Ok(vec![
+            step(0, Command::Clear(DBM_45)),
+            step(100, Command::Clear(RF)),
+        ])
+    );
+}
+
+#[test]
+fn sequence() {
+    let [a, b, c] = [OutputId(0), OutputId(1), OutputId(2)];
+    let interlock = Interlock::new(vec![Rule::Sequence {
+        outputs: vec![a, b, c],
+        delay_ms: 20,
+    }]);
+
+    assert_eq!(
+        interlock.plan(Command::Set(c), Levels::default()),
+        Ok(vec![
+            step(0, Command::Set(a)),
+            step(20, Command::Set(b)),
+            step(20, Command::Set(c)),
+        ])
+    );
+    assert_eq!(
+        interlock.plan(Command::Set(c), levels(&[a])),
+        Ok(vec![step(0, Command::Set(b)), step(20, Command::Set(c))])
+    );
+    assert_eq!(
+        interlock.plan(Command::Clear(a), levels(&[a, b, c])),
+        Ok(vec![
+            step(0, Command::Clear(c)),
+            step(20, Command::Clear(b)),
+            step(20, Command::Clear(a)),
+        ])
+    );
+}
+
+#[test]
+fn pulses() {
+    let interlock = med_chamber();
+    assert_eq!(
+        interlock.plan(Command::Pulse(RF, 500), Levels::default()),
+        Err(Violation::Pulse { output: RF })
+    );
+    assert_eq!(
+        interlock.plan(Command::Pulse(DBM_43, 500), levels(&[RF, DBM_47])),
+        Ok(vec![
+            step(0, Command::Clear(DBM_47)),
+            step(50, Command::Pulse(DBM_43, 500)),
+        ])
+    );
+}
+
+#[test]
+fn loops() {
+    let [a, b] = [OutputId(0), OutputId(1)];
+    let interlock = Interlock::new(vec![
+        Rule::Sequence {
+            outputs: vec![a, b],
+            delay_ms: 0,
+        },
+        Rule::Sequence {
+            outputs: vec![b, a],
+            delay_ms: 0,
+        },
+    ]);
+    assert!(matches!(
+        interlock.plan(Command::Set(a), Levels::default()),
+        Err(Violation::Loop { .. })
+    ));
+}
+
+#[test]
+fn unchecked_commands() {
+    let interlock = med_chamber();
+    assert_eq!(
+        interlock.plan(Command::Read, levels(&[DBM_43])),
+        Ok(vec![step(0, Command::Read)])
+    );
+}
+
+#[test]
+fn safe_state() {
+    // The power level goes off before RF, the bank does the rest
+    assert_eq!(
+        med_chamber().plan(Command::SafeState, levels(&[RF, DBM_45])),
+        Ok(vec![
+            step(0, Command::Clear(DBM_45)),
+            step(100, Command::Clear(RF)),
+            step(0, Command::SafeState),
+        ])
+    );
+
+    // Never rejected, a loop leaves the order to the bank
+    let [a, b] = [OutputId(0), OutputId(1)];
+    let interlock = Interlock::new(vec![
+        Rule::Sequence {
+            outputs: vec![a, b],
+            delay_ms: 0,
+        },
+        Rule::Sequence {
+            outputs: vec![b, a],
+            delay_ms: 0,
+        },
+    ]);
+    assert_eq!(
+        interlock.plan(Command::SafeState, levels(&[a, b])),
+        Ok(vec![step(0, Command::SafeState)])
+    );
+}
+
+const SCREEN: &str = "[screen]\nname = \"Rules\"\n";
+
+fn rules(text: &str) -> Result<Vec<Rule>, String> {
+    ScreenDescription::parse(&format!("{SCREEN}{text}"))
+        .map(|description| description.rules)
+        .map_err(|e| e.to_string())
+}
+
+#[test]
+fn described_rules() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("screens/med_chamber.toml");
+    let description = ScreenDescription::parse(&fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(description.interlock(), med_chamber());
+
+    assert_eq!(
+        rules("[[rule]]\nsequence = [\"D3\", \"D0\"]\n"),
+        Ok(vec![Rule::Sequence {
+            outputs: vec![DBM_47, RF],
+            delay_ms: 0,
+        }])
+    );
+}
+
+#[test]
+fn rule_errors() {
+    for (text, error) in [
+        (
+            "[[rule]]\nexclusive = [\"D1\"]\n",
+            "line 4: `exclusive` needs two outputs or more",
+        ),
+        (
+            "[[rule]]\nsequence = [\"D1\", \"D1\"]\n",
+            "line 4: D1 is listed twice",
+        ),
+        (
+            "[[rule]]\nexclusive = [\"D1\", \"D7\"]\n",
+            "line 4: expected one of the outputs D0, D1, D2, D3",
+        ),
+        (
+            "[[rule]]\noutputs = [\"D0\", \"D1\"]\nrequires = \"D0\"\n",
+            "line 5: D0 cannot require itself",
+        ),
+        (
+            "[[rule]]\noutputs = [\"D1\"]\n",
+            "line 3: expected one of `exclusive`, `sequence` or `outputs` with `requires`",
+        ),
+        (
+            "[[rule]]\nexclusive = [1, 2]\n",
+            "line 4: `exclusive` must be an array of output names",
+        ),
+        (
+            "[[rule]]\nexclusive = [\"D1\", 2]\n",
+            "line 4: arrays hold either numbers or plain strings",
+        ),
+        (
+            "[[rule]]\nexclusive = [\"D1\", \"D2\"]\ndelay = 5\n",
+            "line 5: unknown key `delay`",
+        ),
+        (
+            "[[rule]]\nsequence = [\"D0\", \"D1\", \"D2\"]\n[[rule]]\nexclusive = [\"D2\", \"D0\"]\n",
+            "line 5: D2 needs D0, but the rule on line 5 lets only one of them be on",
+        ),
+        (
+            "[[rule]]\nexclusive = [\"D1\", \"D2\"]\n[[rule]]\noutputs = [\"D2\"]\nrequires = \"D1\"\n",
+            "line 5: D2 needs D1, but the rule on line 3 lets only one of them be on",
+        ),
+        (
+            "[[rule]]\noutputs = [\"D1\"]\nrequires = \"D2\"\n[[rule]]\nsequence = [\"D1\", \"D2\"]\n",
+            "line 6: the rules for D1 form a loop",
+        ),
+        (
+            "[[rules]]\n",
+            "line 3: expected `[screen]`, `[[widget]]` or `[[rule]]`",
+        ),
+    ] {
+        assert_eq!(rules(text), Err(error.to_string()), "{text}");
+    }
+}
+
+const RF_BUTTON: Point = Point::new(236, 129);
+const YES: Point = Point::new(180, 164);
+const DBM_43_BUTTON: Point = Point::new(100, 231);
+const DBM_45_BUTTON: Point = Point::new(236, 231);
+
+fn panel() -> NavigatorSim {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("screens/med_chamber.toml");
+    let description = ScreenDescription::parse(&fs::read_to_string(path).unwrap()).unwrap();
+    NavigatorSim::new(screens::operator_panel(&description)).with_interlock(description.interlock())
+}
+
+#[test]
+fn operator_panel() {
+    let mut sim = panel();
+
+    // No power without RF
+    assert_eq!(sim.touch(DBM_45_BUTTON), None);
+    assert_eq!(sim.pins(), [false; 4]);
+
+    sim.touch(RF_BUTTON);
+    sim.touch(YES);
+    sim.touch(DBM_43_BUTTON);
+    assert_eq!(sim.pins(), [true, true, false, false]);
+    sim.touch(DBM_45_BUTTON);
+    assert_eq!(sim.pins(), [true, false, true, false]);
+    let with_45 = sim.frame();
+
+    // RF off takes the power with it, and the screen shows it
+    sim.touch(RF_BUTTON);
+    assert_eq!(sim.pins(), [false; 4]);
+    assert_eq!(panel().frame(), sim.frame());
+    assert_ne!(with_45, sim.frame());
+}
@@ -0,0 +1,108 @@
+//! Network settings as stored in flash and typed on the console.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+
+use std::net::Ipv4Addr;
+
+use f7disco_rs::netconfig::{self, NetChange, NetConfig, NetSetting, STORED_LEN};
+
+const STATIC: NetConfig = NetConfig {
+    dhcp: false,
+    address: Ipv4Addr::new(10, 0, 0, 5),
+    prefix_len: 16,
+    gateway: None,
+    dns: Some(Ipv4Addr::new(10, 0, 0, 1)),
+};
+
+#[test]
+fn stored_record() {
+    for config in [NetConfig::DEFAULT, STATIC] {
+        let bytes = config.to_bytes();
+        assert_eq!(NetConfig::from_bytes(&bytes), Some(config));
+    }
+    assert_eq!(NetConfig::from_bytes(&[0xFF; STORED_LEN]), None);
+    assert_eq!(NetConfig::from_bytes(&STATIC.to_bytes()[..20]), None);
+
+    // Any damaged byte is noticed
+    let bytes = STATIC.to_bytes();
+    for i in 0..STORED_LEN {
+        let mut damaged = bytes;
+        damaged[i] ^= 0x10;
+        assert_eq!(NetConfig::from_bytes(&damaged), None, "byte {i}");
+    }
+}
+
+#[test]
+fn masks() {
+    assert_eq!(netconfig::netmask(24), Ipv4Addr::new(255, 255, 255, 0));
+    assert_eq!(netconfig::netmask(0), Ipv4Addr::UNSPECIFIED);
+    assert_eq!(netconfig::netmask(32), Ipv4Addr::BROADCAST);
+    assert_eq!(STATIC.netmask(), Ipv4Addr::new(255, 255, 0, 0));
+
+    for len in 0..=32 {
+        assert_eq!(netconfig::prefix_len(netconfig::netmask(len)), Some(len));
+    }
+    assert_eq!(netconfig::prefix_len(Ipv4Addr::new(255, 0, 255, 0)), None);
+    assert_eq!(netconfig::prefix_len(Ipv4Addr::new(0, 0, 0, 255)), None);
+}
+
+#[test]
+fn addresses() {
+    let address = Ipv4Addr::new(192, 168, 1, 37);
+    assert_eq!(
+        netconfig::parse_address("192.168.1.37"),
+        Some((address, None))
+    );
+    assert_eq!(
+        netconfig::parse_address("192.168.1.37/24"),
+        Some((address, Some(24)))
+    );
+    for text in [
+        "",
+        "192.168.1",
+        "192.168.1.37/",
+        "192.168.1.37/33",
+        "192.168.1.256",
+        "0.0.0.0",
+        "255.255.255.255",
+        "239.1.2.3",
+    ] {
+        assert_eq!(netconfig::parse_address(text), None, "{text}");
+    }
+}
+
+#[test]
+fn settings() {
+    let config = NetConfig::DEFAULT
+        .with(NetSetting::Dhcp(false))
+        .with(NetSetting::Address(STATIC.address, Some(16)))
+        .with(NetSetting::Gateway(None))
+        .with(NetSetting::Dns(STATIC.dns));
+    assert_eq!(config, STATIC);
+
+    // The prefix length stays unless it is given
+    let config = STATIC.with(NetSetting::Address(Ipv4Addr::new(10, 0, 9, 9), None));
+    assert_eq!(config.prefix_len, 16);
+    assert_eq!(STATIC.with(NetSetting::PrefixLen(8)).prefix_len, 8);
+
+    // A saved screen replaces everything
+    let dns = NetChange::Setting(NetSetting::Dns(None));
+    assert_eq!(STATIC.changed(dns).dns, None);
+    let saved = NetChange::Config(NetConfig::DEFAULT);
+    assert_eq!(STATIC.changed(saved), NetConfig::DEFAULT);
+}
+
+#[test]
+fn mac_addresses() {
+    let uid = *b"\x2f\x00\x3c\x00\x12\x51\x36\x34\x38\x37\x33\x30";
+    let mac = netconfig::mac_address(&uid);
+    // Locally administered unicast, the same every time
+    assert_eq!(mac[0], 0x02);
+    assert_eq!(netconfig::mac_address(&uid), mac);
+
+    let mut other = uid;
+    other[11] ^= 1;
+    assert_ne!(netconfig::mac_address(&other), mac);
+}
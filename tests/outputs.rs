@@ -0,0 +1,211 @@
+//! Output bank on host pins.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+
+use std::cell::RefCell;
+use std::convert::Infallible;
+
+use embedded_hal::digital::{ErrorType, OutputPin, StatefulOutputPin};
+
+use f7disco_rs::outputs::{
+    Command, Levels, OutputBank, OutputConfig, OutputId, OutputLevel, UnknownOutput, OUTPUTS,
+};
+use f7disco_rs::sim::SimPin;
+
+const PUMP: OutputId = OutputId(0);
+const RELAY: OutputId = OutputId(1);
+const BRAKE: OutputId = OutputId(2);
+
+/// Active high, active low and on when safe
+fn bank() -> OutputBank<SimPin, 3> {
+    OutputBank::new(
+        [SimPin::default(); 3],
+        [
+            OutputConfig::new("pump"),
+            OutputConfig::new("relay").inverted(),
+            OutputConfig::new("brake").safe_on(),
+        ],
+    )
+}
+
+fn pins(bank: &OutputBank<SimPin, 3>) -> [bool; 3] {
+    bank.pins().map(|pin| pin.high)
+}
+
+#[test]
+fn safe_on_boot() {
+    let mut bank = bank();
+    // The relay is off with its pin high
+    assert_eq!(pins(&bank), [false, true, true]);
+    assert_eq!(bank.levels(), Levels(0b100));
+}
+
+#[test]
+fn commands() {
+    let mut bank = bank();
+
+    bank.execute(Command::Set(PUMP), 0).unwrap();
+    bank.execute(Command::Set(RELAY), 0).unwrap();
+    bank.execute(Command::Clear(BRAKE), 0).unwrap();
+    assert_eq!(pins(&bank), [true, false, false]);
+    assert_eq!(bank.get(RELAY), Some(true));
+
+    bank.execute(Command::Toggle(RELAY), 0).unwrap();
+    assert_eq!(bank.get(RELAY), Some(false));
+    assert_eq!(pins(&bank), [true, true, false]);
+
+    bank.execute(Command::Read, 0).unwrap();
+    assert_eq!(bank.levels(), Levels(0b001));
+
+    bank.execute(Command::SafeState, 0).unwrap();
+    assert_eq!(pins(&bank), [false, true, true]);
+}
+
+/// Pin that logs every write, with its index, to a shared list
+struct LogPin<'a> {
+    index: usize,
+    high: bool,
+    log: &'a RefCell<Vec<(usize, bool)>>,
+}
+
+impl ErrorType for LogPin<'_> {
+    type Error = Infallible;
+}
+
+impl OutputPin for LogPin<'_> {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.high = false;
+        self.log.borrow_mut().push((self.index, false));
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.high = true;
+        self.log.borrow_mut().push((self.index, true));
+        Ok(())
+    }
+}
+
+impl StatefulOutputPin for LogPin<'_> {
+    fn is_set_high(&mut self) -> Result<bool, Infallible> {
+        Ok(self.high)
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Infallible> {
+        Ok(!self.high)
+    }
+}
+
+#[test]
+fn safe_state_order() {
+    let log = RefCell::new(Vec::new());
+    let pins = [0, 1, 2, 3].map(|index| LogPin {
+        index,
+        high: false,
+        log: &log,
+    });
+    let mut bank = OutputBank::new(pins, OUTPUTS);
+    for i in 0..4 {
+        bank.execute(Command::Set(OutputId(i)), 0).unwrap();
+    }
+
+    // D1 to D3 depend on D0 (RF) and go off before it
+    log.borrow_mut().clear();
+    bank.execute(Command::SafeState, 0).unwrap();
+    assert_eq!(
+        *log.borrow(),
+        [(3, false), (2, false), (1, false), (0, false)]
+    );
+}
+
+#[test]
+fn unknown_output() {
+    let mut bank = bank();
+    let missing = OutputId(3);
+    for command in [
+        Command::Set(missing),
+        Command::Toggle(missing),
+        Command::Pulse(missing, 10),
+    ] {
+        assert_eq!(bank.execute(command, 0), Err(UnknownOutput(missing)));
+    }
+    assert_eq!(bank.get(missing), None);
+    assert_eq!(bank.levels(), Levels(0b100));
+}
+
+#[test]
+fn pulses() {
+    let mut bank = bank();
+    assert_eq!(bank.next_deadline(), None);
+
+    bank.execute(Command::Pulse(PUMP, 200), 1_000).unwrap();
+    bank.execute(Command::Pulse(RELAY, 50), 1_100).unwrap();
+    assert_eq!(bank.next_deadline(), Some(1_150));
+    assert_eq!(bank.levels(), Levels(0b111));
+
+    bank.poll(1_149);
+    assert_eq!(bank.levels(), Levels(0b111));
+    bank.poll(1_150);
+    assert_eq!(bank.levels(), Levels(0b101));
+    assert_eq!(bank.next_deadline(), Some(1_200));
+
+    // Late is still ended
+    bank.poll(1_500);
+    assert_eq!(bank.levels(), Levels(0b100));
+    assert_eq!(bank.next_deadline(), None);
+}
+
+#[test]
+fn commands_end_pulses() {
+    let mut bank = bank();
+    bank.execute(Command::Pulse(PUMP, 100), 0).unwrap();
+    bank.execute(Command::Set(PUMP), 50).unwrap();
+    assert_eq!(bank.next_deadline(), None);
+    bank.poll(200);
+    assert_eq!(bank.get(PUMP), Some(true));
+
+    bank.execute(Command::Pulse(PUMP, 100), 300).unwrap();
+    bank.execute(Command::SafeState, 310).unwrap();
+    assert_eq!(bank.next_deadline(), None);
+}
+
+#[test]
+fn pulse_over_clock_wrap() {
+    let mut bank = bank();
+    bank.execute(Command::Pulse(PUMP, 100), u32::MAX - 10)
+        .unwrap();
+    bank.poll(20);
+    assert_eq!(bank.get(PUMP), Some(true));
+    bank.poll(89);
+    assert_eq!(bank.get(PUMP), Some(false));
+}
+
+#[test]
+fn level_changes() {
+    let old = Levels(0b0101);
+    let new = Levels(0b0110);
+    let changes: Vec<OutputLevel> = new.changes_since(old).collect();
+    assert_eq!(
+        changes,
+        [
+            OutputLevel {
+                output: OutputId(0),
+                on: false
+            },
+            OutputLevel {
+                output: OutputId(1),
+                on: true
+            },
+        ]
+    );
+    assert_eq!(new.changes_since(new).count(), 0);
+}
+
+#[test]
+fn names() {
+    assert_eq!(OutputId::by_name("D2"), Some(OutputId(2)));
+    assert_eq!(OutputId::by_name("D9"), None);
+    assert_eq!(OutputId(3).name(), OUTPUTS[3].name);
+}
@@ -0,0 +1,87 @@
+//! LTDC register values and PLLSAI settings computed from panel timings.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+
+use f7disco_rs::panel::{LtdcTiming, PanelTiming, AT070TN92, RK043FN48H};
+use f7disco_rs::rcc::PllSai;
+
+/// HSE after PLLM = 25, as in every built-in clock profile
+const VCO_IN_HZ: u32 = 1_000_000;
+
+/// The LTDC pixel clock `pllsai` gives
+fn pixel_clock(pllsai: &PllSai) -> u32 {
+    VCO_IN_HZ * pllsai.n / pllsai.r / pllsai.divr
+}
+
+#[test]
+fn pllsai_for_panels() {
+    for (panel, n, r, divr) in [(RK043FN48H, 192, 5, 4), (AT070TN92, 333, 5, 2)] {
+        let pllsai = panel.pllsai(VCO_IN_HZ).unwrap();
+        assert_eq!(
+            pllsai,
+            PllSai {
+                n,
+                p: 8,
+                q: 15,
+                r,
+                divr,
+            }
+        );
+        assert_eq!(pixel_clock(&pllsai), panel.pixel_clock_hz);
+    }
+}
+
+#[test]
+fn closest_pixel_clock() {
+    // The datasheet's typical 9 MHz from a 2 MHz VCO input, exactly and
+    // within the VCO limits
+    let nine_mhz = PanelTiming {
+        pixel_clock_hz: 9_000_000,
+        ..RK043FN48H
+    };
+    let pllsai = nine_mhz.pllsai(2_000_000).unwrap();
+    assert!((50..=432).contains(&pllsai.n));
+    assert!((100_000_000..=432_000_000).contains(&(2_000_000 * pllsai.n)));
+    assert_eq!(2_000_000 * pllsai.n / pllsai.r / pllsai.divr, 9_000_000);
+}
+
+#[test]
+fn unreachable_pixel_clock() {
+    // Above 432 MHz / 2 / 2 and below 100 MHz / 7 / 16
+    for pixel_clock_hz in [200_000_000, 100_000] {
+        let panel = PanelTiming {
+            pixel_clock_hz,
+            ..RK043FN48H
+        };
+        assert_eq!(panel.pllsai(VCO_IN_HZ), None, "{pixel_clock_hz} Hz");
+    }
+}
+
+#[test]
+fn ltdc_registers() {
+    assert_eq!(
+        AT070TN92.ltdc_timing(),
+        LtdcTiming {
+            // SSCR
+            hsw: 19,
+            vsh: 9,
+            // BPCR
+            ahbp: 45,
+            avbp: 22,
+            // AWCR
+            aaw: 845,
+            aah: 502,
+            // TWCR
+            totalw: 1055,
+            totalh: 524,
+        }
+    );
+    assert_eq!(
+        (AT070TN92.total_width(), AT070TN92.total_height()),
+        (1056, 525)
+    );
+    // 33.3 MHz / (1056 * 525) = 60.06 Hz
+    assert_eq!(AT070TN92.refresh_rate_mhz(), 60_064);
+}
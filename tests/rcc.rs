@@ -0,0 +1,187 @@
+//! Clock tree arithmetic of the built-in profiles and every limit it
+//! checks.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+
+use f7disco_rs::rcc::{
+    self, ClockError, ClockProfile, Clocks, MainPll, PllSai, DEFAULT, LOW_POWER, MAX_PERFORMANCE,
+    USB,
+};
+
+/// [`MAX_PERFORMANCE`] with its main PLL changed by `f`
+fn with_pll(f: impl FnOnce(&mut MainPll)) -> ClockProfile {
+    let mut pll = MAX_PERFORMANCE.pll.unwrap();
+    f(&mut pll);
+    ClockProfile {
+        pll: Some(pll),
+        ..MAX_PERFORMANCE
+    }
+}
+
+/// [`MAX_PERFORMANCE`] with its PLLSAI changed by `f`
+fn with_pllsai(f: impl FnOnce(&mut PllSai)) -> ClockProfile {
+    let mut sai = MAX_PERFORMANCE.pllsai.unwrap();
+    f(&mut sai);
+    MAX_PERFORMANCE.with_pllsai(sai)
+}
+
+#[test]
+fn built_in_profiles() {
+    assert_eq!(
+        MAX_PERFORMANCE.validate(),
+        Ok(Clocks {
+            sysclk: 216_000_000,
+            hclk: 216_000_000,
+            pclk1: 54_000_000,
+            pclk2: 108_000_000,
+            pll48: Some(48_000_000),
+            lcd: Some(9_600_000),
+        })
+    );
+    assert_eq!(
+        USB.validate(),
+        Ok(Clocks {
+            sysclk: 96_000_000,
+            hclk: 96_000_000,
+            pclk1: 48_000_000,
+            pclk2: 96_000_000,
+            pll48: Some(48_000_000),
+            lcd: Some(9_600_000),
+        })
+    );
+    assert_eq!(DEFAULT.validate().unwrap().sysclk, 200_000_000);
+    assert_eq!(rcc::LCD_TUNED.validate().unwrap().lcd, Some(9_000_000));
+
+    let low_power = LOW_POWER.validate().unwrap();
+    assert_eq!((low_power.sysclk, low_power.pll48), (rcc::HSI_HZ, None));
+    assert_eq!(LOW_POWER.vco_input_hz(), None);
+    assert_eq!(MAX_PERFORMANCE.vco_input_hz(), Some(1_000_000));
+}
+
+#[test]
+fn vco_limits() {
+    for (m, error) in [
+        (1, ClockError::PllmOutOfRange),
+        (64, ClockError::PllmOutOfRange),
+        // 2.5 MHz and 0.5 MHz into the VCO
+        (10, ClockError::VcoInputOutOfRange),
+        (50, ClockError::VcoInputOutOfRange),
+    ] {
+        assert_eq!(with_pll(|pll| pll.m = m).validate(), Err(error), "PLLM {m}");
+    }
+
+    for (n, error) in [
+        (49, ClockError::PllnOutOfRange),
+        (433, ClockError::PllnOutOfRange),
+        // 99 MHz out of the VCO
+        (99, ClockError::VcoOutputOutOfRange),
+    ] {
+        assert_eq!(with_pll(|pll| pll.n = n).validate(), Err(error), "PLLN {n}");
+    }
+
+    // 25/13 MHz in, 442 MHz out
+    let fast = with_pll(|pll| {
+        pll.m = 13;
+        pll.n = 230;
+    });
+    assert_eq!(fast.validate(), Err(ClockError::VcoOutputOutOfRange));
+
+    // PLLSAI shares PLLM and has the same VCO limits
+    assert_eq!(
+        with_pllsai(|sai| sai.n = 60).validate(),
+        Err(ClockError::VcoOutputOutOfRange)
+    );
+}
+
+#[test]
+fn pll_dividers() {
+    for p in [0, 3, 5, 10] {
+        assert_eq!(
+            with_pll(|pll| pll.p = p).validate(),
+            Err(ClockError::InvalidPllp),
+            "PLLP {p}"
+        );
+    }
+    for q in [1, 16] {
+        assert_eq!(
+            with_pll(|pll| pll.q = q).validate(),
+            Err(ClockError::PllqOutOfRange),
+            "PLLQ {q}"
+        );
+    }
+    for r in [1, 8] {
+        assert_eq!(
+            with_pllsai(|sai| sai.r = r).validate(),
+            Err(ClockError::PllrOutOfRange),
+            "PLLSAIR {r}"
+        );
+    }
+    for divr in [1, 3, 32] {
+        assert_eq!(
+            with_pllsai(|sai| sai.divr = divr).validate(),
+            Err(ClockError::InvalidPllSaiDivr),
+            "PLLSAIDIVR {divr}"
+        );
+    }
+}
+
+#[test]
+fn bus_limits() {
+    let profile = |ahb_div, apb1_div, apb2_div| ClockProfile {
+        ahb_div,
+        apb1_div,
+        apb2_div,
+        ..MAX_PERFORMANCE
+    };
+    assert_eq!(profile(1, 2, 2).validate(), Err(ClockError::Apb1TooHigh));
+    assert_eq!(profile(1, 4, 1).validate(), Err(ClockError::Apb2TooHigh));
+    assert_eq!(
+        profile(3, 4, 2).validate(),
+        Err(ClockError::InvalidPrescaler)
+    );
+    assert_eq!(
+        profile(1, 4, 32).validate(),
+        Err(ClockError::InvalidPrescaler)
+    );
+    // Slower AHB, the buses may divide less
+    assert_eq!(profile(2, 2, 1).validate().unwrap().pclk2, 108_000_000);
+}
+
+#[test]
+fn sysclk_needs_overdrive() {
+    let no_overdrive = ClockProfile {
+        overdrive: false,
+        ..MAX_PERFORMANCE
+    };
+    assert_eq!(no_overdrive.validate(), Err(ClockError::SysclkTooHigh));
+
+    // 180 MHz is fine without it
+    let at_limit = ClockProfile {
+        pll: Some(MainPll {
+            m: 25,
+            n: 360,
+            p: 2,
+            q: 15,
+        }),
+        usb: false,
+        ..no_overdrive
+    };
+    assert_eq!(at_limit.validate().unwrap().sysclk, 180_000_000);
+}
+
+#[test]
+fn usb_needs_48_mhz() {
+    for profile in [DEFAULT, LOW_POWER] {
+        let usb = ClockProfile {
+            usb: true,
+            ..profile
+        };
+        assert_eq!(usb.validate(), Err(ClockError::UsbClockNot48MHz));
+    }
+    assert_eq!(
+        with_pll(|pll| pll.q = 8).validate(),
+        Err(ClockError::UsbClockNot48MHz)
+    );
+}
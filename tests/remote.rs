@@ -0,0 +1,241 @@
+//! Remote control protocol, as the USB serial port runs it.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+
+use std::net::Ipv4Addr;
+
+use f7disco_rs::netconfig::{NetChange, NetConfig, NetSetting, NetState};
+use f7disco_rs::outputs::{Command, Levels, OutputId};
+use f7disco_rs::remote::{Effect, LineBuffer, Request, RequestError, Session};
+use f7disco_rs::shared::AppState;
+
+const D1: OutputId = OutputId(1);
+
+#[test]
+fn requests() {
+    for (line, request) in [
+        ("SET D1 ON", Request::Set(D1, true)),
+        ("set d1 off", Request::Set(D1, false)),
+        ("  TOGGLE\tD1  ", Request::Toggle(D1)),
+        ("PULSE D1 200", Request::Pulse(D1, 200)),
+        ("SAFE", Request::SafeState),
+        ("GET D1", Request::Get(Some(D1))),
+        ("GET all", Request::Get(None)),
+        ("STATUS", Request::Status),
+        ("SUBSCRIBE", Request::Subscribe),
+        ("UNSUBSCRIBE", Request::Unsubscribe),
+        ("NET", Request::Net(None)),
+        ("net dhcp off", Request::Net(Some(NetSetting::Dhcp(false)))),
+        (
+            "NET ADDRESS 10.0.0.5/16",
+            Request::Net(Some(NetSetting::Address(
+                Ipv4Addr::new(10, 0, 0, 5),
+                Some(16),
+            ))),
+        ),
+        (
+            "NET MASK 255.255.255.128",
+            Request::Net(Some(NetSetting::PrefixLen(25))),
+        ),
+        (
+            "NET GATEWAY 10.0.0.1",
+            Request::Net(Some(NetSetting::Gateway(Some(Ipv4Addr::new(10, 0, 0, 1))))),
+        ),
+        ("NET DNS none", Request::Net(Some(NetSetting::Dns(None)))),
+    ] {
+        assert_eq!(Request::parse(line), Ok(request), "{line}");
+    }
+}
+
+#[test]
+fn invalid_requests() {
+    for (line, error) in [
+        ("", RequestError::Empty),
+        ("RESET", RequestError::UnknownRequest),
+        ("SET D9 ON", RequestError::UnknownOutput),
+        ("SET D1 1", RequestError::InvalidLevel),
+        ("SET D1", RequestError::MissingArgument),
+        ("PULSE D1 -5", RequestError::InvalidDuration),
+        ("GET", RequestError::MissingArgument),
+        ("STATUS NOW", RequestError::TooManyArguments),
+        ("NET DHCP", RequestError::MissingArgument),
+        ("NET DHCP YES", RequestError::InvalidLevel),
+        ("NET ADDRESS 10.0.0", RequestError::InvalidAddress),
+        ("NET ADDRESS 224.0.0.1", RequestError::InvalidAddress),
+        ("NET MASK 255.0.255.0", RequestError::InvalidAddress),
+        ("NET GATEWAY 10.0.0.1/8", RequestError::InvalidAddress),
+        ("NET MTU 1500", RequestError::UnknownSetting),
+    ] {
+        assert_eq!(Request::parse(line), Err(error), "{line}");
+    }
+}
+
+#[test]
+fn commands() {
+    assert_eq!(Request::Set(D1, true).command(), Some(Command::Set(D1)));
+    assert_eq!(Request::Set(D1, false).command(), Some(Command::Clear(D1)));
+    assert_eq!(
+        Request::Pulse(D1, 50).command(),
+        Some(Command::Pulse(D1, 50))
+    );
+    assert_eq!(Request::SafeState.command(), Some(Command::SafeState));
+    assert_eq!(Request::Get(None).command(), None);
+    assert_eq!(Request::Subscribe.command(), None);
+}
+
+fn with_levels(levels: Levels) -> AppState {
+    AppState {
+        outputs: levels,
+        ..Default::default()
+    }
+}
+
+/// Feeds `input` byte by byte, returns the replies and what else the
+/// requests asked for
+fn run_in(session: &mut Session, input: &[u8], state: &AppState) -> (String, Vec<Effect>) {
+    let mut lines = LineBuffer::<32>::new();
+    let mut reply = String::new();
+    let mut effects = Vec::new();
+    for &byte in input {
+        if let Some(line) = lines.push(byte) {
+            effects.extend(session.handle(line, state, &mut reply).unwrap());
+        }
+    }
+    (reply, effects)
+}
+
+/// Like [`run_in`] for requests that only touch the outputs
+fn run(session: &mut Session, input: &[u8], levels: Levels) -> (String, Vec<Command>) {
+    let (reply, effects) = run_in(session, input, &with_levels(levels));
+    let commands = effects
+        .into_iter()
+        .map(|effect| match effect {
+            Effect::Output(command) => command,
+            Effect::Network(setting) => panic!("unexpected network setting {setting:?}"),
+        })
+        .collect();
+    (reply, commands)
+}
+
+#[test]
+fn session() {
+    let mut session = Session::default();
+    let (reply, commands) = run(
+        &mut session,
+        b"SET D1 ON\r\nGET D1\r\nGET ALL\nbogus\rTOGGLE D3\n",
+        Levels(0b0011),
+    );
+    assert_eq!(
+        reply,
+        "OK\r\nD1=ON\r\nD0=ON D1=ON D2=OFF D3=OFF\r\nERR unknown request\r\nOK\r\n"
+    );
+    assert_eq!(commands, [Command::Set(D1), Command::Toggle(OutputId(3))]);
+}
+
+#[test]
+fn status() {
+    let mut session = Session::default();
+    let version = env!("CARGO_PKG_VERSION");
+    let (reply, _) = run(&mut session, b"STATUS\nSUBSCRIBE\nSTATUS\n", Levels(0));
+    assert_eq!(
+        reply,
+        format!(
+            "STATUS VERSION={version} OUTPUTS=D0,D1,D2,D3 SUBSCRIBED=NO\r\nOK\r\n\
+             STATUS VERSION={version} OUTPUTS=D0,D1,D2,D3 SUBSCRIBED=YES\r\n"
+        )
+    );
+}
+
+#[test]
+fn events() {
+    let mut session = Session::default();
+    let mut events = String::new();
+    session
+        .events(Levels(0b0001), Levels(0b0110), &mut events)
+        .unwrap();
+    assert_eq!(events, "");
+
+    run(&mut session, b"SUBSCRIBE\n", Levels(0));
+    assert!(session.is_subscribed());
+    session
+        .events(Levels(0b0001), Levels(0b0110), &mut events)
+        .unwrap();
+    assert_eq!(events, "EVENT D0=OFF\r\nEVENT D1=ON\r\nEVENT D2=ON\r\n");
+
+    run(&mut session, b"UNSUBSCRIBE\n", Levels(0));
+    assert!(!session.is_subscribed());
+}
+
+#[test]
+fn long_and_broken_lines() {
+    let mut session = Session::default();
+    let mut input = vec![b'X'; 40];
+    input.extend_from_slice(b"\r\n\xff\xfe\nGET D2\n");
+    let (reply, _) = run(&mut session, &input, Levels(0b0100));
+    assert_eq!(reply, "ERR line too long\r\nERR invalid UTF-8\r\nD2=ON\r\n");
+}
+
+#[test]
+fn network() {
+    let mut session = Session::default();
+    let mut state = with_levels(Levels(0));
+    state.network = NetState {
+        mac: [0x02, 0x1A, 0x2B, 0x3C, 0x4D, 0x5E],
+        config: NetConfig::DEFAULT,
+        link_up: true,
+        address: Some((Ipv4Addr::new(192, 168, 210, 201), 24)),
+        fallback: true,
+    };
+
+    let (reply, effects) = run_in(&mut session, b"NET\n", &state);
+    assert_eq!(
+        reply,
+        "NET DHCP=ON ADDRESS=192.168.210.201/24 GATEWAY=192.168.210.1 DNS=NONE \
+         MAC=02:1A:2B:3C:4D:5E LINK=UP CURRENT=192.168.210.201/24 FALLBACK=YES\r\n"
+    );
+    assert_eq!(effects, []);
+
+    // Every setting changes the stored ones, one after another
+    let (reply, effects) = run_in(&mut session, b"NET DHCP OFF\nNET DNS 1.1.1.1\n", &state);
+    assert_eq!(reply, "OK\r\nOK\r\n");
+    let config = effects
+        .iter()
+        .fold(state.network.config, |config, effect| match *effect {
+            Effect::Network(setting) => config.changed(NetChange::Setting(setting)),
+            Effect::Output(command) => panic!("unexpected command {command:?}"),
+        });
+    assert_eq!(
+        config,
+        NetConfig {
+            dhcp: false,
+            dns: Some(Ipv4Addr::new(1, 1, 1, 1)),
+            ..NetConfig::DEFAULT
+        }
+    );
+}
+
+#[test]
+fn network_events() {
+    let mut session = Session::default();
+    let down = NetState::default();
+    let up = NetState {
+        link_up: true,
+        address: Some((Ipv4Addr::new(10, 0, 0, 5), 16)),
+        ..down
+    };
+    let mut events = String::new();
+    session.network_events(&down, &up, &mut events).unwrap();
+    assert_eq!(events, "");
+
+    run(&mut session, b"SUBSCRIBE\n", Levels(0));
+    session.network_events(&down, &up, &mut events).unwrap();
+    session.network_events(&up, &down, &mut events).unwrap();
+    session.network_events(&up, &up, &mut events).unwrap();
+    assert_eq!(
+        events,
+        "EVENT LINK=UP\r\nEVENT ADDRESS=10.0.0.5/16\r\n\
+         EVENT LINK=DOWN\r\nEVENT ADDRESS=NONE\r\n"
+    );
+}
@@ -0,0 +1,211 @@
+//! SCPI parser and dispatcher.
+//!
+//! ```sh
+//! cargo test --no-default-features --features sim --target x86_64-unknown-linux-gnu
+//! ```
+
+use f7disco_rs::interlock::{Interlock, Rule};
+use f7disco_rs::outputs::{Command, Levels, OutputId};
+use f7disco_rs::scpi::{self, Scpi, MED_CHAMBER, NO_INSTRUMENT};
+
+const RF: OutputId = OutputId(0);
+const DBM_43: OutputId = OutputId(1);
+const DBM_45: OutputId = OutputId(2);
+const DBM_47: OutputId = OutputId(3);
+
+fn scpi() -> Scpi {
+    Scpi::new(MED_CHAMBER, Interlock::default())
+}
+
+/// Processes `message`, returns the response line and the commands
+fn process(scpi: &mut Scpi, message: &str, levels: Levels) -> (String, Vec<Command>) {
+    let mut reply = String::new();
+    let effect = scpi.process(message, levels, &mut reply).unwrap();
+    (reply, effect.commands.to_vec())
+}
+
+fn query(scpi: &mut Scpi, message: &str, levels: Levels) -> String {
+    process(scpi, message, levels).0
+}
+
+/// Every queued error, oldest first
+fn errors(scpi: &mut Scpi) -> Vec<i16> {
+    std::iter::from_fn(|| Some(scpi.pop_error()).filter(|e| e.code != 0))
+        .map(|e| e.code)
+        .collect()
+}
+
+#[test]
+fn common_commands() {
+    let mut scpi = scpi();
+    let idn = query(&mut scpi, "*IDN?", Levels(0));
+    assert_eq!(
+        idn,
+        format!("f7disco-rs,Output panel,0,{}\n", env!("CARGO_PKG_VERSION"))
+    );
+    assert_eq!(query(&mut scpi, "*opc?", Levels(0)), "1\n");
+    assert_eq!(
+        process(&mut scpi, "*RST", Levels(0b1111)),
+        (String::new(), vec![Command::SafeState])
+    );
+}
+
+#[test]
+fn short_and_long_forms() {
+    let mut scpi = scpi();
+    for message in [
+        "OUTPut:RF ON",
+        "OUTP:RF ON",
+        ":outp:rf:stat 1",
+        "OUTPUT:RF:STATE on",
+    ] {
+        assert_eq!(
+            process(&mut scpi, message, Levels(0)),
+            (String::new(), vec![Command::Set(RF)]),
+            "{message}"
+        );
+    }
+    assert_eq!(errors(&mut scpi), []);
+
+    // Neither short nor long
+    process(&mut scpi, "OUTPU:RF ON", Levels(0));
+    process(&mut scpi, "OUT:RF ON", Levels(0));
+    assert_eq!(errors(&mut scpi), [-113, -113]);
+}
+
+#[test]
+fn queries() {
+    let mut scpi = scpi();
+    let levels = Levels(0b0101);
+    assert_eq!(query(&mut scpi, "OUTP:RF?", levels), "1\n");
+    assert_eq!(query(&mut scpi, "SOUR:POW:LEV?", levels), "45\n");
+    assert_eq!(query(&mut scpi, "POW?", levels), "45\n");
+    assert_eq!(query(&mut scpi, "POW?", Levels(0b0001)), "9.91E37\n");
+    assert_eq!(
+        query(&mut scpi, "OUTP:RF?;:SOUR:POW:LEV?;*OPC?", levels),
+        "1;45;1\n"
+    );
+}
+
+#[test]
+fn power_level() {
+    let mut scpi = scpi();
+    let rf_and_43 = Levels(0b0011);
+    assert_eq!(
+        process(&mut scpi, "SOURce:POWer:LEVel 45", rf_and_43),
+        (
+            String::new(),
+            vec![Command::Clear(DBM_43), Command::Set(DBM_45)]
+        )
+    );
+    for (parameter, output) in [
+        ("47", DBM_47),
+        ("4.7E1", DBM_47),
+        ("47 dBm", DBM_47),
+        ("MAX", DBM_47),
+        ("minimum", DBM_43),
+    ] {
+        let (_, commands) = process(&mut scpi, &format!("POW {parameter}"), Levels(0b0001));
+        assert_eq!(commands, [Command::Set(output)], "{parameter}");
+    }
+
+    process(&mut scpi, "POW 46", Levels(0b0001));
+    process(&mut scpi, "POW loud", Levels(0b0001));
+    process(&mut scpi, "POW", Levels(0b0001));
+    assert_eq!(errors(&mut scpi), [-222, -104, -109]);
+}
+
+#[test]
+fn relative_headers() {
+    let mut scpi = scpi();
+    // `LEV?` continues below `SOUR:POW`, after `*OPC?` as well
+    let (reply, commands) = process(
+        &mut scpi,
+        ":SOUR:POW:LEV 47;*OPC?;LEV?;:OUTP:RF OFF;RF?",
+        Levels(0b0001),
+    );
+    assert_eq!(reply, "1;47;0\n");
+    assert_eq!(commands, [Command::Set(DBM_47), Command::Clear(RF)]);
+    assert_eq!(errors(&mut scpi), []);
+}
+
+#[test]
+fn interlock_conflicts() {
+    let interlock = Interlock::new(vec![Rule::Requires {
+        outputs: vec![DBM_43, DBM_45, DBM_47],
+        requires: RF,
+        delay_ms: 100,
+    }]);
+    let mut scpi = Scpi::new(MED_CHAMBER, interlock);
+
+    // The rest of the message is dropped after the conflict
+    let (reply, commands) = process(&mut scpi, "POW 45;*OPC?", Levels(0));
+    assert_eq!((reply.as_str(), commands), ("", vec![]));
+    assert_eq!(errors(&mut scpi), [-221]);
+
+    // Within one message RF is already on for the power level
+    let mut reply = String::new();
+    let effect = scpi
+        .process("OUTP:RF ON;:POW 45", Levels(0), &mut reply)
+        .unwrap();
+    assert_eq!(effect.commands, [Command::Set(RF), Command::Set(DBM_45)]);
+    assert_eq!(effect.levels, Levels(0b0101));
+}
+
+#[test]
+fn error_queue() {
+    let mut scpi = scpi();
+    assert_eq!(query(&mut scpi, "SYST:ERR?", Levels(0)), "0,\"No error\"\n");
+
+    for message in [
+        "FOO",
+        "OUTP:RF MAYBE",
+        "OUTP:RF ON,OFF",
+        "*IDN? 1",
+        "OUTP::RF ON",
+    ] {
+        process(&mut scpi, message, Levels(0));
+    }
+    assert_eq!(query(&mut scpi, "SYST:ERR:COUN?", Levels(0)), "5\n");
+    assert_eq!(
+        query(&mut scpi, "SYSTem:ERRor:NEXT?;NEXT?", Levels(0)),
+        "-113,\"Undefined header\";-224,\"Illegal parameter value\"\n"
+    );
+    assert_eq!(errors(&mut scpi), [-108, -108, -102]);
+
+    for _ in 0..20 {
+        process(&mut scpi, "FOO", Levels(0));
+    }
+    let queued = errors(&mut scpi);
+    assert_eq!(queued.len(), scpi::ERROR_QUEUE_LEN);
+    assert_eq!(queued.last(), Some(&-350));
+
+    process(&mut scpi, "FOO", Levels(0));
+    process(&mut scpi, "*CLS", Levels(0));
+    assert_eq!(scpi.error_count(), 0);
+}
+
+#[test]
+fn limits() {
+    let mut scpi = scpi();
+    // The commands up to the limit are kept, the rest of the message is
+    // dropped
+    let message = [":OUTP:RF ON"; scpi::MAX_COMMANDS + 1].join(";");
+    let (_, commands) = process(&mut scpi, &message, Levels(0));
+    assert_eq!(commands, [Command::Set(RF); scpi::MAX_COMMANDS]);
+    assert_eq!(errors(&mut scpi), [-223]);
+
+    // Deeper than any header
+    process(&mut scpi, "A:B:C:D:E:F:G:H:I", Levels(0));
+    assert_eq!(errors(&mut scpi), [-113]);
+}
+
+#[test]
+fn without_instrument() {
+    let mut scpi = Scpi::new(NO_INSTRUMENT, Interlock::default());
+    process(&mut scpi, "OUTP:RF ON", Levels(0));
+    process(&mut scpi, "POW 45", Levels(0));
+    // The response ends before the failing query
+    assert_eq!(query(&mut scpi, "*OPC?;POW?;*OPC?", Levels(0)), "1\n");
+    assert_eq!(errors(&mut scpi), [-241, -241, -241]);
+}